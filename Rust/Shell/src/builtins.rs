@@ -1 +1,471 @@
-pub const BUILTINS: [&str; 6] = ["exit", "echo", "type", "pwd", "cd", "history"];
+use crate::completion::{bind_builtin, compgen_builtin, complete_builtin};
+use crate::conditional::test_builtin;
+use crate::directory::{cd_builtin, dirs_builtin, popd_builtin, pushd_builtin, pwd_builtin};
+use crate::hash::hash_builtin;
+use crate::history::{fc_builtin, history_builtin};
+use crate::jobs::{
+    bg_builtin, disown_builtin, fg_builtin, jobs_builtin, kill_builtin, wait_builtin,
+};
+use crate::options::{set_builtin, shopt_builtin};
+use crate::parser::{is_operator, split_words};
+use crate::pattern::fnmatch;
+use crate::printf::{echo_builtin, printf_builtin};
+use crate::read::read_builtin;
+use crate::shell::Shell;
+use crate::source::source_builtin;
+use crate::trap::trap_builtin;
+use crate::variables::{declare_builtin, export_builtin, readonly_builtin, unset_builtin};
+use std::collections::BTreeMap;
+use std::io::Write;
+use std::os::unix::io::RawFd;
+
+pub struct BuiltinIo<'a> {
+    pub stdin: RawFd,
+    pub stdout: &'a mut dyn Write,
+    pub stderr: &'a mut dyn Write,
+}
+
+pub trait Builtin: Sync {
+    fn name(&self) -> &'static str;
+    fn usage(&self) -> &'static str;
+    fn run(&self, args: &[String], shell: &mut Shell, io: &mut BuiltinIo) -> i32;
+}
+
+type BuiltinFn = fn(&[String], &mut Shell, &mut BuiltinIo) -> i32;
+
+struct FnBuiltin {
+    name: &'static str,
+    usage: &'static str,
+    run: BuiltinFn,
+}
+
+impl Builtin for FnBuiltin {
+    fn name(&self) -> &'static str {
+        self.name
+    }
+
+    fn usage(&self) -> &'static str {
+        self.usage
+    }
+
+    fn run(&self, args: &[String], shell: &mut Shell, io: &mut BuiltinIo) -> i32 {
+        (self.run)(args, shell, io)
+    }
+}
+
+static BUILTINS: [FnBuiltin; 38] = [
+    FnBuiltin {
+        name: "exit",
+        usage: "exit [n]",
+        run: exit_builtin,
+    },
+    FnBuiltin {
+        name: "return",
+        usage: "return [n]",
+        run: return_builtin,
+    },
+    FnBuiltin {
+        name: "echo",
+        usage: "echo [-neE] [arg ...]",
+        run: |args, _, io| echo_builtin(&args[1..], io.stdout, io.stderr),
+    },
+    FnBuiltin {
+        name: "type",
+        usage: "type name [name ...]",
+        run: type_builtin,
+    },
+    FnBuiltin {
+        name: "pwd",
+        usage: "pwd [-LP]",
+        run: |args, shell, io| pwd_builtin(&args[1..], shell, io.stdout, io.stderr),
+    },
+    FnBuiltin {
+        name: "cd",
+        usage: "cd [-L|-P] [dir]",
+        run: |args, shell, io| cd_builtin(&args[1..], shell, io.stdout, io.stderr),
+    },
+    FnBuiltin {
+        name: "history",
+        usage: "history [-c] [-d offset] [n] or history -anrw [filename] or history -ps arg [arg...]",
+        run: |args, shell, io| history_builtin(&args[1..], shell, io.stdout, io.stderr),
+    },
+    FnBuiltin {
+        name: "fc",
+        usage: "fc [-e ename] [-lnr] [first] [last] or fc -s [pat=rep] [command]",
+        run: |args, shell, io| fc_builtin(&args[1..], shell, io.stdout, io.stderr),
+    },
+    FnBuiltin {
+        name: "complete",
+        usage: "complete [-pr] [-DE] [-o option] [-A action] [-W wordlist] [-F function] [-C command] [name ...]",
+        run: |args, shell, io| complete_builtin(&args[1..], shell, io.stdout, io.stderr),
+    },
+    FnBuiltin {
+        name: "compgen",
+        usage: "compgen [-o option] [-A action] [-W wordlist] [-F function] [-C command] [word]",
+        run: |args, shell, io| compgen_builtin(&args[1..], shell, io.stdout, io.stderr),
+    },
+    FnBuiltin {
+        name: "alias",
+        usage: "alias [-p] [name[=value] ... ]",
+        run: |args, shell, io| alias_builtin(&args[1..], &mut shell.aliases, io.stdout, io.stderr),
+    },
+    FnBuiltin {
+        name: "unalias",
+        usage: "unalias [-a] name [name ...]",
+        run: |args, shell, io| unalias_builtin(&args[1..], &mut shell.aliases, io.stderr),
+    },
+    FnBuiltin {
+        name: "bind",
+        usage: "bind [-vV] [\"set variable value\"]",
+        run: |args, shell, io| bind_builtin(&args[1..], shell, io.stdout, io.stderr),
+    },
+    FnBuiltin {
+        name: "hash",
+        usage: "hash [-lr] [-p pathname] [-dt] [name ...]",
+        run: |args, shell, io| hash_builtin(&args[1..], shell, io.stdout, io.stderr),
+    },
+    FnBuiltin {
+        name: "export",
+        usage: "export [-n] [name[=value] ...] or export -p",
+        run: |args, shell, io| {
+            export_builtin(&args[1..], &mut shell.variables, io.stdout, io.stderr)
+        },
+    },
+    FnBuiltin {
+        name: "unset",
+        usage: "unset [-f] [-v] [name ...]",
+        run: |args, shell, io| unset_builtin(&args[1..], shell, io.stderr),
+    },
+    FnBuiltin {
+        name: "readonly",
+        usage: "readonly [-a] [name[=value] ...] or readonly -p",
+        run: |args, shell, io| {
+            readonly_builtin(&args[1..], &mut shell.variables, io.stdout, io.stderr)
+        },
+    },
+    FnBuiltin {
+        name: "declare",
+        usage: "declare [-ailrux] [-p] [name[=value] ...]",
+        run: |args, shell, io| {
+            declare_builtin(&args[1..], &mut shell.variables, io.stdout, io.stderr)
+        },
+    },
+    FnBuiltin {
+        name: "typeset",
+        usage: "typeset [-ailrux] [-p] [name[=value] ...]",
+        run: |args, shell, io| {
+            declare_builtin(&args[1..], &mut shell.variables, io.stdout, io.stderr)
+        },
+    },
+    FnBuiltin {
+        name: "pushd",
+        usage: "pushd [-n] [+N | -N | dir]",
+        run: |args, shell, io| pushd_builtin(&args[1..], shell, io.stdout, io.stderr),
+    },
+    FnBuiltin {
+        name: "popd",
+        usage: "popd [-n] [+N | -N]",
+        run: |args, shell, io| popd_builtin(&args[1..], shell, io.stdout, io.stderr),
+    },
+    FnBuiltin {
+        name: "dirs",
+        usage: "dirs [-clpv] [+N] [-N]",
+        run: |args, shell, io| dirs_builtin(&args[1..], shell, io.stdout, io.stderr),
+    },
+    FnBuiltin {
+        name: "test",
+        usage: "test [expr]",
+        run: |args, shell, io| test_builtin("test", &args[1..], &mut shell.variables, io.stderr),
+    },
+    FnBuiltin {
+        name: "[",
+        usage: "[ arg... ]",
+        run: |args, shell, io| test_builtin("[", &args[1..], &mut shell.variables, io.stderr),
+    },
+    FnBuiltin {
+        name: "read",
+        usage: "read [-ers] [-a array] [-d delim] [-n nchars] [-N nchars] [-p prompt] [-t timeout] [name ...]",
+        run: |args, shell, io| read_builtin(&args[1..], shell, io.stdin, io.stderr),
+    },
+    FnBuiltin {
+        name: "printf",
+        usage: "printf [-v var] format [arguments]",
+        run: |args, shell, io| {
+            printf_builtin(&args[1..], &mut shell.variables, io.stdout, io.stderr)
+        },
+    },
+    FnBuiltin {
+        name: "source",
+        usage: "source filename [arguments]",
+        run: |args, shell, io| source_builtin("source", &args[1..], shell, io.stderr),
+    },
+    FnBuiltin {
+        name: ".",
+        usage: ". filename [arguments]",
+        run: |args, shell, io| source_builtin(".", &args[1..], shell, io.stderr),
+    },
+    FnBuiltin {
+        name: "set",
+        usage: "set [-eufnvxC] [-o option-name] [--] [-] [arg ...]",
+        run: |args, shell, io| set_builtin(&args[1..], shell, io.stdout, io.stderr),
+    },
+    FnBuiltin {
+        name: "trap",
+        usage: "trap [-lp] [[arg] signal_spec ...]",
+        run: |args, shell, io| trap_builtin(&args[1..], shell, io.stdout, io.stderr),
+    },
+    FnBuiltin {
+        name: "jobs",
+        usage: "jobs [-lp] [jobspec ...]",
+        run: |args, shell, io| jobs_builtin(&args[1..], shell, io.stdout, io.stderr),
+    },
+    FnBuiltin {
+        name: "fg",
+        usage: "fg [job_spec]",
+        run: |args, shell, io| fg_builtin(&args[1..], shell, io.stdout, io.stderr),
+    },
+    FnBuiltin {
+        name: "bg",
+        usage: "bg [job_spec ...]",
+        run: |args, shell, io| bg_builtin(&args[1..], shell, io.stdout, io.stderr),
+    },
+    FnBuiltin {
+        name: "kill",
+        usage: "kill [-s sigspec | -n signum | -sigspec] pid | jobspec ... or kill -l [sigspec]",
+        run: |args, shell, io| kill_builtin(&args[1..], shell, io.stdout, io.stderr),
+    },
+    FnBuiltin {
+        name: "wait",
+        usage: "wait [-n] [id ...]",
+        run: |args, shell, io| wait_builtin(&args[1..], shell, io.stderr),
+    },
+    FnBuiltin {
+        name: "disown",
+        usage: "disown [-h] [-ar] [jobspec ... | pid ...]",
+        run: |args, shell, io| disown_builtin(&args[1..], shell, io.stderr),
+    },
+    FnBuiltin {
+        name: "shopt",
+        usage: "shopt [-pqsu] [optname ...]",
+        run: |args, shell, io| shopt_builtin(&args[1..], shell, io.stdout, io.stderr),
+    },
+    FnBuiltin {
+        name: "help",
+        usage: "help [pattern ...]",
+        run: help_builtin,
+    },
+];
+
+pub fn find_builtin(name: &str) -> Option<&'static dyn Builtin> {
+    BUILTINS
+        .iter()
+        .find(|builtin| builtin.name() == name)
+        .map(|builtin| builtin as &dyn Builtin)
+}
+
+pub fn is_builtin(name: &str) -> bool {
+    find_builtin(name).is_some()
+}
+
+pub fn builtin_names() -> impl Iterator<Item = &'static str> {
+    BUILTINS.iter().map(|builtin| builtin.name())
+}
+
+fn exit_builtin(args: &[String], shell: &mut Shell, io: &mut BuiltinIo) -> i32 {
+    if args.len() > 2 {
+        let _ = writeln!(io.stderr, "exit: too many arguments");
+        return 1;
+    }
+    if shell.jobs.exit_blocked(shell.interactive, io.stderr) {
+        return 1;
+    }
+    shell.exiting = true;
+    match args.get(1) {
+        Some(arg) => match arg.parse::<i64>() {
+            Ok(status) => (status & 0xff) as i32,
+            Err(_) => {
+                let _ = writeln!(io.stderr, "exit: {}: numeric argument required", arg);
+                2
+            }
+        },
+        None => shell.variables.status(),
+    }
+}
+
+fn return_builtin(args: &[String], shell: &mut Shell, io: &mut BuiltinIo) -> i32 {
+    if shell.call_depth == 0 {
+        let _ = writeln!(
+            io.stderr,
+            "return: can only `return' from a function or sourced script"
+        );
+        return 1;
+    }
+    shell.returning = true;
+    match args.get(1) {
+        Some(arg) => match arg.parse::<i64>() {
+            Ok(status) => (status & 0xff) as i32,
+            Err(_) => {
+                let _ = writeln!(io.stderr, "return: {}: numeric argument required", arg);
+                2
+            }
+        },
+        None => shell.variables.status(),
+    }
+}
+
+fn type_builtin(args: &[String], shell: &mut Shell, io: &mut BuiltinIo) -> i32 {
+    let path_var = shell.variables.get("PATH").unwrap_or_default().to_string();
+    let mut status = 0;
+    for arg in &args[1..] {
+        let _ = if let Some(value) = shell.aliases.get(arg) {
+            writeln!(io.stdout, "{} is aliased to `{}'", arg, value)
+        } else if KEYWORDS.contains(&arg.as_str()) {
+            writeln!(io.stdout, "{} is a shell keyword", arg)
+        } else if let Some(body) = shell.functions.get(arg) {
+            writeln!(
+                io.stdout,
+                "{} is a function\n{} () {}",
+                arg,
+                arg,
+                body.join(" ")
+            )
+        } else if is_builtin(arg) {
+            writeln!(io.stdout, "{} is a shell builtin", arg)
+        } else {
+            match shell.command_hash.lookup(&path_var, arg) {
+                Some(path) => writeln!(io.stdout, "{} is {}", arg, path.display()),
+                None => {
+                    status = 1;
+                    writeln!(io.stdout, "{}: not found", arg)
+                }
+            }
+        };
+    }
+    status
+}
+
+fn help_builtin(args: &[String], _: &mut Shell, io: &mut BuiltinIo) -> i32 {
+    let patterns = &args[1..];
+    if patterns.is_empty() {
+        for builtin in &BUILTINS {
+            let _ = writeln!(io.stdout, "{}", builtin.usage());
+        }
+        return 0;
+    }
+
+    let mut status = 0;
+    for pattern in patterns {
+        let mut matched = false;
+        for builtin in BUILTINS.iter().filter(|b| fnmatch(pattern, b.name())) {
+            let _ = writeln!(io.stdout, "{}: {}", builtin.name(), builtin.usage());
+            matched = true;
+        }
+        if !matched {
+            let _ = writeln!(io.stderr, "help: no help topics match `{}'.", pattern);
+            status = 1;
+        }
+    }
+    status
+}
+
+pub const KEYWORDS: [&str; 22] = [
+    "!", "[[", "]]", "case", "coproc", "do", "done", "elif", "else", "esac", "fi", "for",
+    "function", "if", "in", "select", "then", "time", "until", "while", "{", "}",
+];
+
+fn quote_alias(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "'\\''"))
+}
+
+pub fn alias_builtin(
+    args: &[String],
+    aliases: &mut BTreeMap<String, String>,
+    out: &mut dyn Write,
+    err: &mut dyn Write,
+) -> i32 {
+    let names: Vec<&String> = args.iter().filter(|arg| arg.as_str() != "-p").collect();
+
+    if names.is_empty() {
+        for (name, value) in aliases.iter() {
+            let _ = writeln!(out, "alias {}={}", name, quote_alias(value));
+        }
+        return 0;
+    }
+
+    let mut status = 0;
+    for arg in names {
+        match arg.split_once('=') {
+            Some((name, value)) if !name.is_empty() => {
+                aliases.insert(name.to_string(), value.to_string());
+            }
+            _ => match aliases.get(arg) {
+                Some(value) => {
+                    let _ = writeln!(out, "alias {}={}", arg, quote_alias(value));
+                }
+                None => {
+                    let _ = writeln!(err, "alias: {}: not found", arg);
+                    status = 1;
+                }
+            },
+        }
+    }
+    status
+}
+
+pub fn unalias_builtin(
+    args: &[String],
+    aliases: &mut BTreeMap<String, String>,
+    err: &mut dyn Write,
+) -> i32 {
+    if args.is_empty() {
+        let _ = writeln!(err, "unalias: usage: unalias [-a] name [name ...]");
+        return 2;
+    }
+
+    let mut status = 0;
+    for arg in args {
+        if arg == "-a" {
+            aliases.clear();
+        } else if aliases.remove(arg).is_none() {
+            let _ = writeln!(err, "unalias: {}: not found", arg);
+            status = 1;
+        }
+    }
+    status
+}
+
+pub fn expand_aliases(args: Vec<String>, aliases: &BTreeMap<String, String>) -> Vec<String> {
+    let mut expanded = Vec::new();
+    let mut check_next = true;
+
+    for arg in args {
+        if is_operator(&arg) || arg == "{" {
+            expanded.push(arg);
+            check_next = true;
+            continue;
+        }
+
+        match aliases.get(&arg).filter(|_| check_next) {
+            Some(value) => {
+                let mut seen = vec![arg.clone()];
+                let mut words = split_words(value);
+                while let Some(first) = words.first().cloned() {
+                    match aliases.get(&first).filter(|_| !seen.contains(&first)) {
+                        Some(inner) => {
+                            seen.push(first);
+                            words.splice(0..1, split_words(inner));
+                        }
+                        None => break,
+                    }
+                }
+                expanded.extend(words);
+                check_next = value.ends_with(' ');
+            }
+            None => {
+                expanded.push(arg);
+                check_next = false;
+            }
+        }
+    }
+    expanded
+}
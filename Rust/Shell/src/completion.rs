@@ -0,0 +1,1291 @@
+use crate::builtins::{KEYWORDS, builtin_names, is_builtin};
+use crate::executor::call_function;
+use crate::hash::is_executable;
+use crate::parser::{expand_tilde_prefix, parse_input};
+use crate::pattern::{fnmatch, glob};
+use crate::shell::Shell;
+use crate::trap::SIGNALS;
+use crate::variables::Variables;
+use std::collections::{HashMap, HashSet};
+use std::ffi::CStr;
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+const SPEC_OPTIONS: [&str; 8] = [
+    "bashdefault",
+    "default",
+    "dirnames",
+    "filenames",
+    "noquote",
+    "nosort",
+    "nospace",
+    "plusdirs",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CandidateKind {
+    Builtin,
+    Alias,
+    Command,
+    File,
+    Directory,
+    Word,
+}
+
+#[derive(Debug, Clone)]
+pub struct Candidate {
+    pub text: String,
+    pub display: String,
+    pub kind: CandidateKind,
+}
+
+pub struct Completion {
+    pub start: usize,
+    pub quote: Option<char>,
+    pub candidates: Vec<Candidate>,
+    pub no_space: bool,
+    pub no_quote: bool,
+    pub matcher: Matcher,
+}
+
+impl Completion {
+    pub fn escape(&self, text: &str) -> String {
+        if self.no_quote {
+            return self.quote.into_iter().chain(text.chars()).collect();
+        }
+        escape_completion(text, self.quote)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MatchMode {
+    Prefix,
+    Substring,
+    Fuzzy,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct CompletionSettings {
+    pub ignore_case: bool,
+    pub map_case: bool,
+    pub matching: MatchMode,
+    pub query_items: usize,
+}
+
+impl Default for CompletionSettings {
+    fn default() -> Self {
+        CompletionSettings {
+            ignore_case: false,
+            map_case: false,
+            matching: MatchMode::Prefix,
+            query_items: 100,
+        }
+    }
+}
+
+pub struct Matcher {
+    word: String,
+    settings: CompletionSettings,
+}
+
+struct WordContext {
+    start: usize,
+    quote: Option<char>,
+    word: String,
+    words: Vec<String>,
+    command_position: bool,
+    after_redirect: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CompletionAction {
+    Alias,
+    Builtin,
+    Command,
+    Directory,
+    Export,
+    File,
+    Function,
+    Group,
+    Hostname,
+    Job,
+    Keyword,
+    Signal,
+    User,
+    Variable,
+}
+
+const ACTIONS: [(&str, Option<char>, CompletionAction); 14] = [
+    ("alias", Some('a'), CompletionAction::Alias),
+    ("builtin", Some('b'), CompletionAction::Builtin),
+    ("command", Some('c'), CompletionAction::Command),
+    ("directory", Some('d'), CompletionAction::Directory),
+    ("export", Some('e'), CompletionAction::Export),
+    ("file", Some('f'), CompletionAction::File),
+    ("function", None, CompletionAction::Function),
+    ("group", Some('g'), CompletionAction::Group),
+    ("hostname", None, CompletionAction::Hostname),
+    ("job", Some('j'), CompletionAction::Job),
+    ("keyword", Some('k'), CompletionAction::Keyword),
+    ("signal", None, CompletionAction::Signal),
+    ("user", Some('u'), CompletionAction::User),
+    ("variable", Some('v'), CompletionAction::Variable),
+];
+
+#[derive(Debug, Clone, Default)]
+pub struct CompletionSpec {
+    actions: Vec<CompletionAction>,
+    options: Vec<&'static str>,
+    glob: Option<String>,
+    wordlist: Option<String>,
+    function: Option<String>,
+    command: Option<String>,
+    filter: Option<String>,
+    prefix: String,
+    suffix: String,
+}
+
+#[derive(Default)]
+pub struct CompletionSpecs {
+    commands: HashMap<String, CompletionSpec>,
+    default: Option<CompletionSpec>,
+    empty: Option<CompletionSpec>,
+}
+
+struct CompletionRequest<'a> {
+    line: &'a str,
+    words: Vec<String>,
+    command: &'a str,
+    word: &'a str,
+    previous: &'a str,
+    matcher: Matcher,
+}
+
+impl Matcher {
+    pub fn new(word: &str, settings: CompletionSettings) -> Self {
+        let mut matcher = Matcher {
+            word: String::new(),
+            settings,
+        };
+        matcher.word = matcher.normalize(word);
+        matcher
+    }
+
+    fn exact(word: &str) -> Self {
+        Matcher {
+            word: word.to_string(),
+            settings: CompletionSettings::default(),
+        }
+    }
+
+    fn normalize_char(&self, c: char) -> char {
+        if !self.settings.ignore_case {
+            return c;
+        }
+        let c = c.to_lowercase().next().unwrap_or(c);
+        if self.settings.map_case && c == '_' {
+            '-'
+        } else {
+            c
+        }
+    }
+
+    fn normalize(&self, text: &str) -> String {
+        text.chars().map(|c| self.normalize_char(c)).collect()
+    }
+
+    fn is_literal_prefix(&self) -> bool {
+        self.settings.matching == MatchMode::Prefix && !self.settings.ignore_case
+    }
+
+    pub fn extends(&self, text: &str) -> bool {
+        let text = self.normalize(text);
+        text.len() > self.word.len() && text.starts_with(&self.word)
+    }
+
+    pub fn score(&self, candidate: &str) -> Option<i32> {
+        let candidate = self.normalize(candidate);
+        if candidate.starts_with(&self.word) {
+            return Some(3000);
+        }
+
+        match self.settings.matching {
+            MatchMode::Prefix => None,
+            MatchMode::Substring => candidate
+                .find(&self.word)
+                .map(|position| 2000 - position.min(999) as i32),
+            MatchMode::Fuzzy => match candidate.find(&self.word) {
+                Some(position) => Some(2000 - position.min(999) as i32),
+                None => fuzzy_score(&candidate, &self.word).map(|score| 1000 + score),
+            },
+        }
+    }
+}
+
+fn fuzzy_score(candidate: &str, word: &str) -> Option<i32> {
+    let candidate: Vec<char> = candidate.chars().collect();
+    let mut score = 0i32;
+    let mut index = 0;
+    let mut last_match: Option<usize> = None;
+
+    for wanted in word.chars() {
+        while candidate.get(index) != Some(&wanted) {
+            index += 1;
+            if index >= candidate.len() {
+                return None;
+            }
+        }
+
+        match last_match {
+            Some(last) if last + 1 == index => score += 8,
+            Some(last) => score -= (index - last - 1) as i32,
+            None => score -= index as i32,
+        }
+        if index == 0 || "-_./ ".contains(candidate[index - 1]) {
+            score += 5;
+        }
+        last_match = Some(index);
+        index += 1;
+    }
+
+    Some(score.clamp(-999, 999))
+}
+
+fn is_word_break(c: char) -> bool {
+    c.is_whitespace() || "|;&<>()".contains(c)
+}
+
+fn scan_current_word(buffer: &str) -> WordContext {
+    let mut start = 0;
+    let mut word = String::new();
+    let mut words = Vec::new();
+    let mut quote: Option<char> = None;
+    let mut escaped = false;
+    let mut command_position = true;
+    let mut after_redirect = false;
+    let mut word_has_text = false;
+
+    for (i, c) in buffer.char_indices() {
+        if escaped {
+            word.push(c);
+            escaped = false;
+        } else if let Some(q) = quote {
+            if c == q {
+                quote = None;
+            } else if q == '"' && c == '\\' {
+                escaped = true;
+            } else {
+                word.push(c);
+            }
+        } else if c == '\\' {
+            escaped = true;
+            word_has_text = true;
+        } else if c == '\'' || c == '"' {
+            quote = Some(c);
+            word_has_text = true;
+        } else if is_word_break(c) {
+            if word_has_text {
+                if after_redirect {
+                    after_redirect = false;
+                } else {
+                    words.push(std::mem::take(&mut word));
+                }
+                command_position = false;
+            }
+            if "|;&(".contains(c) {
+                command_position = true;
+                after_redirect = false;
+                words.clear();
+            } else if "<>".contains(c) {
+                command_position = false;
+                after_redirect = true;
+            }
+            word.clear();
+            word_has_text = false;
+            start = i + c.len_utf8();
+        } else {
+            word.push(c);
+            word_has_text = true;
+        }
+    }
+
+    WordContext {
+        start,
+        quote,
+        word,
+        words,
+        command_position,
+        after_redirect,
+    }
+}
+
+fn user_directory_candidates(user: &str, settings: CompletionSettings) -> Vec<Candidate> {
+    let matcher = Matcher::new(user, settings);
+    let mut candidates: Vec<Candidate> = system_users()
+        .into_iter()
+        .filter(|name| matcher.score(name).is_some())
+        .map(|name| Candidate {
+            text: format!("~{}/", name),
+            display: format!("~{}/", name),
+            kind: CandidateKind::Directory,
+        })
+        .collect();
+    candidates.sort_by(|a, b| a.text.cmp(&b.text));
+    candidates.dedup_by(|a, b| a.text == b.text);
+    candidates
+}
+
+pub fn find_file_completions(
+    word: &str,
+    variables: &Variables,
+    executables_only: bool,
+    settings: CompletionSettings,
+) -> Vec<Candidate> {
+    if let Some(user) = word.strip_prefix('~')
+        && !user.is_empty()
+        && !user.contains('/')
+    {
+        return user_directory_candidates(user, settings);
+    }
+
+    let (dir_part, name_part) = match word.rfind('/') {
+        Some(index) => word.split_at(index + 1),
+        None if word == "~" => ("~/", ""),
+        None => ("", word),
+    };
+
+    let search_dir = if dir_part.is_empty() {
+        ".".to_string()
+    } else {
+        expand_tilde_prefix(dir_part, variables)
+    };
+
+    let Ok(entries) = fs::read_dir(&search_dir) else {
+        return Vec::new();
+    };
+
+    let matcher = Matcher::new(name_part, settings);
+    let mut candidates = Vec::new();
+    for entry in entries.flatten() {
+        let name = entry.file_name().to_string_lossy().to_string();
+        if matcher.score(&name).is_none() || (name.starts_with('.') && !name_part.starts_with('.'))
+        {
+            continue;
+        }
+
+        let Ok(metadata) = fs::metadata(entry.path()) else {
+            continue;
+        };
+
+        if metadata.is_dir() {
+            candidates.push(Candidate {
+                text: format!("{}{}/", dir_part, name),
+                display: format!("{}/", name),
+                kind: CandidateKind::Directory,
+            });
+        } else if !executables_only || is_executable(&metadata) {
+            candidates.push(Candidate {
+                text: format!("{}{}", dir_part, name),
+                display: name,
+                kind: CandidateKind::File,
+            });
+        }
+    }
+    candidates.sort_by(|a, b| a.text.cmp(&b.text));
+    candidates
+}
+
+pub fn find_completions(matcher: &Matcher, shell: &mut Shell) -> Vec<String> {
+    let mut candidates: HashSet<String> = builtin_names()
+        .filter(|builtin| matcher.score(builtin).is_some())
+        .map(|builtin| builtin.to_string())
+        .collect();
+
+    let path_var = shell.variables.get("PATH").unwrap_or_default().to_string();
+    let prefix = if matcher.is_literal_prefix() {
+        matcher.word.as_str()
+    } else {
+        ""
+    };
+    candidates.extend(
+        shell
+            .command_hash
+            .executables_with_prefix(&path_var, prefix)
+            .into_iter()
+            .filter(|name| matcher.score(name).is_some()),
+    );
+
+    let mut results: Vec<String> = candidates.into_iter().collect();
+    results.sort();
+    results
+}
+
+fn command_candidates(matcher: &Matcher, shell: &mut Shell) -> Vec<Candidate> {
+    let mut candidates: Vec<Candidate> = shell
+        .aliases
+        .keys()
+        .filter(|name| matcher.score(name).is_some())
+        .map(|name| Candidate {
+            text: name.clone(),
+            display: name.clone(),
+            kind: CandidateKind::Alias,
+        })
+        .collect();
+
+    for name in find_completions(matcher, shell) {
+        if shell.aliases.contains_key(&name) {
+            continue;
+        }
+        candidates.push(Candidate {
+            kind: if is_builtin(&name) {
+                CandidateKind::Builtin
+            } else {
+                CandidateKind::Command
+            },
+            display: name.clone(),
+            text: name,
+        });
+    }
+    candidates.sort_by(|a, b| a.text.cmp(&b.text));
+    candidates
+}
+
+fn lookup_spec<'a>(specs: &'a CompletionSpecs, command: &str) -> Option<&'a CompletionSpec> {
+    let basename = command.rsplit('/').next().unwrap_or(command);
+    specs
+        .commands
+        .get(command)
+        .or_else(|| specs.commands.get(basename))
+        .or(specs.default.as_ref())
+}
+
+pub fn complete_line(buffer: &str, shell: &mut Shell) -> Completion {
+    let context = scan_current_word(buffer);
+    let settings = shell.completion_settings;
+    let mut no_space = false;
+    let mut no_quote = false;
+    let mut no_sort = false;
+
+    let mut candidates = if context.command_position && !context.word.contains('/') {
+        match shell.completion_specs.empty.clone() {
+            Some(spec) if buffer.trim().is_empty() => {
+                let request = CompletionRequest {
+                    line: buffer,
+                    words: vec![context.word.clone()],
+                    command: "",
+                    word: &context.word,
+                    previous: "",
+                    matcher: Matcher::new(&context.word, settings),
+                };
+                let (candidates, spec_no_space, spec_no_quote) =
+                    generate_from_spec(&spec, &request, shell);
+                no_space = spec_no_space;
+                no_quote = spec_no_quote;
+                no_sort = spec.options.contains(&"nosort");
+                candidates
+            }
+            _ => command_candidates(&Matcher::new(&context.word, settings), shell),
+        }
+    } else if let Some(spec) = context
+        .words
+        .first()
+        .filter(|_| !context.after_redirect)
+        .and_then(|command| lookup_spec(&shell.completion_specs, command).cloned())
+    {
+        let request = CompletionRequest {
+            line: buffer,
+            words: context
+                .words
+                .iter()
+                .chain([&context.word])
+                .cloned()
+                .collect(),
+            command: &context.words[0],
+            word: &context.word,
+            previous: context.words.last().map(|s| s.as_str()).unwrap_or(""),
+            matcher: Matcher::new(&context.word, settings),
+        };
+        let (candidates, spec_no_space, spec_no_quote) = generate_from_spec(&spec, &request, shell);
+        no_space = spec_no_space;
+        no_quote = spec_no_quote;
+        no_sort = spec.options.contains(&"nosort");
+        candidates
+    } else {
+        find_file_completions(
+            &context.word,
+            &shell.variables,
+            context.command_position,
+            settings,
+        )
+    };
+
+    if !no_sort {
+        let name_part = context.word.rsplit('/').next().unwrap_or("");
+        let ranking = Matcher::new(name_part, settings);
+        candidates.sort_by_cached_key(|candidate| {
+            -ranking
+                .score(candidate.display.trim_end_matches('/'))
+                .unwrap_or(0)
+        });
+    }
+
+    Completion {
+        start: context.start,
+        quote: context.quote,
+        matcher: Matcher::new(&context.word, settings),
+        candidates,
+        no_space,
+        no_quote,
+    }
+}
+
+fn word_candidates(names: impl IntoIterator<Item = String>, matcher: &Matcher) -> Vec<Candidate> {
+    names
+        .into_iter()
+        .filter(|name| matcher.score(name).is_some())
+        .map(|name| Candidate {
+            display: name.clone(),
+            text: name,
+            kind: CandidateKind::Word,
+        })
+        .collect()
+}
+
+fn system_users() -> Vec<String> {
+    let mut users = Vec::new();
+    unsafe {
+        libc::setpwent();
+        loop {
+            let entry = libc::getpwent();
+            if entry.is_null() {
+                break;
+            }
+            users.push(
+                CStr::from_ptr((*entry).pw_name)
+                    .to_string_lossy()
+                    .into_owned(),
+            );
+        }
+        libc::endpwent();
+    }
+    users
+}
+
+fn system_groups() -> Vec<String> {
+    let mut groups = Vec::new();
+    unsafe {
+        libc::setgrent();
+        loop {
+            let entry = libc::getgrent();
+            if entry.is_null() {
+                break;
+            }
+            groups.push(
+                CStr::from_ptr((*entry).gr_name)
+                    .to_string_lossy()
+                    .into_owned(),
+            );
+        }
+        libc::endgrent();
+    }
+    groups
+}
+
+fn system_hostnames() -> Vec<String> {
+    let contents = fs::read_to_string("/etc/hosts").unwrap_or_default();
+    contents
+        .lines()
+        .map(|line| line.split('#').next().unwrap_or(""))
+        .flat_map(|line| line.split_whitespace().skip(1).map(|s| s.to_string()))
+        .collect()
+}
+
+fn action_candidates(
+    action: CompletionAction,
+    request: &CompletionRequest,
+    shell: &mut Shell,
+) -> Vec<Candidate> {
+    let matcher = &request.matcher;
+    let settings = matcher.settings;
+    let word = request.word;
+    match action {
+        CompletionAction::Alias => word_candidates(shell.aliases.keys().cloned(), matcher),
+        CompletionAction::Builtin => {
+            word_candidates(builtin_names().map(|name| name.to_string()), matcher)
+        }
+        CompletionAction::Command => command_candidates(matcher, shell),
+        CompletionAction::Directory => {
+            find_file_completions(word, &shell.variables, false, settings)
+                .into_iter()
+                .filter(|candidate| candidate.kind == CandidateKind::Directory)
+                .collect()
+        }
+        CompletionAction::File => find_file_completions(word, &shell.variables, false, settings),
+        CompletionAction::Function => word_candidates(shell.functions.keys().cloned(), matcher),
+        CompletionAction::Export => {
+            word_candidates(shell.variables.exported_names().cloned(), matcher)
+        }
+        CompletionAction::Variable => word_candidates(shell.variables.names().cloned(), matcher),
+        CompletionAction::Job => word_candidates(shell.jobs.names(), matcher),
+        CompletionAction::Group => word_candidates(system_groups(), matcher),
+        CompletionAction::Hostname => word_candidates(system_hostnames(), matcher),
+        CompletionAction::Keyword => {
+            word_candidates(KEYWORDS.iter().map(|name| name.to_string()), matcher)
+        }
+        CompletionAction::Signal => {
+            word_candidates(SIGNALS.iter().map(|name| name.to_string()), matcher)
+        }
+        CompletionAction::User => word_candidates(system_users(), matcher),
+    }
+}
+
+fn run_completion_command(
+    command: &str,
+    request: &CompletionRequest,
+    variables: &Variables,
+) -> Vec<Candidate> {
+    let mut words = parse_input(command).into_iter();
+    let Some(program) = words.next() else {
+        return Vec::new();
+    };
+
+    let output = Command::new(program)
+        .args(words)
+        .args([request.command, request.word, request.previous])
+        .env_clear()
+        .envs(variables.exported())
+        .env("COMP_LINE", request.line)
+        .env("COMP_POINT", request.line.len().to_string())
+        .env("COMP_WORDS", request.words.join(" "))
+        .env("COMP_CWORD", (request.words.len() - 1).to_string())
+        .env("COMP_TYPE", "9")
+        .env("COMP_KEY", "9")
+        .stdin(Stdio::null())
+        .stderr(Stdio::null())
+        .output();
+
+    match output {
+        Ok(output) => word_candidates(
+            String::from_utf8_lossy(&output.stdout)
+                .lines()
+                .map(|line| line.to_string())
+                .collect::<Vec<_>>(),
+            &Matcher::exact(""),
+        ),
+        Err(_) => Vec::new(),
+    }
+}
+
+fn run_completion_function(
+    name: &str,
+    request: &CompletionRequest,
+    shell: &mut Shell,
+) -> Vec<Candidate> {
+    let names = [
+        "COMP_WORDS",
+        "COMP_CWORD",
+        "COMP_LINE",
+        "COMP_POINT",
+        "COMPREPLY",
+    ];
+    let saved: Vec<_> = names
+        .iter()
+        .map(|name| shell.variables.snapshot(name))
+        .collect();
+    let _ = shell
+        .variables
+        .set_array("COMP_WORDS", request.words.clone());
+    let _ = shell
+        .variables
+        .set("COMP_CWORD", &(request.words.len() - 1).to_string());
+    let _ = shell.variables.set("COMP_LINE", request.line);
+    let _ = shell
+        .variables
+        .set("COMP_POINT", &request.line.len().to_string());
+    let _ = shell.variables.unset("COMPREPLY");
+
+    let args = [request.command, request.word, request.previous].map(|arg| arg.to_string());
+    let status = shell.variables.status();
+    let replies = match call_function(name, &args, shell) {
+        Some(_) => shell
+            .variables
+            .get_array("COMPREPLY")
+            .map(|replies| replies.to_vec())
+            .unwrap_or_default(),
+        None => Vec::new(),
+    };
+    shell.variables.set_status(status);
+
+    for (name, snapshot) in names.iter().zip(saved) {
+        shell.variables.restore(name, snapshot);
+    }
+    word_candidates(replies, &Matcher::exact(""))
+}
+
+fn generate_from_spec(
+    spec: &CompletionSpec,
+    request: &CompletionRequest,
+    shell: &mut Shell,
+) -> (Vec<Candidate>, bool, bool) {
+    let word = request.word;
+    let mut candidates = Vec::new();
+
+    for &action in &spec.actions {
+        candidates.extend(action_candidates(action, request, shell));
+    }
+
+    if let Some(pattern) = &spec.glob {
+        candidates.extend(word_candidates(glob(pattern), &request.matcher));
+    }
+
+    if let Some(wordlist) = &spec.wordlist {
+        candidates.extend(word_candidates(
+            wordlist.split_whitespace().map(|s| s.to_string()),
+            &request.matcher,
+        ));
+    }
+
+    if let Some(function) = &spec.function {
+        candidates.extend(run_completion_function(function, request, shell));
+    }
+
+    if let Some(command) = &spec.command {
+        candidates.extend(run_completion_command(command, request, &shell.variables));
+    }
+
+    if let Some(filter) = &spec.filter {
+        let (negate, pattern) = match filter.strip_prefix('!') {
+            Some(pattern) => (true, pattern),
+            None => (false, filter.as_str()),
+        };
+        let pattern = pattern
+            .replace("\\&", "\u{0}")
+            .replace('&', word)
+            .replace('\u{0}', "&");
+        candidates.retain(|candidate| fnmatch(&pattern, &candidate.text) == negate);
+    }
+
+    for candidate in candidates.iter_mut() {
+        candidate.text = format!("{}{}{}", spec.prefix, candidate.text, spec.suffix);
+    }
+
+    let has_option = |name: &str| spec.options.contains(&name);
+    if has_option("filenames") {
+        for candidate in candidates.iter_mut() {
+            if candidate.kind == CandidateKind::Word && Path::new(&candidate.text).is_dir() {
+                candidate.kind = CandidateKind::Directory;
+                candidate.text.push('/');
+                candidate.display.push('/');
+            }
+        }
+    }
+    if has_option("plusdirs") || (candidates.is_empty() && has_option("dirnames")) {
+        candidates.extend(action_candidates(
+            CompletionAction::Directory,
+            request,
+            shell,
+        ));
+    }
+    if candidates.is_empty() && (has_option("default") || has_option("bashdefault")) {
+        candidates = find_file_completions(word, &shell.variables, false, request.matcher.settings);
+    }
+
+    if !has_option("nosort") {
+        candidates.sort_by(|a, b| a.text.cmp(&b.text));
+    }
+    let mut seen = HashSet::new();
+    candidates.retain(|candidate| seen.insert(candidate.text.clone()));
+
+    (candidates, has_option("nospace"), has_option("noquote"))
+}
+
+#[derive(Default)]
+struct SpecArgs {
+    spec: CompletionSpec,
+    print: bool,
+    remove: bool,
+    default: bool,
+    empty: bool,
+    operands: Vec<String>,
+}
+
+fn parse_spec_args(builtin: &str, args: &[String], err: &mut dyn Write) -> Option<SpecArgs> {
+    let mut parsed = SpecArgs::default();
+    let mut index = 0;
+
+    while index < args.len() {
+        let arg = &args[index];
+        index += 1;
+
+        if arg == "--" {
+            parsed.operands.extend(args[index..].iter().cloned());
+            break;
+        }
+        if !arg.starts_with('-') || arg.len() == 1 {
+            parsed.operands.push(arg.clone());
+            continue;
+        }
+
+        let flags: Vec<char> = arg[1..].chars().collect();
+        let mut flag_index = 0;
+        while flag_index < flags.len() {
+            let flag = flags[flag_index];
+            flag_index += 1;
+
+            if let Some(&(_, _, action)) = ACTIONS.iter().find(|(_, short, _)| *short == Some(flag))
+            {
+                parsed.spec.actions.push(action);
+                continue;
+            }
+
+            match flag {
+                'p' => parsed.print = true,
+                'r' => parsed.remove = true,
+                'D' => parsed.default = true,
+                'E' => parsed.empty = true,
+                'A' | 'o' | 'G' | 'W' | 'F' | 'C' | 'X' | 'P' | 'S' => {
+                    let value = if flag_index < flags.len() {
+                        let value: String = flags[flag_index..].iter().collect();
+                        flag_index = flags.len();
+                        value
+                    } else if index < args.len() {
+                        index += 1;
+                        args[index - 1].clone()
+                    } else {
+                        let _ =
+                            writeln!(err, "{}: -{}: option requires an argument", builtin, flag);
+                        return None;
+                    };
+
+                    match flag {
+                        'A' => match ACTIONS.iter().find(|(name, _, _)| *name == value) {
+                            Some(&(_, _, action)) => parsed.spec.actions.push(action),
+                            None => {
+                                let _ =
+                                    writeln!(err, "{}: {}: invalid action name", builtin, value);
+                                return None;
+                            }
+                        },
+                        'o' => match SPEC_OPTIONS.iter().find(|&&name| name == value) {
+                            Some(&name) => parsed.spec.options.push(name),
+                            None => {
+                                let _ =
+                                    writeln!(err, "{}: {}: invalid option name", builtin, value);
+                                return None;
+                            }
+                        },
+                        'G' => parsed.spec.glob = Some(value),
+                        'W' => parsed.spec.wordlist = Some(value),
+                        'F' => parsed.spec.function = Some(value),
+                        'C' => parsed.spec.command = Some(value),
+                        'X' => parsed.spec.filter = Some(value),
+                        'P' => parsed.spec.prefix = value,
+                        _ => parsed.spec.suffix = value,
+                    }
+                }
+                _ => {
+                    let _ = writeln!(err, "{}: -{}: invalid option", builtin, flag);
+                    let _ = writeln!(
+                        err,
+                        "{}: usage: {} [-abcdefgjksuv] [-pr] [-DE] [-o option] [-A action] [-G globpat] [-W wordlist] [-F function] [-C command] [-X filterpat] [-P prefix] [-S suffix] [name ...]",
+                        builtin, builtin
+                    );
+                    return None;
+                }
+            }
+        }
+    }
+    Some(parsed)
+}
+
+fn quote_spec_value(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "'\\''"))
+}
+
+fn format_spec(spec: &CompletionSpec, target: &str) -> String {
+    let mut line = String::from("complete");
+    for option in &spec.options {
+        line.push_str(&format!(" -o {}", option));
+    }
+    for action in &spec.actions {
+        match ACTIONS.iter().find(|(_, _, a)| a == action) {
+            Some((_, Some(short), _)) => line.push_str(&format!(" -{}", short)),
+            Some((name, None, _)) => line.push_str(&format!(" -A {}", name)),
+            None => {}
+        }
+    }
+    let values = [
+        ('G', &spec.glob),
+        ('W', &spec.wordlist),
+        ('F', &spec.function),
+        ('X', &spec.filter),
+        ('C', &spec.command),
+    ];
+    for (flag, value) in values {
+        if let Some(value) = value {
+            line.push_str(&format!(" -{} {}", flag, quote_spec_value(value)));
+        }
+    }
+    if !spec.prefix.is_empty() {
+        line.push_str(&format!(" -P {}", quote_spec_value(&spec.prefix)));
+    }
+    if !spec.suffix.is_empty() {
+        line.push_str(&format!(" -S {}", quote_spec_value(&spec.suffix)));
+    }
+    line.push(' ');
+    line.push_str(target);
+    line
+}
+
+pub fn complete_builtin(
+    args: &[String],
+    shell: &mut Shell,
+    out: &mut dyn Write,
+    err: &mut dyn Write,
+) -> i32 {
+    let Some(parsed) = parse_spec_args("complete", args, err) else {
+        return 1;
+    };
+    let specs = &mut shell.completion_specs;
+
+    if parsed.remove {
+        if parsed.default {
+            specs.default = None;
+        }
+        if parsed.empty {
+            specs.empty = None;
+        }
+        if parsed.operands.is_empty() && !parsed.default && !parsed.empty {
+            specs.commands.clear();
+        }
+        let mut status = 0;
+        for name in &parsed.operands {
+            if specs.commands.remove(name).is_none() {
+                let _ = writeln!(err, "complete: {}: no completion specification", name);
+                status = 1;
+            }
+        }
+        return status;
+    }
+
+    let defines_nothing = !parsed.default && !parsed.empty && parsed.operands.is_empty();
+    if parsed.print || defines_nothing {
+        if let Some(spec) = specs
+            .default
+            .as_ref()
+            .filter(|_| parsed.default || defines_nothing)
+        {
+            let _ = writeln!(out, "{}", format_spec(spec, "-D"));
+        }
+        if let Some(spec) = specs
+            .empty
+            .as_ref()
+            .filter(|_| parsed.empty || defines_nothing)
+        {
+            let _ = writeln!(out, "{}", format_spec(spec, "-E"));
+        }
+
+        let mut names: Vec<&String> = if parsed.operands.is_empty() && defines_nothing {
+            specs.commands.keys().collect()
+        } else {
+            parsed.operands.iter().collect()
+        };
+        names.sort();
+        let mut status = 0;
+        for name in names {
+            match specs.commands.get(name) {
+                Some(spec) => {
+                    let _ = writeln!(out, "{}", format_spec(spec, name));
+                }
+                None => {
+                    let _ = writeln!(err, "complete: {}: no completion specification", name);
+                    status = 1;
+                }
+            }
+        }
+        return status;
+    }
+
+    if parsed.default {
+        specs.default = Some(parsed.spec.clone());
+    }
+    if parsed.empty {
+        specs.empty = Some(parsed.spec.clone());
+    }
+    for name in parsed.operands {
+        specs.commands.insert(name, parsed.spec.clone());
+    }
+    0
+}
+
+pub fn compgen_builtin(
+    args: &[String],
+    shell: &mut Shell,
+    out: &mut dyn Write,
+    err: &mut dyn Write,
+) -> i32 {
+    let Some(parsed) = parse_spec_args("compgen", args, err) else {
+        return 1;
+    };
+
+    let word = parsed.operands.first().map(|s| s.as_str()).unwrap_or("");
+    let request = CompletionRequest {
+        line: word,
+        words: vec![word.to_string()],
+        command: "",
+        word,
+        previous: "",
+        matcher: Matcher::exact(word),
+    };
+    let (candidates, _, _) = generate_from_spec(&parsed.spec, &request, shell);
+    if candidates.is_empty() {
+        return 1;
+    }
+    for candidate in candidates {
+        let _ = writeln!(out, "{}", candidate.text);
+    }
+    0
+}
+
+fn escape_completion(text: &str, quote: Option<char>) -> String {
+    let mut escaped = String::new();
+    match quote {
+        Some('\'') => {
+            escaped.push('\'');
+            escaped.push_str(&text.replace('\'', "'\\''"));
+        }
+        Some(q) => {
+            escaped.push(q);
+            for c in text.chars() {
+                if "\"\\$`".contains(c) {
+                    escaped.push('\\');
+                }
+                escaped.push(c);
+            }
+        }
+        None => {
+            for c in text.chars() {
+                if c.is_whitespace() || "\\'\"$&|;()<>*?[]{}!#`".contains(c) {
+                    escaped.push('\\');
+                }
+                escaped.push(c);
+            }
+        }
+    }
+    escaped
+}
+
+pub fn get_longest_common_prefix(strings: &[String], matcher: &Matcher) -> String {
+    if strings.is_empty() {
+        return String::new();
+    }
+    let mut prefix_len = strings[0].chars().count();
+
+    for s in &strings[1..] {
+        prefix_len = strings[0]
+            .chars()
+            .zip(s.chars())
+            .take(prefix_len)
+            .take_while(|(a, b)| matcher.normalize_char(*a) == matcher.normalize_char(*b))
+            .count();
+    }
+    strings[0].chars().take(prefix_len).collect()
+}
+
+const BIND_VARIABLES: [&str; 4] = [
+    "completion-ignore-case",
+    "completion-map-case",
+    "completion-query-items",
+    "shell-completion-matching",
+];
+
+fn bind_variable_value(settings: &CompletionSettings, name: &str) -> String {
+    let on_off = |value: bool| if value { "on" } else { "off" }.to_string();
+    match name {
+        "completion-ignore-case" => on_off(settings.ignore_case),
+        "completion-map-case" => on_off(settings.map_case),
+        "shell-completion-matching" => match settings.matching {
+            MatchMode::Prefix => "prefix",
+            MatchMode::Substring => "substring",
+            MatchMode::Fuzzy => "fuzzy",
+        }
+        .to_string(),
+        _ => settings.query_items.to_string(),
+    }
+}
+
+fn set_bind_variable(settings: &mut CompletionSettings, name: &str, value: &str) -> bool {
+    let enabled = value.eq_ignore_ascii_case("on") || value == "1";
+    match name {
+        "completion-ignore-case" => settings.ignore_case = enabled,
+        "completion-map-case" => settings.map_case = enabled,
+        "shell-completion-matching" => {
+            settings.matching = match value {
+                "prefix" => MatchMode::Prefix,
+                "substring" => MatchMode::Substring,
+                "fuzzy" => MatchMode::Fuzzy,
+                _ => return false,
+            }
+        }
+        "completion-query-items" => match value.parse() {
+            Ok(items) => settings.query_items = items,
+            Err(_) => return false,
+        },
+        _ => return false,
+    }
+    true
+}
+
+pub fn bind_builtin(
+    args: &[String],
+    shell: &mut Shell,
+    out: &mut dyn Write,
+    err: &mut dyn Write,
+) -> i32 {
+    let settings = &mut shell.completion_settings;
+    let mut status = 0;
+
+    for arg in args {
+        match arg.as_str() {
+            "-v" => {
+                for name in BIND_VARIABLES {
+                    let _ = writeln!(out, "set {} {}", name, bind_variable_value(settings, name));
+                }
+            }
+            "-V" => {
+                for name in BIND_VARIABLES {
+                    let value = bind_variable_value(settings, name);
+                    let _ = writeln!(out, "{} is set to `{}'", name, value);
+                }
+            }
+            line => {
+                let words: Vec<&str> = line.split_whitespace().collect();
+                match words.as_slice() {
+                    ["set", name, value] => {
+                        if !set_bind_variable(settings, name, value) {
+                            let _ = writeln!(err, "bind: {}: invalid variable or value", line);
+                            status = 1;
+                        }
+                    }
+                    _ => {
+                        let _ = writeln!(err, "bind: {}: unsupported binding", line);
+                        status = 1;
+                    }
+                }
+            }
+        }
+    }
+    status
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn settings(matching: MatchMode, ignore_case: bool, map_case: bool) -> CompletionSettings {
+        CompletionSettings {
+            ignore_case,
+            map_case,
+            matching,
+            ..CompletionSettings::default()
+        }
+    }
+
+    fn spec(args: &[&str]) -> CompletionSpec {
+        let args: Vec<String> = args.iter().map(|arg| arg.to_string()).collect();
+        parse_spec_args("complete", &args, &mut Vec::new())
+            .unwrap()
+            .spec
+    }
+
+    fn generate(spec: &CompletionSpec, word: &str) -> Vec<String> {
+        let request = CompletionRequest {
+            line: word,
+            words: vec![word.to_string()],
+            command: "",
+            word,
+            previous: "",
+            matcher: Matcher::exact(word),
+        };
+        let (candidates, _, _) = generate_from_spec(spec, &request, &mut Shell::new());
+        candidates
+            .into_iter()
+            .map(|candidate| candidate.text)
+            .collect()
+    }
+
+    #[test]
+    fn prefix_matching() {
+        let matcher = Matcher::new("gi", CompletionSettings::default());
+        assert_eq!(matcher.score("git"), Some(3000));
+        assert_eq!(matcher.score("Git"), None);
+        assert_eq!(matcher.score("digit"), None);
+        assert!(matcher.extends("git") && !matcher.extends("gi"));
+    }
+
+    #[test]
+    fn substring_and_fuzzy_matching() {
+        let substring = Matcher::new("it", settings(MatchMode::Substring, false, false));
+        assert_eq!(substring.score("git"), Some(1999));
+        assert_eq!(substring.score("tig"), None);
+
+        let fuzzy = Matcher::new("gco", settings(MatchMode::Fuzzy, false, false));
+        let adjacent = fuzzy.score("gcommit").unwrap();
+        let scattered = fuzzy.score("git-checkout").unwrap();
+        assert!(adjacent > scattered && scattered > 1000);
+        assert_eq!(fuzzy.score("cog"), None);
+    }
+
+    #[test]
+    fn case_insensitive_matching() {
+        let matcher = Matcher::new("read", settings(MatchMode::Prefix, true, false));
+        assert_eq!(matcher.score("README.md"), Some(3000));
+        assert_eq!(matcher.score("read_me"), Some(3000));
+
+        let mapped = Matcher::new("foo-b", settings(MatchMode::Prefix, true, true));
+        assert_eq!(mapped.score("FOO_BAR"), Some(3000));
+        let unmapped = Matcher::new("foo-b", settings(MatchMode::Prefix, false, true));
+        assert_eq!(unmapped.score("foo_bar"), None);
+    }
+
+    #[test]
+    fn scans_the_current_word() {
+        let context = scan_current_word("git commit -m 'a b");
+        assert_eq!(context.words, ["git", "commit", "-m"]);
+        assert_eq!((context.word.as_str(), context.quote), ("a b", Some('\'')));
+        assert_eq!(context.start, 14);
+
+        let context = scan_current_word("ls | gr");
+        assert!(context.command_position && context.words.is_empty());
+
+        let context = scan_current_word("cat < fi");
+        assert!(context.after_redirect && context.words == ["cat"]);
+    }
+
+    #[test]
+    fn wordlists_are_sorted_and_deduplicated() {
+        assert_eq!(generate(&spec(&["-W", "b a b c"]), ""), ["a", "b", "c"]);
+        assert_eq!(generate(&spec(&["-W", "bc a bd"]), "b"), ["bc", "bd"]);
+    }
+
+    #[test]
+    fn nosort_keeps_order_and_drops_duplicates() {
+        let spec = spec(&["-o", "nosort", "-W", "b a b c a"]);
+        assert_eq!(generate(&spec, ""), ["b", "a", "c"]);
+    }
+
+    #[test]
+    fn spec_argument_errors() {
+        let mut err = Vec::new();
+        let args = ["-o".to_string(), "bogus".to_string()];
+        assert!(parse_spec_args("complete", &args, &mut err).is_none());
+        let args = ["-A".to_string()];
+        assert!(parse_spec_args("compgen", &args, &mut err).is_none());
+        assert_eq!(
+            String::from_utf8(err).unwrap(),
+            "complete: bogus: invalid option name\ncompgen: -A: option requires an argument\n"
+        );
+    }
+}
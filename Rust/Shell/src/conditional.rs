@@ -0,0 +1,416 @@
+use crate::parser::{expand_pattern, expand_word};
+use crate::pattern::{GLOB_SPECIAL, fnmatch, regex_match};
+use crate::variables::{Variables, evaluate_arithmetic};
+use std::ffi::CString;
+use std::fs;
+use std::io::Write;
+use std::os::unix::fs::{FileTypeExt, MetadataExt};
+
+const UNARY_OPERATORS: [&str; 25] = [
+    "-a", "-b", "-c", "-d", "-e", "-f", "-g", "-G", "-h", "-k", "-L", "-n", "-N", "-o", "-O", "-p",
+    "-r", "-s", "-S", "-t", "-u", "-v", "-w", "-x", "-z",
+];
+
+const BINARY_OPERATORS: [&str; 12] = [
+    "=", "==", "!=", "-eq", "-ne", "-lt", "-le", "-gt", "-ge", "-nt", "-ot", "-ef",
+];
+
+const REGEX_SPECIAL: &str = "\\.[]()*+?{}|^$";
+
+fn access(path: &str, mode: libc::c_int) -> bool {
+    match CString::new(path) {
+        Ok(path) => unsafe { libc::access(path.as_ptr(), mode) == 0 },
+        Err(_) => false,
+    }
+}
+
+fn file_test(op: &str, path: &str) -> bool {
+    if op == "-h" || op == "-L" {
+        return fs::symlink_metadata(path).is_ok_and(|m| m.file_type().is_symlink());
+    }
+
+    let Ok(metadata) = fs::metadata(path) else {
+        return false;
+    };
+    let file_type = metadata.file_type();
+    match op {
+        "-a" | "-e" => true,
+        "-b" => file_type.is_block_device(),
+        "-c" => file_type.is_char_device(),
+        "-d" => file_type.is_dir(),
+        "-f" => file_type.is_file(),
+        "-p" => file_type.is_fifo(),
+        "-S" => file_type.is_socket(),
+        "-g" => metadata.mode() & 0o2000 != 0,
+        "-u" => metadata.mode() & 0o4000 != 0,
+        "-k" => metadata.mode() & 0o1000 != 0,
+        "-s" => metadata.len() > 0,
+        "-r" => access(path, libc::R_OK),
+        "-w" => access(path, libc::W_OK),
+        "-x" => access(path, libc::X_OK),
+        "-O" => metadata.uid() == unsafe { libc::geteuid() },
+        "-G" => metadata.gid() == unsafe { libc::getegid() },
+        "-N" => metadata.mtime() > metadata.atime(),
+        _ => false,
+    }
+}
+
+fn compare_files(left: &str, op: &str, right: &str) -> bool {
+    let (left, right) = (fs::metadata(left).ok(), fs::metadata(right).ok());
+    let modified = |m: &fs::Metadata| m.modified().ok();
+    match (op, left, right) {
+        ("-nt", Some(left), Some(right)) => modified(&left) > modified(&right),
+        ("-nt", Some(_), None) => true,
+        ("-ot", Some(left), Some(right)) => modified(&left) < modified(&right),
+        ("-ot", None, Some(_)) => true,
+        ("-ef", Some(left), Some(right)) => left.dev() == right.dev() && left.ino() == right.ino(),
+        _ => false,
+    }
+}
+
+struct Evaluator<'a> {
+    name: &'a str,
+    words: &'a [String],
+    pos: usize,
+    extended: bool,
+    variables: &'a mut Variables,
+}
+
+impl Evaluator<'_> {
+    fn peek(&self, offset: usize) -> Option<&str> {
+        self.words.get(self.pos + offset).map(|s| s.as_str())
+    }
+
+    fn value(&self, index: usize) -> String {
+        let word = &self.words[index];
+        if self.extended {
+            expand_word(word, self.variables).unwrap_or_default()
+        } else {
+            word.clone()
+        }
+    }
+
+    fn is_binary(&self, word: &str) -> bool {
+        BINARY_OPERATORS.contains(&word) || (self.extended && matches!(word, "=~" | "<" | ">"))
+    }
+
+    fn integer(&self, index: usize) -> Result<i64, String> {
+        let value = self.value(index);
+        if self.extended {
+            return Ok(evaluate_arithmetic(&value, self.variables));
+        }
+        value
+            .trim()
+            .parse()
+            .map_err(|_| format!("{}: integer expression expected", value))
+    }
+
+    fn or_expression(&mut self) -> Result<bool, String> {
+        let or = if self.extended { "||" } else { "-o" };
+        let mut result = self.and_expression()?;
+        while self.peek(0) == Some(or) {
+            self.pos += 1;
+            let rhs = self.and_expression()?;
+            result = result || rhs;
+        }
+        Ok(result)
+    }
+
+    fn and_expression(&mut self) -> Result<bool, String> {
+        let and = if self.extended { "&&" } else { "-a" };
+        let mut result = self.not_expression()?;
+        while self.peek(0) == Some(and) {
+            self.pos += 1;
+            let rhs = self.not_expression()?;
+            result = result && rhs;
+        }
+        Ok(result)
+    }
+
+    fn not_expression(&mut self) -> Result<bool, String> {
+        if self.peek(0) == Some("!") {
+            self.pos += 1;
+            return Ok(!self.not_expression()?);
+        }
+        self.primary()
+    }
+
+    fn primary(&mut self) -> Result<bool, String> {
+        let Some(word) = self.peek(0) else {
+            return Err("argument expected".to_string());
+        };
+
+        if word == "(" {
+            self.pos += 1;
+            let result = self.or_expression()?;
+            if self.peek(0) != Some(")") {
+                return Err("expected `)'".to_string());
+            }
+            self.pos += 1;
+            return Ok(result);
+        }
+
+        if let Some(op) = self.peek(1)
+            && self.is_binary(op)
+        {
+            if self.peek(2).is_none() {
+                return Err(format!("{}: argument expected", op));
+            }
+            let op = op.to_string();
+            let result = self.binary(self.pos, &op, self.pos + 2);
+            self.pos += 3;
+            return result;
+        }
+
+        if UNARY_OPERATORS.contains(&word) && self.peek(1).is_some() {
+            let op = word.to_string();
+            let result = self.unary(&op, self.pos + 1);
+            self.pos += 2;
+            return result;
+        }
+
+        if self.extended && UNARY_OPERATORS.contains(&word) {
+            return Err(format!(
+                "unexpected argument to conditional unary operator `{}'",
+                word
+            ));
+        }
+
+        let result = !self.value(self.pos).is_empty();
+        self.pos += 1;
+        Ok(result)
+    }
+
+    fn unary(&mut self, op: &str, index: usize) -> Result<bool, String> {
+        let operand = self.value(index);
+        Ok(match op {
+            "-z" => operand.is_empty(),
+            "-n" => !operand.is_empty(),
+            "-v" => self.variables.get(&operand).is_some(),
+            "-o" => false,
+            "-t" => match operand.trim().parse::<libc::c_int>() {
+                Ok(fd) => unsafe { libc::isatty(fd) == 1 },
+                Err(_) => return Err(format!("{}: integer expression expected", operand)),
+            },
+            _ => file_test(op, &operand),
+        })
+    }
+
+    fn binary(&mut self, left: usize, op: &str, right: usize) -> Result<bool, String> {
+        let lhs = self.value(left);
+        match op {
+            "=" | "==" | "!=" => {
+                let matched = if self.extended {
+                    let pattern = expand_pattern(&self.words[right], self.variables, GLOB_SPECIAL);
+                    fnmatch(&pattern, &lhs)
+                } else {
+                    lhs == self.value(right)
+                };
+                Ok(matched == (op != "!="))
+            }
+            "=~" => {
+                let pattern = expand_pattern(&self.words[right], self.variables, REGEX_SPECIAL);
+                let groups = regex_match(&pattern, &lhs)?;
+                let matched = groups.is_some();
+                let _ = self
+                    .variables
+                    .set_array("BASH_REMATCH", groups.unwrap_or_default());
+                Ok(matched)
+            }
+            "<" => Ok(lhs < self.value(right)),
+            ">" => Ok(lhs > self.value(right)),
+            "-nt" | "-ot" | "-ef" => Ok(compare_files(&lhs, op, &self.value(right))),
+            _ => {
+                let (lhs, rhs) = (self.integer(left)?, self.integer(right)?);
+                Ok(match op {
+                    "-eq" => lhs == rhs,
+                    "-ne" => lhs != rhs,
+                    "-lt" => lhs < rhs,
+                    "-le" => lhs <= rhs,
+                    "-gt" => lhs > rhs,
+                    _ => lhs >= rhs,
+                })
+            }
+        }
+    }
+
+    fn posix(&mut self, len: usize) -> Result<bool, String> {
+        let word = |offset: usize| self.words[self.pos + offset].as_str();
+        match len {
+            0 => Ok(false),
+            1 => Ok(!word(0).is_empty()),
+            2 if word(0) == "!" => {
+                self.pos += 1;
+                Ok(!self.posix(1)?)
+            }
+            2 if UNARY_OPERATORS.contains(&word(0)) => self.unary(word(0), self.pos + 1),
+            2 => Err(format!("{}: unary operator expected", word(0))),
+            3 if BINARY_OPERATORS.contains(&word(1)) => {
+                let op = word(1).to_string();
+                self.binary(self.pos, &op, self.pos + 2)
+            }
+            3 if word(1) == "-a" => Ok(!word(0).is_empty() && !word(2).is_empty()),
+            3 if word(1) == "-o" => Ok(!word(0).is_empty() || !word(2).is_empty()),
+            3 | 4 if word(0) == "!" => {
+                self.pos += 1;
+                Ok(!self.posix(len - 1)?)
+            }
+            3 if word(0) == "(" && word(2) == ")" => Ok(!word(1).is_empty()),
+            3 => Err(format!("{}: binary operator expected", word(1))),
+            4 if word(0) == "(" && word(3) == ")" => {
+                self.pos += 1;
+                self.posix(2)
+            }
+            _ => {
+                let result = self.or_expression()?;
+                match self.peek(0) {
+                    Some(extra) => Err(format!("{}: too many arguments", extra)),
+                    None => Ok(result),
+                }
+            }
+        }
+    }
+}
+
+fn status(result: Result<bool, String>, name: &str, err: &mut dyn Write) -> i32 {
+    match result {
+        Ok(true) => 0,
+        Ok(false) => 1,
+        Err(e) => {
+            let _ = writeln!(err, "{}: {}", name, e);
+            2
+        }
+    }
+}
+
+pub fn test_builtin(
+    name: &str,
+    args: &[String],
+    variables: &mut Variables,
+    err: &mut dyn Write,
+) -> i32 {
+    let args = if name == "[" {
+        match args.split_last() {
+            Some((last, rest)) if last == "]" => rest,
+            _ => {
+                let _ = writeln!(err, "[: missing `]'");
+                return 2;
+            }
+        }
+    } else {
+        args
+    };
+
+    let mut evaluator = Evaluator {
+        name,
+        words: args,
+        pos: 0,
+        extended: false,
+        variables,
+    };
+    let result = evaluator.posix(args.len());
+    status(result, evaluator.name, err)
+}
+
+pub fn conditional_command(
+    words: &[String],
+    variables: &mut Variables,
+    err: &mut dyn Write,
+) -> i32 {
+    let Some((_, words)) = words.split_last().filter(|(last, _)| *last == "]]") else {
+        let _ = writeln!(err, "[[: syntax error: expected `]]'");
+        return 2;
+    };
+
+    let mut evaluator = Evaluator {
+        name: "[[",
+        words,
+        pos: 0,
+        extended: true,
+        variables,
+    };
+    let result = evaluator
+        .or_expression()
+        .and_then(|result| match evaluator.peek(0) {
+            Some(extra) => Err(format!("syntax error near `{}'", extra)),
+            None => Ok(result),
+        });
+    status(result, evaluator.name, err)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn words(line: &str) -> Vec<String> {
+        line.split(' ').map(|word| word.to_string()).collect()
+    }
+
+    fn test(name: &str, line: &str) -> (i32, String) {
+        let mut variables = Variables::default();
+        let mut err = Vec::new();
+        let status = test_builtin(name, &words(line), &mut variables, &mut err);
+        (status, String::from_utf8(err).unwrap())
+    }
+
+    fn conditional(line: &str) -> i32 {
+        let mut variables = Variables::default();
+        variables.set("name", "shell.rs").unwrap();
+        variables.set("empty", "").unwrap();
+        conditional_command(&words(line), &mut variables, &mut Vec::new())
+    }
+
+    #[test]
+    fn test_strings_and_integers() {
+        assert_eq!(test("test", "abc").0, 0);
+        assert_eq!(test("test", "-z abc").0, 1);
+        assert_eq!(test("test", "a = a").0, 0);
+        assert_eq!(test("test", "a != a").0, 1);
+        assert_eq!(test("test", "2 -lt 10").0, 0);
+        assert_eq!(test("test", "! 2 -gt 10").0, 0);
+        assert_eq!(test("test", "a = b -o 1 -eq 1").0, 0);
+        assert_eq!(test("test", "a = a -a 1 -eq 2").0, 1);
+        assert_eq!(test("[", "-d / ]").0, 0);
+        assert_eq!(test("[", "-f /").0, 2);
+    }
+
+    #[test]
+    fn test_errors() {
+        assert_eq!(
+            test("test", "x -eq 1"),
+            (2, "test: x: integer expression expected\n".to_string())
+        );
+        assert_eq!(test("[", "a = a"), (2, "[: missing `]'\n".to_string()));
+    }
+
+    #[test]
+    fn conditional_expressions() {
+        assert_eq!(conditional("-n $name ]]"), 0);
+        assert_eq!(conditional("-z $empty ]]"), 0);
+        assert_eq!(conditional("$name == *.rs ]]"), 0);
+        assert_eq!(conditional("$name == \"*.rs\" ]]"), 1);
+        assert_eq!(conditional("$name != *.c ]]"), 0);
+        assert_eq!(conditional("$name =~ ^sh.*rs$ ]]"), 0);
+        assert_eq!(conditional("a < b && ( b > a || -z x ) ]]"), 0);
+        assert_eq!(conditional("1+1 -eq 2 ]]"), 0);
+        assert_eq!(conditional("! -e /nonexistent ]]"), 0);
+    }
+
+    #[test]
+    fn conditional_syntax_errors() {
+        let mut variables = Variables::default();
+        let mut err = Vec::new();
+        assert_eq!(
+            conditional_command(&words("-n x"), &mut variables, &mut err),
+            2
+        );
+        assert_eq!(
+            conditional_command(&words("a b ]]"), &mut variables, &mut err),
+            2
+        );
+        assert_eq!(
+            String::from_utf8(err).unwrap(),
+            "[[: syntax error: expected `]]'\n[[: syntax error near `b'\n"
+        );
+    }
+}
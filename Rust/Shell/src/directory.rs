@@ -0,0 +1,465 @@
+use crate::shell::Shell;
+use std::env;
+use std::fs;
+use std::io::{self, Write};
+use std::os::unix::fs::MetadataExt;
+use std::path::{Component, Path, PathBuf};
+
+pub fn error_reason(e: &io::Error) -> String {
+    let message = e.to_string();
+    match message.find(" (os error") {
+        Some(end) => message[..end].to_string(),
+        None => message,
+    }
+}
+
+fn same_file(a: &Path, b: &Path) -> bool {
+    match (fs::metadata(a), fs::metadata(b)) {
+        (Ok(a), Ok(b)) => a.dev() == b.dev() && a.ino() == b.ino(),
+        _ => false,
+    }
+}
+
+fn physical_directory() -> Option<String> {
+    env::current_dir()
+        .ok()
+        .map(|path| path.to_string_lossy().into_owned())
+}
+
+pub fn logical_directory(shell: &Shell) -> Option<String> {
+    match shell.variables.get("PWD") {
+        Some(pwd) if pwd.starts_with('/') && same_file(Path::new(pwd), Path::new(".")) => {
+            Some(pwd.to_string())
+        }
+        _ => physical_directory(),
+    }
+}
+
+fn normalize_logical(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::from("/");
+    for component in path.components() {
+        match component {
+            Component::ParentDir => {
+                normalized.pop();
+            }
+            Component::Normal(name) => normalized.push(name),
+            Component::RootDir | Component::CurDir | Component::Prefix(_) => {}
+        }
+    }
+    normalized
+}
+
+fn abbreviate_home(dir: &str, shell: &Shell) -> String {
+    match shell.variables.get("HOME") {
+        Some(home) if !home.is_empty() && home != "/" => match dir.strip_prefix(home) {
+            Some("") => "~".to_string(),
+            Some(rest) if rest.starts_with('/') => format!("~{}", rest),
+            _ => dir.to_string(),
+        },
+        _ => dir.to_string(),
+    }
+}
+
+fn resolve_cdpath(operand: &str, shell: &Shell) -> Option<(String, bool)> {
+    let first = Path::new(operand).components().next();
+    if operand.starts_with('/') || matches!(first, Some(Component::CurDir | Component::ParentDir)) {
+        return None;
+    }
+
+    let cdpath = shell.variables.get("CDPATH")?;
+    cdpath.split(':').find_map(|entry| {
+        let candidate = if entry.is_empty() {
+            operand.to_string()
+        } else {
+            format!("{}/{}", entry.trim_end_matches('/'), operand)
+        };
+        Path::new(&candidate)
+            .is_dir()
+            .then_some((candidate, !entry.is_empty()))
+    })
+}
+
+fn change_directory(
+    builtin: &str,
+    target: &str,
+    physical: bool,
+    shell: &mut Shell,
+) -> Result<String, String> {
+    let old_pwd = logical_directory(shell);
+    let logical = if target.starts_with('/') {
+        PathBuf::from(target)
+    } else {
+        PathBuf::from(old_pwd.clone().unwrap_or_default()).join(target)
+    };
+    let logical = normalize_logical(&logical);
+
+    let result = if physical {
+        env::set_current_dir(target)
+    } else {
+        env::set_current_dir(&logical).or_else(|_| env::set_current_dir(target))
+    };
+    if let Err(e) = result {
+        return Err(format!("{}: {}: {}", builtin, target, error_reason(&e)));
+    }
+
+    let new_pwd = if physical || !same_file(&logical, Path::new(".")) {
+        physical_directory().unwrap_or_default()
+    } else {
+        logical.to_string_lossy().into_owned()
+    };
+
+    if let Some(old_pwd) = old_pwd {
+        let _ = shell.variables.set("OLDPWD", &old_pwd);
+    }
+    let _ = shell.variables.set("PWD", &new_pwd);
+    Ok(new_pwd)
+}
+
+pub fn cd_builtin(
+    args: &[String],
+    shell: &mut Shell,
+    out: &mut dyn Write,
+    err: &mut dyn Write,
+) -> i32 {
+    let mut physical = false;
+    let mut options_done = false;
+    let mut operands: Vec<&String> = Vec::new();
+
+    for arg in args {
+        if options_done || !arg.starts_with('-') || arg.len() == 1 {
+            options_done = true;
+            operands.push(arg);
+            continue;
+        }
+        if arg == "--" {
+            options_done = true;
+            continue;
+        }
+        for flag in arg[1..].chars() {
+            match flag {
+                'L' => physical = false,
+                'P' => physical = true,
+                _ => {
+                    let _ = writeln!(err, "cd: -{}: invalid option", flag);
+                    let _ = writeln!(err, "cd: usage: cd [-L|-P] [dir]");
+                    return 2;
+                }
+            }
+        }
+    }
+
+    if operands.len() > 1 {
+        let _ = writeln!(err, "cd: too many arguments");
+        return 1;
+    }
+
+    let mut print_directory = false;
+    let target = match operands.first().map(|s| s.as_str()) {
+        None => match shell.variables.get("HOME") {
+            Some(home) => home.to_string(),
+            None => {
+                let _ = writeln!(err, "cd: HOME not set");
+                return 1;
+            }
+        },
+        Some("-") => match shell.variables.get("OLDPWD") {
+            Some(old_pwd) => {
+                print_directory = true;
+                old_pwd.to_string()
+            }
+            None => {
+                let _ = writeln!(err, "cd: OLDPWD not set");
+                return 1;
+            }
+        },
+        Some(operand) => match resolve_cdpath(operand, shell) {
+            Some((resolved, from_cdpath)) => {
+                print_directory = from_cdpath;
+                resolved
+            }
+            None => operand.to_string(),
+        },
+    };
+
+    match change_directory("cd", &target, physical, shell) {
+        Ok(new_pwd) => {
+            if print_directory {
+                let _ = writeln!(out, "{}", new_pwd);
+            }
+            0
+        }
+        Err(e) => {
+            let _ = writeln!(err, "{}", e);
+            1
+        }
+    }
+}
+
+pub fn pwd_builtin(
+    args: &[String],
+    shell: &Shell,
+    out: &mut dyn Write,
+    err: &mut dyn Write,
+) -> i32 {
+    let mut physical = false;
+    for arg in args {
+        match arg.as_str() {
+            "-L" => physical = false,
+            "-P" => physical = true,
+            _ => {
+                let _ = writeln!(err, "pwd: {}: invalid option", arg);
+                let _ = writeln!(err, "pwd: usage: pwd [-LP]");
+                return 2;
+            }
+        }
+    }
+
+    let dir = if physical {
+        physical_directory()
+    } else {
+        logical_directory(shell)
+    };
+    match dir {
+        Some(dir) => {
+            let _ = writeln!(out, "{}", dir);
+            0
+        }
+        None => {
+            let _ = writeln!(err, "pwd: error retrieving current directory");
+            1
+        }
+    }
+}
+
+fn full_stack(shell: &Shell) -> Vec<String> {
+    let mut stack = vec![logical_directory(shell).unwrap_or_default()];
+    stack.extend(shell.dir_stack.iter().cloned());
+    stack
+}
+
+fn stack_index(builtin: &str, arg: &str, len: usize) -> Result<usize, String> {
+    let n: usize = arg[1..]
+        .parse()
+        .map_err(|_| format!("{}: {}: invalid number", builtin, arg))?;
+    if n >= len {
+        return Err(format!(
+            "{}: {}: directory stack index out of range",
+            builtin, arg
+        ));
+    }
+    Ok(if arg.starts_with('+') { n } else { len - 1 - n })
+}
+
+fn is_stack_offset(arg: &str) -> bool {
+    arg.len() > 1
+        && (arg.starts_with('+') || arg.starts_with('-'))
+        && arg[1..].chars().all(|c| c.is_ascii_digit())
+}
+
+fn print_stack(shell: &Shell, out: &mut dyn Write) {
+    let stack: Vec<String> = full_stack(shell)
+        .iter()
+        .map(|dir| abbreviate_home(dir, shell))
+        .collect();
+    let _ = writeln!(out, "{}", stack.join(" "));
+}
+
+pub fn dirs_builtin(
+    args: &[String],
+    shell: &mut Shell,
+    out: &mut dyn Write,
+    err: &mut dyn Write,
+) -> i32 {
+    let mut long = false;
+    let mut per_line = false;
+    let mut verbose = false;
+    let mut entry: Option<&String> = None;
+
+    for arg in args {
+        if is_stack_offset(arg) {
+            entry = Some(arg);
+            continue;
+        }
+        if !arg.starts_with('-') || arg.len() == 1 {
+            let _ = writeln!(err, "dirs: {}: invalid argument", arg);
+            return 1;
+        }
+        for flag in arg[1..].chars() {
+            match flag {
+                'c' => {
+                    shell.dir_stack.clear();
+                    return 0;
+                }
+                'l' => long = true,
+                'p' => per_line = true,
+                'v' => verbose = true,
+                _ => {
+                    let _ = writeln!(err, "dirs: -{}: invalid option", flag);
+                    let _ = writeln!(err, "dirs: usage: dirs [-clpv] [+N] [-N]");
+                    return 2;
+                }
+            }
+        }
+    }
+
+    let stack: Vec<String> = full_stack(shell)
+        .into_iter()
+        .map(|dir| {
+            if long {
+                dir
+            } else {
+                abbreviate_home(&dir, shell)
+            }
+        })
+        .collect();
+
+    if let Some(arg) = entry {
+        return match stack_index("dirs", arg, stack.len()) {
+            Ok(index) => {
+                let _ = writeln!(out, "{}", stack[index]);
+                0
+            }
+            Err(e) => {
+                let _ = writeln!(err, "{}", e);
+                1
+            }
+        };
+    }
+
+    if verbose {
+        for (index, dir) in stack.iter().enumerate() {
+            let _ = writeln!(out, "{:2}  {}", index, dir);
+        }
+    } else if per_line {
+        for dir in &stack {
+            let _ = writeln!(out, "{}", dir);
+        }
+    } else {
+        let _ = writeln!(out, "{}", stack.join(" "));
+    }
+    0
+}
+
+pub fn pushd_builtin(
+    args: &[String],
+    shell: &mut Shell,
+    out: &mut dyn Write,
+    err: &mut dyn Write,
+) -> i32 {
+    let mut no_change = false;
+    let mut operand: Option<&String> = None;
+
+    for arg in args {
+        if arg == "-n" {
+            no_change = true;
+        } else if operand.is_none() {
+            operand = Some(arg);
+        } else {
+            let _ = writeln!(err, "pushd: too many arguments");
+            return 1;
+        }
+    }
+
+    match operand {
+        Some(arg) if is_stack_offset(arg) => {
+            let mut stack = full_stack(shell);
+            let index = match stack_index("pushd", arg, stack.len()) {
+                Ok(index) => index,
+                Err(e) => {
+                    let _ = writeln!(err, "{}", e);
+                    return 1;
+                }
+            };
+            stack.rotate_left(index);
+            if !no_change && let Err(e) = change_directory("pushd", &stack[0], false, shell) {
+                let _ = writeln!(err, "{}", e);
+                return 1;
+            }
+            shell.dir_stack = stack.split_off(1);
+        }
+        Some(arg) => {
+            let target = arg.to_string();
+            if no_change {
+                shell.dir_stack.insert(0, target);
+            } else {
+                let old_pwd = logical_directory(shell).unwrap_or_default();
+                if let Err(e) = change_directory("pushd", &target, false, shell) {
+                    let _ = writeln!(err, "{}", e);
+                    return 1;
+                }
+                shell.dir_stack.insert(0, old_pwd);
+            }
+        }
+        None => {
+            if shell.dir_stack.is_empty() {
+                let _ = writeln!(err, "pushd: no other directory");
+                return 1;
+            }
+            if no_change {
+                return 0;
+            }
+            let target = shell.dir_stack[0].clone();
+            let old_pwd = logical_directory(shell).unwrap_or_default();
+            if let Err(e) = change_directory("pushd", &target, false, shell) {
+                let _ = writeln!(err, "{}", e);
+                return 1;
+            }
+            shell.dir_stack[0] = old_pwd;
+        }
+    }
+
+    print_stack(shell, out);
+    0
+}
+
+pub fn popd_builtin(
+    args: &[String],
+    shell: &mut Shell,
+    out: &mut dyn Write,
+    err: &mut dyn Write,
+) -> i32 {
+    let mut no_change = false;
+    let mut offset: Option<&String> = None;
+
+    for arg in args {
+        if arg == "-n" {
+            no_change = true;
+        } else if is_stack_offset(arg) && offset.is_none() {
+            offset = Some(arg);
+        } else {
+            let _ = writeln!(err, "popd: {}: invalid argument", arg);
+            let _ = writeln!(err, "popd: usage: popd [-n] [+N | -N]");
+            return 2;
+        }
+    }
+
+    if shell.dir_stack.is_empty() {
+        let _ = writeln!(err, "popd: directory stack empty");
+        return 1;
+    }
+
+    let stack_len = shell.dir_stack.len() + 1;
+    let index = match offset.map(|arg| stack_index("popd", arg, stack_len)) {
+        Some(Ok(index)) => index,
+        Some(Err(e)) => {
+            let _ = writeln!(err, "{}", e);
+            return 1;
+        }
+        None if no_change => 1,
+        None => 0,
+    };
+
+    if index == 0 {
+        let target = shell.dir_stack[0].clone();
+        if let Err(e) = change_directory("popd", &target, false, shell) {
+            let _ = writeln!(err, "{}", e);
+            return 1;
+        }
+        shell.dir_stack.remove(0);
+    } else {
+        shell.dir_stack.remove(index - 1);
+    }
+
+    print_stack(shell, out);
+    0
+}
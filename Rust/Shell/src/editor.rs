@@ -0,0 +1,347 @@
+use crate::completion::{CandidateKind, complete_line, get_longest_common_prefix};
+use crate::menu::{CompletionMenu, format_columns};
+use crate::prompt::prompt_width;
+use crate::shell::Shell;
+use crate::terminal::{TerminalInput, terminal_height, terminal_width};
+use std::io::{self, Read, Write};
+
+#[derive(PartialEq, Eq)]
+enum Key {
+    Tab,
+    BackTab,
+    Enter,
+    EndOfFile,
+    Backspace,
+    Up,
+    Down,
+    Left,
+    Right,
+    Char(char),
+    Ignored,
+}
+
+fn read_key(handle: &mut impl Read) -> Option<Key> {
+    let mut byte_buffer = [0u8; 1];
+    handle.read_exact(&mut byte_buffer).ok()?;
+
+    let key = match byte_buffer[0] {
+        4 => Key::EndOfFile,
+        9 => Key::Tab,
+        10 => Key::Enter,
+        127 => Key::Backspace,
+        27 => {
+            let mut seq = [0u8; 2];
+            if handle.read_exact(&mut seq).is_err() || seq[0] != 91 {
+                return Some(Key::Ignored);
+            }
+            match seq[1] {
+                65 => Key::Up,
+                66 => Key::Down,
+                67 => Key::Right,
+                68 => Key::Left,
+                90 => Key::BackTab,
+                _ => Key::Ignored,
+            }
+        }
+        c => Key::Char(c as char),
+    };
+    Some(key)
+}
+
+struct PromptLine<'a> {
+    text: &'a str,
+    width: usize,
+    rows: usize,
+    right: &'a str,
+    right_width: usize,
+}
+
+fn input_row(prompt: &PromptLine, buffer: &str) -> usize {
+    let columns = terminal_width().max(1);
+    (prompt.width + buffer.chars().count()).saturating_sub(1) / columns
+}
+
+fn move_to_input_start(prompt: &PromptLine, buffer: &str) {
+    print!("\r");
+    let row = input_row(prompt, buffer);
+    if row > 0 {
+        print!("\x1b[{}A", row);
+    }
+}
+
+fn right_prompt_fits(prompt: &PromptLine, buffer: &str) -> bool {
+    prompt.width + buffer.chars().count() + prompt.right_width < terminal_width()
+}
+
+fn draw_right_prompt(prompt: &PromptLine, buffer: &str) {
+    if prompt.right.is_empty() {
+        return;
+    }
+    if right_prompt_fits(prompt, buffer) {
+        print!(
+            "\x1b7\x1b[{}G{}\x1b8",
+            terminal_width() - prompt.right_width + 1,
+            prompt.right
+        );
+    } else {
+        print!("\x1b[K");
+    }
+}
+
+fn redraw(prompt: &PromptLine, previous: &str, buffer: &str) {
+    move_to_input_start(prompt, previous);
+    print!("{}{}\x1b[J", prompt.text, buffer);
+    draw_right_prompt(prompt, buffer);
+    io::stdout().flush().unwrap();
+}
+
+fn collapse_prompt(prompt: &PromptLine, buffer: &str, transient: &str) {
+    move_to_input_start(prompt, buffer);
+    if prompt.rows > 0 {
+        print!("\x1b[{}A", prompt.rows);
+    }
+    print!("{}{}\x1b[J", transient, buffer);
+}
+
+fn replace_word(prompt: &PromptLine, buffer: &mut String, start: usize, replacement: &str) {
+    let mut updated = buffer[..start].to_string();
+    updated.push_str(replacement);
+
+    if let Some(remainder) = updated.strip_prefix(buffer.as_str()) {
+        print!("{}", remainder);
+        draw_right_prompt(prompt, &updated);
+        io::stdout().flush().unwrap();
+    } else {
+        redraw(prompt, buffer, &updated);
+    }
+    *buffer = updated;
+}
+
+fn select_menu_candidate(buffer: &mut String, menu: &CompletionMenu) {
+    buffer.truncate(menu.completion.start);
+    buffer.push_str(&menu.completion.escape(&menu.current().text));
+}
+
+fn draw_menu(prompt: &PromptLine, previous: &str, buffer: &str, menu: &mut CompletionMenu) {
+    let height = terminal_height().saturating_sub(prompt.rows + input_row(prompt, buffer) + 1);
+    let lines = menu.render(terminal_width(), height);
+    move_to_input_start(prompt, previous);
+    print!("{}{}\x1b[J", prompt.text, buffer);
+    draw_right_prompt(prompt, buffer);
+    for line in &lines {
+        print!("\r\n{}", line);
+    }
+    print!(
+        "\x1b[{}A\r{}{}",
+        lines.len() + input_row(prompt, buffer),
+        prompt.text,
+        buffer
+    );
+    io::stdout().flush().unwrap();
+}
+
+fn print_candidates(prompt: &PromptLine, buffer: &str, lines: &[String]) {
+    print!("\r\n");
+    for line in lines {
+        print!("{}\r\n", line);
+    }
+    print!("{}{}", prompt.text, buffer);
+    draw_right_prompt(prompt, buffer);
+    io::stdout().flush().unwrap();
+}
+
+pub fn read_line(
+    shell: &mut Shell,
+    prompt: &str,
+    right_prompt: &str,
+    transient_prompt: Option<&str>,
+) -> Option<String> {
+    let (full, width) = prompt_width(prompt);
+    let (right, right_width) = prompt_width(right_prompt);
+    let (transient, _) = prompt_width(transient_prompt.unwrap_or_default());
+    let prompt = PromptLine {
+        text: full.rsplit('\n').next().unwrap_or_default(),
+        width,
+        rows: full.matches('\n').count(),
+        right: if right.contains('\n') { "" } else { &right },
+        right_width,
+    };
+    print!("{}", full);
+    draw_right_prompt(&prompt, "");
+    io::stdout().flush().unwrap();
+    let prompt = &prompt;
+
+    let mut input = TerminalInput;
+    let mut buffer = String::new();
+    let mut tab_press_count = 0;
+    let mut menu: Option<CompletionMenu> = None;
+    let mut history_index = shell.history.len();
+
+    while let Some(key) = read_key(&mut input) {
+        if let Some(active) = menu.as_mut() {
+            let navigated = match key {
+                Key::Tab | Key::Down => {
+                    active.next();
+                    true
+                }
+                Key::BackTab | Key::Up => {
+                    active.previous();
+                    true
+                }
+                Key::Right => {
+                    active.right();
+                    true
+                }
+                Key::Left => {
+                    active.left();
+                    true
+                }
+                _ => false,
+            };
+
+            if navigated {
+                let previous = buffer.clone();
+                select_menu_candidate(&mut buffer, active);
+                draw_menu(prompt, &previous, &buffer, active);
+                continue;
+            }
+
+            let accepted = menu.take().unwrap();
+            print!("\x1b[J");
+            draw_right_prompt(prompt, &buffer);
+            tab_press_count = 0;
+            if key == Key::Enter {
+                let candidate = accepted.current();
+                if candidate.kind != CandidateKind::Directory && !accepted.completion.no_space {
+                    let mut suffix: String = accepted.completion.quote.into_iter().collect();
+                    suffix.push(' ');
+                    let end = buffer.len();
+                    replace_word(prompt, &mut buffer, end, &suffix);
+                }
+                io::stdout().flush().unwrap();
+                continue;
+            }
+        }
+
+        match key {
+            Key::Tab => {
+                let completion = complete_line(&buffer, shell);
+                let texts: Vec<String> = completion
+                    .candidates
+                    .iter()
+                    .map(|candidate| candidate.text.clone())
+                    .collect();
+
+                if texts.len() == 1 {
+                    let candidate = &completion.candidates[0];
+                    let mut replacement = completion.escape(&candidate.text);
+                    if candidate.kind != CandidateKind::Directory && !completion.no_space {
+                        replacement.extend(completion.quote);
+                        replacement.push(' ');
+                    }
+                    replace_word(prompt, &mut buffer, completion.start, &replacement);
+                    tab_press_count = 0;
+                } else if texts.len() > 1 {
+                    let lcp = get_longest_common_prefix(&texts, &completion.matcher);
+
+                    if completion.matcher.extends(&lcp) {
+                        let replacement = completion.escape(&lcp);
+                        replace_word(prompt, &mut buffer, completion.start, &replacement);
+                        tab_press_count = 0;
+                    } else {
+                        tab_press_count += 1;
+
+                        if tab_press_count == 1 {
+                            print!("\x07");
+                            io::stdout().flush().unwrap();
+                        } else if tab_press_count == 2 {
+                            let lines =
+                                format_columns(&completion.candidates, terminal_width(), None);
+                            if texts.len() > shell.completion_settings.query_items {
+                                print!("\r\nDisplay all {} possibilities? (y or n)", texts.len());
+                                io::stdout().flush().unwrap();
+                                if matches!(read_key(&mut input), Some(Key::Char('y' | 'Y'))) {
+                                    print_candidates(prompt, &buffer, &lines);
+                                } else {
+                                    print!("\r\n{}{}", prompt.text, buffer);
+                                    draw_right_prompt(prompt, &buffer);
+                                    io::stdout().flush().unwrap();
+                                }
+                            } else {
+                                print_candidates(prompt, &buffer, &lines);
+                            }
+                        } else {
+                            let mut active = CompletionMenu::new(completion, terminal_width());
+                            let previous = buffer.clone();
+                            select_menu_candidate(&mut buffer, &active);
+                            draw_menu(prompt, &previous, &buffer, &mut active);
+                            menu = Some(active);
+                        }
+                    }
+                } else {
+                    print!("\x07");
+                    io::stdout().flush().unwrap();
+                    tab_press_count = 0;
+                }
+            }
+
+            Key::Enter => {
+                if transient_prompt.is_some() {
+                    collapse_prompt(prompt, &buffer, &transient);
+                }
+                println!();
+                return Some(buffer);
+            }
+
+            Key::EndOfFile if buffer.is_empty() => {
+                println!();
+                return None;
+            }
+
+            Key::Up if !shell.history.is_empty() && history_index > 0 => {
+                history_index -= 1;
+                let previous = std::mem::replace(&mut buffer, shell.history[history_index].clone());
+                redraw(prompt, &previous, &buffer);
+            }
+
+            Key::Down if !shell.history.is_empty() && history_index < shell.history.len() => {
+                history_index += 1;
+                let next = shell
+                    .history
+                    .get(history_index)
+                    .cloned()
+                    .unwrap_or_default();
+                let previous = std::mem::replace(&mut buffer, next);
+                redraw(prompt, &previous, &buffer);
+            }
+
+            Key::Backspace => {
+                tab_press_count = 0;
+                if !buffer.is_empty() {
+                    let shown = right_prompt_fits(prompt, &buffer);
+                    buffer.pop();
+                    print!("\x08 \x08");
+                    if !shown && right_prompt_fits(prompt, &buffer) {
+                        draw_right_prompt(prompt, &buffer);
+                    }
+                    io::stdout().flush().unwrap();
+                }
+            }
+
+            Key::Char(char) => {
+                tab_press_count = 0;
+                let shown = right_prompt_fits(prompt, &buffer);
+                buffer.push(char);
+                print!("{}", char);
+                if shown && !right_prompt_fits(prompt, &buffer) {
+                    draw_right_prompt(prompt, &buffer);
+                }
+                io::stdout().flush().unwrap();
+            }
+
+            _ => {}
+        }
+    }
+
+    None
+}
@@ -1,373 +1,515 @@
-#[allow(unused_imports)]
-use std::io::{self, BufRead, BufReader, Write};
-
-use crate::builtins::BUILTINS;
-use crate::parser::{parse_input, split_by_pipe};
+use crate::builtins::{Builtin, BuiltinIo, expand_aliases, find_builtin};
+use crate::conditional::conditional_command;
+use crate::jobs::{
+    join_process_group, leave_job_control, place_in_group, restored_signals, wait_foreground,
+};
+use crate::parser::{expand_word_checked, expand_words_checked, split_words};
+use crate::prompt::{prompt_string, prompt_width};
+use crate::redirect::{apply_redirects, restore_fds};
+use crate::shell::Shell;
+use crate::syntax::{
+    AndOr, Command as SyntaxCommand, Connector, List, Pipeline, SimpleCommand, parse,
+};
 use crate::terminal::{disable_raw_mode, enable_raw_mode};
-use std::collections::HashSet;
-use std::env;
-use std::fs::{self, File, OpenOptions};
-use std::os::unix::fs::PermissionsExt;
+use crate::trap::{
+    DEBUG_TRAP, ERR_TRAP, reset_subshell_traps, run_exit_trap, run_pending_traps, run_trap,
+};
+use crate::variables::{Value, Variable, split_assignments};
+use std::io::{self, Write};
 use std::os::unix::process::CommandExt;
 use std::path::{Path, PathBuf};
-use std::process::{Command, Stdio};
+use std::process::Command;
+
+fn record_command_hit(command_name: &str, shell: &mut Shell) -> Option<PathBuf> {
+    let path_var = shell.variables.get("PATH").unwrap_or_default().to_string();
+    shell.command_hash.record_hit(&path_var, command_name)
+}
 
-fn find_executable(command_name: &str) -> Option<PathBuf> {
-    let path_var = env::var("PATH").unwrap_or_default();
+fn expand_assignment(word: &str, shell: &Shell) -> Result<(String, Value), String> {
+    let (name, value) = word.split_once('=').unwrap_or((word, ""));
+    let nounset = shell.options.nounset;
+    let value = match value.strip_prefix('(').and_then(|v| v.strip_suffix(')')) {
+        Some(elements) => Value::Array(expand_words_checked(
+            &split_words(elements),
+            &shell.variables,
+            nounset,
+            shell.options.noglob,
+        )?),
+        None => Value::Scalar(
+            expand_word_checked(value, &shell.variables, nounset)?.unwrap_or_default(),
+        ),
+    };
+    Ok((name.to_string(), value))
+}
+
+type ExpandedCommand = (Vec<(String, Value)>, Vec<String>);
 
-    for dir in path_var.split(':') {
-        let mut full_path = PathBuf::from(dir);
-        full_path.push(command_name);
+fn expand_command(words: &[String], shell: &Shell) -> Result<ExpandedCommand, String> {
+    let assignment_count = split_assignments(words);
+    let assignments = words[..assignment_count]
+        .iter()
+        .map(|word| expand_assignment(word, shell))
+        .collect::<Result<_, _>>()?;
+    let args = expand_words_checked(
+        &words[assignment_count..],
+        &shell.variables,
+        shell.options.nounset,
+        shell.options.noglob,
+    )?;
+    Ok((assignments, args))
+}
+
+fn trace_word(word: &str) -> String {
+    if !word.is_empty()
+        && word
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "%+,-./:=@_^".contains(c))
+    {
+        return word.to_string();
+    }
+    format!("'{}'", word.replace('\'', "'\\''"))
+}
 
-        if let Ok(metadata) = fs::metadata(&full_path) {
-            if metadata.is_file() && (metadata.permissions().mode() & 0o111 != 0) {
-                return Some(full_path);
+fn trace_command(assignments: &[(String, Value)], args: &[String], shell: &Shell) {
+    let mut words: Vec<String> = assignments
+        .iter()
+        .map(|(name, value)| match value {
+            Value::Scalar(value) => format!("{}={}", name, trace_word(value)),
+            Value::Array(values) => {
+                let values: Vec<String> = values.iter().map(|value| trace_word(value)).collect();
+                format!("{}=({})", name, values.join(" "))
             }
+        })
+        .collect();
+    words.extend(args.iter().map(|arg| trace_word(arg)));
+    trace_line(&words, shell);
+}
+
+fn trace_line(words: &[String], shell: &Shell) {
+    let (prefix, _) = prompt_width(&prompt_string(shell, "PS4", "+ "));
+    eprintln!("{}{}", prefix, words.join(" "));
+}
+
+fn environment_assignments(assignments: &[(String, Value)]) -> Vec<(String, String)> {
+    assignments
+        .iter()
+        .filter_map(|(name, value)| match value {
+            Value::Scalar(value) => Some((name.clone(), value.clone())),
+            Value::Array(_) => None,
+        })
+        .collect()
+}
+
+type SavedVariables = Vec<(String, Option<Variable>)>;
+
+fn set_temporary(
+    assignments: &[(String, Value)],
+    shell: &mut Shell,
+    err: &mut dyn Write,
+) -> SavedVariables {
+    let mut saved_variables = Vec::new();
+    for (name, value) in assignments {
+        saved_variables.push((name.clone(), shell.variables.snapshot(name)));
+        if let Err(e) = shell.variables.set_value(name, value.clone()) {
+            let _ = writeln!(err, "shell: {}", e);
         }
     }
-    None
+    saved_variables
 }
 
-pub fn execute_command_line(
-    input: &str,
-    history: &mut Vec<String>,
-    last_saved_index: &mut usize,
-) -> bool {
-    let clean_input = input.trim();
-    if clean_input.is_empty() {
-        return false;
+fn restore_temporary(saved_variables: SavedVariables, shell: &mut Shell) {
+    for (name, snapshot) in saved_variables.into_iter().rev() {
+        shell.variables.restore(&name, snapshot);
     }
+}
 
-    let parsed_args = parse_input(clean_input);
-    let commands = split_by_pipe(parsed_args);
+fn run_builtin(
+    builtin: &dyn Builtin,
+    args: &[String],
+    assignments: &[(String, Value)],
+    shell: &mut Shell,
+    io: &mut BuiltinIo,
+) -> i32 {
+    let saved_variables = set_temporary(assignments, shell, io.stderr);
+    let status = builtin.run(args, shell, io);
+    let _ = io.stdout.flush();
+    let _ = io.stderr.flush();
+    restore_temporary(saved_variables, shell);
+    status
+}
 
-    if commands.len() > 1 {
-        execute_pipeline(commands);
-        return false;
+pub fn call_function(name: &str, args: &[String], shell: &mut Shell) -> Option<i32> {
+    let body = shell.functions.get(name)?.clone();
+    let previous = shell.variables.set_arguments(args.to_vec());
+    shell.call_depth += 1;
+    match parse(&body) {
+        Ok(list) => execute_list(&list, shell),
+        Err(e) => {
+            eprintln!("shell: {}", e);
+            shell.variables.set_status(2);
+        }
     }
+    shell.call_depth -= 1;
+    shell.returning = false;
+    shell.variables.set_arguments(previous);
+    Some(shell.variables.status())
+}
 
-    let mut parsed_args = commands.into_iter().next().unwrap_or_default();
-    let mut output_file: Option<File> = None;
-    let mut error_file: Option<File> = None;
+pub fn execute_command_line(input: &str, shell: &mut Shell) -> bool {
+    let clean_input = input.trim();
+    if clean_input.is_empty() {
+        return false;
+    }
 
-    if let Some(index) = parsed_args
-        .iter()
-        .position(|arg| arg == ">>" || arg == "1>>")
-    {
-        if index + 1 < parsed_args.len() {
-            let filename = &parsed_args[index + 1];
+    let words = expand_aliases(split_words(clean_input), &shell.aliases);
+    match parse(&words) {
+        Ok(_) if shell.options.noexec && !shell.interactive => {}
+        Ok(list) => execute_list(&list, shell),
+        Err(e) => {
+            eprintln!("shell: {}", e);
+            shell.variables.set_status(2);
+        }
+    }
+    shell.exiting
+}
 
-            if let Some(parent) = Path::new(filename).parent() {
-                let _ = fs::create_dir_all(parent);
-            }
+fn execute_list(list: &List, shell: &mut Shell) {
+    for and_or in list {
+        if shell.exiting || shell.returning {
+            return;
+        }
+        if and_or.background {
+            execute_background(and_or, shell);
+        } else {
+            execute_and_or(and_or, shell);
+        }
+        run_pending_traps(shell);
+    }
+}
 
-            match OpenOptions::new()
-                .create(true)
-                .write(true)
-                .append(true)
-                .open(filename)
-            {
-                Ok(file) => {
-                    output_file = Some(file);
-                    parsed_args.drain(index..=index + 1);
-                }
-                Err(e) => {
-                    eprintln!("Failed to open file for appending: {}", e);
-                    return false;
+fn execute_background(and_or: &AndOr, shell: &mut Shell) {
+    let _ = io::stdout().flush();
+    let pid = unsafe { libc::fork() };
+    if pid == 0 {
+        leave_job_control(shell, 0, false);
+        unsafe {
+            libc::setpgid(0, 0);
+            if !shell.interactive {
+                let null = libc::open(c"/dev/null".as_ptr(), libc::O_RDONLY);
+                if null >= 0 {
+                    libc::dup2(null, libc::STDIN_FILENO);
+                    libc::close(null);
                 }
             }
         }
-    } else if let Some(index) = parsed_args.iter().position(|arg| arg == ">" || arg == "1>") {
-        if index + 1 < parsed_args.len() {
-            let filename = &parsed_args[index + 1];
-
-            match File::create(filename) {
-                Ok(file) => {
-                    output_file = Some(file);
-                    parsed_args.drain(index..=index + 1);
-                }
-                Err(e) => {
-                    eprintln!("Failed to create file: {}", e);
-                    return false;
-                }
+        reset_subshell_traps(shell);
+        shell.interactive = false;
+        match (&and_or.first.commands[..], and_or.rest.is_empty()) {
+            ([SyntaxCommand::Simple(simple)], true) if !and_or.first.negated => {
+                execute_simple(simple, shell, true)
             }
+            _ => execute_and_or(and_or, shell),
         }
+        run_exit_trap(shell);
+        let _ = io::stdout().flush();
+        std::process::exit(shell.variables.status());
+    }
+    if pid == -1 {
+        eprintln!("Failed to fork background job");
+        shell.variables.set_status(1);
+        return;
     }
 
-    if let Some(index) = parsed_args.iter().position(|arg| arg == "2>>") {
-        if index + 1 < parsed_args.len() {
-            let filename = &parsed_args[index + 1];
+    unsafe {
+        libc::setpgid(pid, pid);
+    }
+    let id = shell.jobs.add(pid, &and_or.text);
+    if shell.interactive {
+        eprintln!("[{}] {}", id, pid);
+    }
+    shell.variables.set_background_pid(pid);
+    shell.variables.set_status(0);
+}
 
-            if let Some(parent) = Path::new(filename).parent() {
-                let _ = fs::create_dir_all(parent);
-            }
+fn execute_guarded(pipeline: &Pipeline, shell: &mut Shell) {
+    shell.errexit_ignored += 1;
+    execute_pipeline(pipeline, shell);
+    shell.errexit_ignored -= 1;
+}
 
-            match OpenOptions::new()
-                .create(true)
-                .write(true)
-                .append(true)
-                .open(filename)
-            {
-                Ok(file) => {
-                    error_file = Some(file);
-                    parsed_args.drain(index..=index + 1);
-                }
-                Err(e) => {
-                    eprintln!("Failed to open file for appending stderr: {}", e);
-                    return false;
-                }
-            }
+fn execute_and_or(and_or: &AndOr, shell: &mut Shell) {
+    let mut last = &and_or.first;
+    if and_or.rest.is_empty() {
+        execute_pipeline(last, shell);
+    } else {
+        execute_guarded(last, shell);
+    }
+
+    for (i, (connector, pipeline)) in and_or.rest.iter().enumerate() {
+        if shell.exiting || shell.returning {
+            return;
         }
-    } else if let Some(index) = parsed_args.iter().position(|arg| arg == "2>") {
-        if index + 1 < parsed_args.len() {
-            let filename = &parsed_args[index + 1];
-            match File::create(filename) {
-                Ok(file) => {
-                    error_file = Some(file);
-                    parsed_args.drain(index..=index + 1);
-                }
-                Err(e) => {
-                    eprintln!("Failed to create error file: {}", e);
-                    return false;
-                }
-            }
+        let succeeded = shell.variables.status() == 0;
+        if succeeded != (*connector == Connector::And) {
+            continue;
+        }
+        last = pipeline;
+        if i + 1 == and_or.rest.len() {
+            execute_pipeline(pipeline, shell);
+        } else {
+            execute_guarded(pipeline, shell);
         }
     }
 
-    if parsed_args.is_empty() {
-        return false;
+    let final_pipeline = and_or
+        .rest
+        .last()
+        .map_or(&and_or.first, |(_, pipeline)| pipeline);
+    if shell.errexit_ignored == 0
+        && std::ptr::eq(last, final_pipeline)
+        && !last.negated
+        && shell.variables.status() != 0
+    {
+        run_trap(shell, ERR_TRAP);
+        shell.exiting |= shell.options.errexit;
     }
+}
 
-    let command = &parsed_args[0];
-    let args = &parsed_args[1..];
-
-    match command.as_str() {
-        "exit" => return true,
-        "echo" => {
-            let output = args.join(" ");
-            match output_file {
-                Some(mut file) => {
-                    if let Err(e) = writeln!(file, "{}", output) {
-                        eprintln!("Error writing to file: {}", e);
-                    }
-                }
-                None => println!("{}", output),
-            }
+fn execute_command(command: &SyntaxCommand, shell: &mut Shell) {
+    match command {
+        SyntaxCommand::Simple(simple) => execute_simple(simple, shell, false),
+        SyntaxCommand::Group(list, redirects) => {
+            let Ok(saved) = apply_redirects(redirects, shell) else {
+                shell.variables.set_status(1);
+                return;
+            };
+            execute_list(list, shell);
+            restore_fds(saved);
         }
-        "type" => {
-            if let Some(arg) = args.get(0) {
-                if BUILTINS.contains(&arg.as_str()) {
-                    println!("{} is a shell builtin", arg);
-                } else {
-                    match find_executable(arg) {
-                        Some(path) => println!("{} is {}", arg, path.display()),
-                        None => println!("{}: not found", arg),
-                    }
-                }
-            }
+        SyntaxCommand::Function(name, body) => {
+            shell.functions.insert(name.clone(), body.clone());
+            shell.variables.set_status(0);
         }
-        "pwd" => match env::current_dir() {
-            Ok(path) => {
-                println!("{}", path.display());
-            }
-            Err(e) => {
-                eprintln!("Error retrieving directory: {}", e)
-            }
-        },
-        "cd" => {
-            let arg = args.get(0).map(|s| s.as_str()).unwrap_or("~");
-            let new_dir = if arg == "~" {
-                match env::var("HOME") {
-                    Ok(path) => path,
-                    Err(_) => {
-                        println!("cd: HOME not set");
-                        return false;
-                    }
+        SyntaxCommand::Subshell(list, redirects) => {
+            let _ = io::stdout().flush();
+            disable_raw_mode();
+            let pid = unsafe { libc::fork() };
+            if pid == 0 {
+                leave_job_control(shell, 0, true);
+                reset_subshell_traps(shell);
+                match apply_redirects(redirects, shell) {
+                    Ok(_) => execute_list(list, shell),
+                    Err(()) => shell.variables.set_status(1),
                 }
+                run_exit_trap(shell);
+                let _ = io::stdout().flush();
+                std::process::exit(shell.variables.status());
+            }
+            let status = if pid == -1 {
+                eprintln!("Failed to fork subshell");
+                1
             } else {
-                arg.to_string()
+                place_in_group(shell, pid, pid);
+                wait_foreground(shell, &[pid]).pop().unwrap_or(0)
             };
-            let path = Path::new(&new_dir);
-            if let Err(_) = env::set_current_dir(path) {
-                println!("cd: {}: No such file or directory", new_dir);
-            }
+            enable_raw_mode();
+            shell.variables.set_status(status);
         }
-        "history" => {
-            if args.get(0).map(|s| s.as_str()) == Some("-r") {
-                if let Some(filepath) = args.get(1) {
-                    match File::open(filepath) {
-                        Ok(file) => {
-                            let reader = BufReader::new(file);
-                            for line in reader.lines() {
-                                match line {
-                                    Ok(cmd) => {
-                                        let trimmed = cmd.trim();
-                                        if !trimmed.is_empty() {
-                                            history.push(trimmed.to_string());
-                                        }
-                                    }
-                                    Err(e) => {
-                                        eprintln!("Error reading history file: {}", e);
-                                        break;
-                                    }
-                                }
-                            }
-                        }
-                        Err(e) => {
-                            eprintln!("history: {}: {}", filepath, e);
-                        }
-                    }
-                } else {
-                    eprintln!("history: -r requires a filename argument");
-                }
-            } else if args.get(0).map(|s| s.as_str()) == Some("-w") {
-                if let Some(filepath) = args.get(1) {
-                    match File::create(filepath) {
-                        Ok(mut file) => {
-                            for cmd in history.iter() {
-                                if let Err(e) = writeln!(file, "{}", cmd) {
-                                    eprintln!("Error writing to history file: {}", e);
-                                    break;
-                                }
-                            }
-                        }
-                        Err(e) => {
-                            eprintln!("history: {}: {}", filepath, e);
-                        }
-                    }
-                } else {
-                    eprintln!("history: -w requires a filename argument");
-                }
-            } else if args.get(0).map(|s| s.as_str()) == Some("-a") {
-                if let Some(filepath) = args.get(1) {
-                    match OpenOptions::new()
-                        .create(true)
-                        .write(true)
-                        .append(true)
-                        .open(filepath)
-                    {
-                        Ok(mut file) => {
-                            for cmd in history.iter().skip(*last_saved_index) {
-                                if let Err(e) = writeln!(file, "{}", cmd) {
-                                    eprintln!("Error writing to history file: {}", e);
-                                    break;
-                                }
-                            }
-                            *last_saved_index = history.len();
-                        }
-                        Err(e) => {
-                            eprintln!("history: {}: {}", filepath, e);
-                        }
-                    }
-                } else {
-                    eprintln!("history: -a requires a filename argument");
-                }
-            } else {
-                let n: usize = args
-                    .get(0)
-                    .and_then(|s| s.parse().ok())
-                    .unwrap_or(history.len());
-                let start = history.len().saturating_sub(n);
-                for (i, cmd) in history.iter().enumerate().skip(start) {
-                    println!("{:5}  {}", i + 1, cmd);
+    }
+}
+
+fn execute_conditional(simple: &SimpleCommand, shell: &mut Shell) {
+    let words = &simple.words;
+    if shell.options.nounset || shell.options.xtrace {
+        let expanded = words
+            .iter()
+            .map(|word| {
+                expand_word_checked(word, &shell.variables, shell.options.nounset)
+                    .map(|word| word.filter(|word| !word.is_empty()))
+                    .map(|word| word.unwrap_or_else(|| "''".to_string()))
+            })
+            .collect::<Result<Vec<_>, _>>();
+        match expanded {
+            Ok(expanded) if shell.options.xtrace => trace_line(&expanded, shell),
+            Ok(_) => {}
+            Err(e) => {
+                eprintln!("{}", e);
+                shell.variables.set_status(1);
+                if !shell.interactive {
+                    shell.exiting = true;
                 }
+                return;
             }
         }
-        _ => match find_executable(command) {
-            Some(path) => {
-                let command_name = Path::new(command).file_name().unwrap().to_str().unwrap();
-
-                let stdout_dest = match output_file {
-                    Some(file) => Stdio::from(file),
-                    None => Stdio::inherit(),
-                };
-
-                let stderr_dest = match error_file {
-                    Some(file) => Stdio::from(file),
-                    None => Stdio::inherit(),
-                };
-
-                disable_raw_mode();
+    }
 
-                let res = Command::new(&path)
-                    .arg0(command_name)
-                    .args(args)
-                    .stdout(stdout_dest)
-                    .stderr(stderr_dest)
-                    .status();
+    let Ok(saved) = apply_redirects(&simple.redirects, shell) else {
+        shell.variables.set_status(1);
+        return;
+    };
+    let status = conditional_command(&words[1..], &mut shell.variables, &mut io::stderr());
+    restore_fds(saved);
+    shell.variables.set_status(status);
+}
 
-                enable_raw_mode();
+fn execute_simple(simple: &SimpleCommand, shell: &mut Shell, exec: bool) {
+    run_trap(shell, DEBUG_TRAP);
+    let words = &simple.words;
+    if words.first().is_some_and(|word| word == "[[") {
+        execute_conditional(simple, shell);
+        return;
+    }
 
-                if let Err(e) = res {
-                    eprintln!("Error while executing: {}", e);
-                }
+    let (assignments, parsed_args) = match expand_command(words, shell) {
+        Ok(expanded) => expanded,
+        Err(e) => {
+            eprintln!("{}", e);
+            shell.variables.set_status(1);
+            if !shell.interactive {
+                shell.exiting = true;
             }
+            return;
+        }
+    };
+    if shell.options.xtrace {
+        trace_command(&assignments, &parsed_args, shell);
+    }
+    if let Some(name) = parsed_args
+        .first()
+        .filter(|name| shell.functions.contains_key(*name))
+    {
+        let Ok(saved) = apply_redirects(&simple.redirects, shell) else {
+            shell.variables.set_status(1);
+            return;
+        };
+        let saved_variables = set_temporary(&assignments, shell, &mut io::stderr());
+        let status = call_function(name, &parsed_args[1..], shell).unwrap_or(0);
+        restore_temporary(saved_variables, shell);
+        restore_fds(saved);
+        shell.variables.set_status(status);
+        return;
+    }
+    let builtin = parsed_args.first().and_then(|name| find_builtin(name));
+    let path = match parsed_args.first() {
+        Some(command) if builtin.is_none() => match record_command_hit(command, shell) {
+            Some(path) => Some(path),
             None => {
-                println!("{}: command not found", command);
+                eprintln!("{}: command not found", command);
+                shell.variables.set_status(127);
+                return;
             }
         },
-    }
-    false
-}
+        _ => None,
+    };
 
-fn is_builtin(cmd: &str) -> bool {
-    BUILTINS.contains(&cmd)
-}
-
-fn execute_builtin_in_pipeline(cmd_name: &str, args: &[String]) {
-    match cmd_name {
-        "echo" => {
-            let output = args.join(" ");
-            println!("{}", output);
+    if path.is_some() && !exec {
+        disable_raw_mode();
+    }
+    let Ok(saved) = apply_redirects(&simple.redirects, shell) else {
+        if path.is_some() && !exec {
+            enable_raw_mode();
         }
-        "type" => {
-            if let Some(arg) = args.get(0) {
-                if BUILTINS.contains(&arg.as_str()) {
-                    println!("{} is a shell builtin", arg);
-                } else {
-                    match find_executable(arg) {
-                        Some(path) => println!("{} is {}", arg, path.display()),
-                        None => println!("{}: not found", arg),
-                    }
-                }
+        shell.variables.set_status(1);
+        return;
+    };
+
+    let status = if let Some(builtin) = builtin {
+        let mut stdout = io::stdout();
+        let mut stderr = io::stderr();
+        let mut io = BuiltinIo {
+            stdin: libc::STDIN_FILENO,
+            stdout: &mut stdout,
+            stderr: &mut stderr,
+        };
+        run_builtin(builtin, &parsed_args, &assignments, shell, &mut io)
+    } else if let Some(path) = path {
+        let command = &parsed_args[0];
+        let command_name = Path::new(command).file_name().unwrap().to_str().unwrap();
+        let mut process = Command::new(&path);
+        process
+            .arg0(command_name)
+            .args(&parsed_args[1..])
+            .env_clear()
+            .envs(shell.variables.exported())
+            .envs(environment_assignments(&assignments));
+
+        if exec {
+            let e = process.exec();
+            eprintln!("Error while executing: {}", e);
+            std::process::exit(126);
+        }
+        if shell.job_control {
+            let signals = restored_signals(shell);
+            unsafe {
+                process.pre_exec(move || {
+                    join_process_group(0, true, &signals);
+                    Ok(())
+                });
             }
         }
-        "pwd" => {
-            if let Ok(path) = std::env::current_dir() {
-                println!("{}", path.display());
+        let status = match process.spawn() {
+            Ok(child) => {
+                let pid = child.id() as libc::pid_t;
+                place_in_group(shell, pid, pid);
+                wait_foreground(shell, &[pid]).pop().unwrap_or(0)
+            }
+            Err(e) => {
+                eprintln!("Error while executing: {}", e);
+                126
+            }
+        };
+        enable_raw_mode();
+        status
+    } else {
+        let mut status = 0;
+        for (name, value) in assignments {
+            if let Err(e) = shell.variables.set_value(&name, value) {
+                eprintln!("shell: {}", e);
+                status = 1;
             }
         }
-        _ => {}
-    }
+        status
+    };
+
+    restore_fds(saved);
+    shell.variables.set_status(status);
 }
 
-fn execute_pipeline(commands: Vec<Vec<String>>) {
+fn execute_pipeline(pipeline: &Pipeline, shell: &mut Shell) {
+    shell.jobs.foreground.clone_from(&pipeline.text);
+    let statuses = pipeline_statuses(&pipeline.commands, shell);
+    let last = statuses.last().copied().unwrap_or(0);
+    let status = if shell.options.pipefail {
+        statuses
+            .iter()
+            .rev()
+            .find(|status| **status != 0)
+            .copied()
+            .unwrap_or(0)
+    } else {
+        last
+    };
+
+    let _ = shell.variables.set_array(
+        "PIPESTATUS",
+        statuses.iter().map(|status| status.to_string()).collect(),
+    );
+    shell
+        .variables
+        .set_status(match (pipeline.negated, status) {
+            (false, status) => status,
+            (true, 0) => 1,
+            (true, _) => 0,
+        });
+}
+
+fn pipeline_statuses(commands: &[SyntaxCommand], shell: &mut Shell) -> Vec<i32> {
     let n = commands.len();
     if n < 2 {
-        return;
-    }
-
-    for cmd_args in &commands {
-        if cmd_args.is_empty() {
-            eprintln!("Empty command in pipeline");
-            return;
-        }
-    }
-
-    let mut cmd_paths: Vec<Option<PathBuf>> = Vec::new();
-    for cmd_args in &commands {
-        let cmd_name = &cmd_args[0];
-        if is_builtin(cmd_name) {
-            cmd_paths.push(None);
-        } else {
-            match find_executable(cmd_name) {
-                Some(path) => cmd_paths.push(Some(path)),
-                None => {
-                    println!("{}: command not found", cmd_name);
-                    return;
-                }
-            }
+        if let Some(command) = commands.first() {
+            execute_command(command, shell);
         }
+        return vec![shell.variables.status()];
     }
 
     let mut pipes: Vec<[libc::c_int; 2]> = Vec::new();
@@ -380,17 +522,21 @@ fn execute_pipeline(commands: Vec<Vec<String>>) {
                     libc::close(p[0]);
                     libc::close(p[1]);
                 }
-                return;
+                return vec![1];
             }
         }
         pipes.push(pipe_fds);
     }
 
+    let lastpipe = shell.options.lastpipe && !shell.job_control;
+    let forked = if lastpipe { n - 1 } else { n };
+
+    let _ = io::stdout().flush();
     disable_raw_mode();
 
     let mut pids: Vec<libc::pid_t> = Vec::new();
 
-    for i in 0..n {
+    for (i, command) in commands.iter().enumerate().take(forked) {
         let pid = unsafe { libc::fork() };
 
         if pid == -1 {
@@ -402,108 +548,64 @@ fn execute_pipeline(commands: Vec<Vec<String>>) {
                 }
             }
             enable_raw_mode();
-            return;
+            return vec![1];
         }
 
         if pid == 0 {
-            if i > 0 {
-                unsafe {
+            leave_job_control(shell, pids.first().copied().unwrap_or(0), true);
+            unsafe {
+                if i > 0 {
                     libc::dup2(pipes[i - 1][0], libc::STDIN_FILENO);
                 }
-            }
-
-            if i < n - 1 {
-                unsafe {
+                if i < n - 1 {
                     libc::dup2(pipes[i][1], libc::STDOUT_FILENO);
                 }
-            }
-
-            unsafe {
                 for p in &pipes {
                     libc::close(p[0]);
                     libc::close(p[1]);
                 }
+                libc::signal(libc::SIGPIPE, libc::SIG_DFL);
             }
+            reset_subshell_traps(shell);
 
-            let cmd_name = &commands[i][0];
-            let cmd_rest = &commands[i][1..];
-
-            if is_builtin(cmd_name) {
-                execute_builtin_in_pipeline(cmd_name, cmd_rest);
-                std::process::exit(0);
-            } else {
-                let cmd_file_name = Path::new(cmd_name).file_name().unwrap().to_str().unwrap();
-                let _ = Command::new(cmd_paths[i].as_ref().unwrap())
-                    .arg0(cmd_file_name)
-                    .args(cmd_rest)
-                    .exec();
-                std::process::exit(1);
+            match command {
+                SyntaxCommand::Simple(simple) => execute_simple(simple, shell, true),
+                _ => execute_command(command, shell),
             }
+            run_exit_trap(shell);
+            let _ = io::stdout().flush();
+            std::process::exit(shell.variables.status());
         }
 
+        place_in_group(shell, pid, pids.first().copied().unwrap_or(pid));
         pids.push(pid);
     }
 
+    let lastpipe_input = lastpipe.then(|| pipes[n - 2][0]);
     unsafe {
         for p in &pipes {
-            libc::close(p[0]);
+            if Some(p[0]) != lastpipe_input {
+                libc::close(p[0]);
+            }
             libc::close(p[1]);
         }
-
-        let mut status: libc::c_int = 0;
-        for pid in &pids {
-            libc::waitpid(*pid, &mut status, 0);
-        }
     }
 
-    enable_raw_mode();
-}
+    let lastpipe_status = lastpipe_input.map(|input| unsafe {
+        let saved_stdin = libc::dup(libc::STDIN_FILENO);
+        libc::dup2(input, libc::STDIN_FILENO);
+        libc::close(input);
 
-pub fn find_completions(prefix: &str) -> Vec<String> {
-    let mut candidates = HashSet::new();
-
-    for &builtin in BUILTINS.iter() {
-        if builtin.starts_with(prefix) {
-            candidates.insert(builtin.to_string());
-        }
-    }
+        execute_command(&commands[n - 1], shell);
 
-    if let Ok(path_var) = env::var("PATH") {
-        for dir in path_var.split(':') {
-            if let Ok(entries) = fs::read_dir(dir) {
-                for entry in entries {
-                    if let Ok(entry) = entry {
-                        let filename = entry.file_name().to_string_lossy().to_string();
-
-                        if filename.starts_with(prefix) {
-                            if let Ok(metadata) = entry.metadata() {
-                                if metadata.is_file()
-                                    && (metadata.permissions().mode() & 0o111 != 0)
-                                {
-                                    candidates.insert(filename);
-                                }
-                            }
-                        }
-                    }
-                }
-            }
-        }
-    }
-    let mut results: Vec<String> = candidates.into_iter().collect();
-    results.sort();
-    results
-}
+        libc::dup2(saved_stdin, libc::STDIN_FILENO);
+        libc::close(saved_stdin);
+        shell.variables.status()
+    });
 
-pub fn get_longest_common_prefix(strings: &[String]) -> String {
-    if strings.is_empty() {
-        return String::new();
-    }
-    let mut prefix = strings[0].clone();
+    let mut statuses = wait_foreground(shell, &pids);
+    statuses.extend(lastpipe_status);
 
-    for s in &strings[1..] {
-        while !s.starts_with(&prefix) {
-            prefix.pop();
-        }
-    }
-    prefix
+    enable_raw_mode();
+    statuses
 }
@@ -0,0 +1,1086 @@
+use std::collections::{BinaryHeap, HashMap, HashSet};
+use std::ffi::OsStr;
+use std::fs::{self, File};
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::fs::{FileExt, MetadataExt};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{Duration, Instant, SystemTime};
+
+type ObjectId = [u8; 20];
+type StagedKey = (PathBuf, SystemTime, Option<ObjectId>);
+
+const COMMIT: u8 = 1;
+const TREE: u8 = 2;
+const OFS_DELTA: u8 = 6;
+const REF_DELTA: u8 = 7;
+
+const LENGTH_BASE: [u16; 29] = [
+    3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131,
+    163, 195, 227, 258,
+];
+const LENGTH_EXTRA: [u32; 29] = [
+    0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0,
+];
+const DISTANCE_BASE: [u16; 30] = [
+    1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537,
+    2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577,
+];
+const DISTANCE_EXTRA: [u32; 30] = [
+    0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13,
+    13,
+];
+const CODE_LENGTH_ORDER: [usize; 19] = [
+    16, 17, 18, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15,
+];
+
+static STAGED_CACHE: Mutex<Option<(StagedKey, bool)>> = Mutex::new(None);
+
+struct Repository {
+    git_dir: PathBuf,
+    common_dir: PathBuf,
+    work_tree: PathBuf,
+}
+
+struct Bits<'a> {
+    data: &'a [u8],
+    pos: usize,
+    buffer: u32,
+    count: u32,
+}
+
+struct Huffman {
+    counts: [u16; 16],
+    symbols: Vec<u16>,
+}
+
+struct Pack {
+    index: File,
+    data: File,
+    fanout: [u32; 256],
+}
+
+struct Objects {
+    directory: PathBuf,
+    packs: Vec<Pack>,
+}
+
+#[derive(Default)]
+struct Commit {
+    time: i64,
+    tree: Option<ObjectId>,
+    parents: Vec<ObjectId>,
+}
+
+struct IndexEntry {
+    path: Vec<u8>,
+    mtime: u32,
+    size: u32,
+    mode: u32,
+    id: ObjectId,
+    stage: u16,
+    skip: bool,
+}
+
+struct Index {
+    entries: Vec<IndexEntry>,
+    tree: Option<ObjectId>,
+    modified: SystemTime,
+}
+
+impl Bits<'_> {
+    fn bits(&mut self, need: u32) -> Option<u32> {
+        let mut value = self.buffer;
+        while self.count < need {
+            let byte = *self.data.get(self.pos)?;
+            self.pos += 1;
+            value |= (byte as u32) << self.count;
+            self.count += 8;
+        }
+        self.buffer = value >> need;
+        self.count -= need;
+        Some(value & ((1 << need) - 1))
+    }
+
+    fn decode(&mut self, huffman: &Huffman) -> Option<usize> {
+        let (mut code, mut first, mut index) = (0i32, 0i32, 0i32);
+        for len in 1..16 {
+            code |= self.bits(1)? as i32;
+            let count = huffman.counts[len] as i32;
+            if code - count < first {
+                return Some(huffman.symbols[(index + code - first) as usize] as usize);
+            }
+            index += count;
+            first = (first + count) << 1;
+            code <<= 1;
+        }
+        None
+    }
+}
+
+fn huffman(lengths: &[u8]) -> Huffman {
+    let mut counts = [0u16; 16];
+    for &len in lengths {
+        counts[len as usize] += 1;
+    }
+    let mut offsets = [0u16; 16];
+    for len in 1..15 {
+        offsets[len + 1] = offsets[len] + counts[len];
+    }
+    let mut symbols = vec![0u16; lengths.len()];
+    for (symbol, &len) in lengths.iter().enumerate() {
+        if len != 0 {
+            symbols[offsets[len as usize] as usize] = symbol as u16;
+            offsets[len as usize] += 1;
+        }
+    }
+    Huffman { counts, symbols }
+}
+
+fn inflate_codes(
+    bits: &mut Bits,
+    out: &mut Vec<u8>,
+    literals: &Huffman,
+    distances: &Huffman,
+) -> Option<()> {
+    loop {
+        let symbol = bits.decode(literals)?;
+        match symbol {
+            0..=255 => out.push(symbol as u8),
+            256 => return Some(()),
+            _ => {
+                let symbol = symbol - 257;
+                let length =
+                    *LENGTH_BASE.get(symbol)? as usize + bits.bits(LENGTH_EXTRA[symbol])? as usize;
+                let symbol = bits.decode(distances)?;
+                let distance = *DISTANCE_BASE.get(symbol)? as usize
+                    + bits.bits(DISTANCE_EXTRA[symbol])? as usize;
+                let start = out.len().checked_sub(distance)?;
+                for offset in 0..length {
+                    out.push(out[start + offset]);
+                }
+            }
+        }
+    }
+}
+
+fn inflate_dynamic(bits: &mut Bits, out: &mut Vec<u8>) -> Option<()> {
+    let literal_count = bits.bits(5)? as usize + 257;
+    let distance_count = bits.bits(5)? as usize + 1;
+    let code_count = bits.bits(4)? as usize + 4;
+    let mut code_lengths = [0u8; 19];
+    for &index in &CODE_LENGTH_ORDER[..code_count] {
+        code_lengths[index] = bits.bits(3)? as u8;
+    }
+    let code = huffman(&code_lengths);
+
+    let total = literal_count + distance_count;
+    let mut lengths = vec![0u8; total];
+    let mut index = 0;
+    while index < total {
+        let symbol = bits.decode(&code)?;
+        let (value, repeat) = match symbol {
+            0..=15 => (symbol as u8, 1),
+            16 => (
+                *lengths.get(index.checked_sub(1)?)?,
+                3 + bits.bits(2)? as usize,
+            ),
+            17 => (0, 3 + bits.bits(3)? as usize),
+            _ => (0, 11 + bits.bits(7)? as usize),
+        };
+        lengths.get_mut(index..index + repeat)?.fill(value);
+        index += repeat;
+    }
+    let literals = huffman(&lengths[..literal_count]);
+    let distances = huffman(&lengths[literal_count..]);
+    inflate_codes(bits, out, &literals, &distances)
+}
+
+fn inflate(data: &[u8], size: usize) -> Option<Vec<u8>> {
+    if data.len() < 2 || data[0] & 0x0f != 8 {
+        return None;
+    }
+    let mut bits = Bits {
+        data,
+        pos: 2,
+        buffer: 0,
+        count: 0,
+    };
+    let mut out = Vec::with_capacity(size);
+    loop {
+        let last = bits.bits(1)?;
+        match bits.bits(2)? {
+            0 => {
+                bits.buffer = 0;
+                bits.count = 0;
+                let header = data.get(bits.pos..bits.pos + 4)?;
+                let len = u16::from_le_bytes([header[0], header[1]]) as usize;
+                bits.pos += 4;
+                out.extend_from_slice(data.get(bits.pos..bits.pos + len)?);
+                bits.pos += len;
+            }
+            1 => {
+                let mut lengths = [8u8; 288];
+                lengths[144..256].fill(9);
+                lengths[256..280].fill(7);
+                inflate_codes(&mut bits, &mut out, &huffman(&lengths), &huffman(&[5; 30]))?;
+            }
+            2 => inflate_dynamic(&mut bits, &mut out)?,
+            _ => return None,
+        }
+        if last == 1 {
+            return Some(out);
+        }
+    }
+}
+
+fn delta_size(delta: &[u8], pos: &mut usize) -> Option<usize> {
+    let mut value = 0;
+    let mut shift = 0;
+    loop {
+        let byte = *delta.get(*pos)?;
+        *pos += 1;
+        if shift >= usize::BITS {
+            return None;
+        }
+        value |= ((byte & 0x7f) as usize) << shift;
+        shift += 7;
+        if byte & 0x80 == 0 {
+            return Some(value);
+        }
+    }
+}
+
+fn apply_delta(base: &[u8], delta: &[u8]) -> Option<Vec<u8>> {
+    let mut pos = 0;
+    if delta_size(delta, &mut pos)? != base.len() {
+        return None;
+    }
+    let size = delta_size(delta, &mut pos)?;
+    let mut out = Vec::with_capacity(size);
+    while let Some(&op) = delta.get(pos) {
+        pos += 1;
+        if op & 0x80 != 0 {
+            let mut offset = 0;
+            let mut length = 0;
+            for bit in 0..7 {
+                if op & (1 << bit) != 0 {
+                    let byte = *delta.get(pos)? as usize;
+                    pos += 1;
+                    if bit < 4 {
+                        offset |= byte << (8 * bit);
+                    } else {
+                        length |= byte << (8 * (bit - 4));
+                    }
+                }
+            }
+            if length == 0 {
+                length = 0x10000;
+            }
+            out.extend_from_slice(base.get(offset..offset + length)?);
+        } else if op != 0 {
+            out.extend_from_slice(delta.get(pos..pos + op as usize)?);
+            pos += op as usize;
+        } else {
+            return None;
+        }
+    }
+    (out.len() == size).then_some(out)
+}
+
+fn read_chunk(file: &File, offset: u64, len: usize) -> Vec<u8> {
+    let mut buffer = vec![0u8; len];
+    let mut filled = 0;
+    while filled < len {
+        match file.read_at(&mut buffer[filled..], offset + filled as u64) {
+            Ok(0) | Err(_) => break,
+            Ok(count) => filled += count,
+        }
+    }
+    buffer.truncate(filled);
+    buffer
+}
+
+fn parse_id(hex: &str) -> Option<ObjectId> {
+    if hex.len() != 40 {
+        return None;
+    }
+    let mut id = [0u8; 20];
+    for (index, byte) in id.iter_mut().enumerate() {
+        *byte = u8::from_str_radix(hex.get(index * 2..index * 2 + 2)?, 16).ok()?;
+    }
+    Some(id)
+}
+
+fn short_id(id: &ObjectId) -> String {
+    id[..4]
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect::<String>()[..7]
+        .to_string()
+}
+
+fn be32(data: &[u8], pos: usize) -> Option<u32> {
+    Some(u32::from_be_bytes(data.get(pos..pos + 4)?.try_into().ok()?))
+}
+
+impl Pack {
+    fn open(index_path: &Path) -> Option<Pack> {
+        let index = File::open(index_path).ok()?;
+        let data = File::open(index_path.with_extension("pack")).ok()?;
+        let header = read_chunk(&index, 0, 8 + 256 * 4);
+        if header.len() != 8 + 256 * 4 || header[..8] != [0xff, b't', b'O', b'c', 0, 0, 0, 2] {
+            return None;
+        }
+        let mut fanout = [0u32; 256];
+        for (slot, value) in fanout.iter_mut().enumerate() {
+            *value = be32(&header, 8 + slot * 4)?;
+        }
+        Some(Pack {
+            index,
+            data,
+            fanout,
+        })
+    }
+
+    fn find(&self, id: &ObjectId) -> Option<u64> {
+        let total = self.fanout[255] as u64;
+        let mut low = match id[0] {
+            0 => 0,
+            first => self.fanout[first as usize - 1],
+        } as u64;
+        let mut high = self.fanout[id[0] as usize] as u64;
+        while low < high {
+            let middle = (low + high) / 2;
+            let entry = read_chunk(&self.index, 1032 + middle * 20, 20);
+            match entry.as_slice().cmp(id.as_slice()) {
+                std::cmp::Ordering::Less => low = middle + 1,
+                std::cmp::Ordering::Greater => high = middle,
+                std::cmp::Ordering::Equal => {
+                    let offset = be32(
+                        &read_chunk(&self.index, 1032 + total * 24 + middle * 4, 4),
+                        0,
+                    )?;
+                    if offset & 0x8000_0000 == 0 {
+                        return Some(offset as u64);
+                    }
+                    let large = (offset & 0x7fff_ffff) as u64;
+                    let bytes = read_chunk(&self.index, 1032 + total * 28 + large * 8, 8);
+                    return Some(u64::from_be_bytes(bytes.try_into().ok()?));
+                }
+            }
+        }
+        None
+    }
+}
+
+impl Objects {
+    fn open(common_dir: &Path) -> Objects {
+        let directory = common_dir.join("objects");
+        let packs = fs::read_dir(directory.join("pack"))
+            .into_iter()
+            .flatten()
+            .flatten()
+            .map(|entry| entry.path())
+            .filter(|path| path.extension() == Some(OsStr::new("idx")))
+            .filter_map(|path| Pack::open(&path))
+            .collect();
+        Objects { directory, packs }
+    }
+
+    fn read(&self, id: &ObjectId) -> Option<(u8, Vec<u8>)> {
+        self.read_loose(id).or_else(|| {
+            self.packs.iter().find_map(|pack| {
+                let offset = pack.find(id)?;
+                self.read_packed(pack, offset, 0)
+            })
+        })
+    }
+
+    fn read_loose(&self, id: &ObjectId) -> Option<(u8, Vec<u8>)> {
+        let hex: String = id.iter().map(|byte| format!("{:02x}", byte)).collect();
+        let data = fs::read(self.directory.join(&hex[..2]).join(&hex[2..])).ok()?;
+        let object = inflate(&data, data.len() * 2)?;
+        let end = object.iter().position(|&byte| byte == 0)?;
+        let kind = match object[..end].split(|&byte| byte == b' ').next()? {
+            b"commit" => COMMIT,
+            b"tree" => TREE,
+            b"blob" => 3,
+            b"tag" => 4,
+            _ => return None,
+        };
+        Some((kind, object[end + 1..].to_vec()))
+    }
+
+    fn read_packed(&self, pack: &Pack, offset: u64, depth: usize) -> Option<(u8, Vec<u8>)> {
+        if depth > 64 {
+            return None;
+        }
+        let header = read_chunk(&pack.data, offset, 32);
+        let mut byte = *header.first()?;
+        let kind = (byte >> 4) & 7;
+        let mut size = (byte & 0x0f) as usize;
+        let mut shift = 4;
+        let mut pos = 1;
+        while byte & 0x80 != 0 {
+            byte = *header.get(pos)?;
+            pos += 1;
+            if shift >= usize::BITS {
+                return None;
+            }
+            size |= ((byte & 0x7f) as usize) << shift;
+            shift += 7;
+        }
+        let base = match kind {
+            OFS_DELTA => {
+                byte = *header.get(pos)?;
+                pos += 1;
+                let mut distance = (byte & 0x7f) as u64;
+                while byte & 0x80 != 0 {
+                    byte = *header.get(pos)?;
+                    pos += 1;
+                    distance = distance.checked_add(1)?.checked_mul(0x80)? | (byte & 0x7f) as u64;
+                }
+                Some(self.read_packed(pack, offset.checked_sub(distance)?, depth + 1)?)
+            }
+            REF_DELTA => {
+                let id: ObjectId = header.get(pos..pos + 20)?.try_into().ok()?;
+                pos += 20;
+                Some(self.read(&id)?)
+            }
+            _ => None,
+        };
+        let compressed = read_chunk(&pack.data, offset + pos as u64, size + size / 8 + 1024);
+        let data = inflate(&compressed, size)?;
+        match base {
+            Some((kind, base)) => Some((kind, apply_delta(&base, &data)?)),
+            None => Some((kind, data)),
+        }
+    }
+
+    fn commit(&self, id: &ObjectId) -> Option<Commit> {
+        let (kind, data) = self.read(id)?;
+        if kind != COMMIT {
+            return None;
+        }
+        let text = String::from_utf8_lossy(&data);
+        let mut commit = Commit::default();
+        for line in text.lines().take_while(|line| !line.is_empty()) {
+            if let Some(tree) = line.strip_prefix("tree ") {
+                commit.tree = parse_id(tree);
+            } else if let Some(parent) = line.strip_prefix("parent ") {
+                commit.parents.extend(parse_id(parent));
+            } else if let Some(committer) = line.strip_prefix("committer ") {
+                commit.time = committer
+                    .rsplit(' ')
+                    .nth(1)
+                    .and_then(|time| time.parse().ok())
+                    .unwrap_or(0);
+            }
+        }
+        Some(commit)
+    }
+
+    fn flatten_tree(
+        &self,
+        id: &ObjectId,
+        prefix: &[u8],
+        entries: &mut HashMap<Vec<u8>, ObjectId>,
+        deadline: Instant,
+    ) -> Option<()> {
+        if Instant::now() > deadline {
+            return None;
+        }
+        let (kind, data) = self.read(id)?;
+        if kind != TREE {
+            return None;
+        }
+        let mut pos = 0;
+        while pos < data.len() {
+            let space = pos + data[pos..].iter().position(|&byte| byte == b' ')?;
+            let end = space + data[space..].iter().position(|&byte| byte == 0)?;
+            let id: ObjectId = data.get(end + 1..end + 21)?.try_into().ok()?;
+            let mut path = prefix.to_vec();
+            path.extend_from_slice(&data[space + 1..end]);
+            if &data[pos..space] == b"40000" {
+                path.push(b'/');
+                self.flatten_tree(&id, &path, entries, deadline)?;
+            } else {
+                entries.insert(path, id);
+            }
+            pos = end + 21;
+        }
+        Some(())
+    }
+}
+
+fn find_repository(start: &Path) -> Option<Repository> {
+    let mut directory = Some(start);
+    while let Some(current) = directory {
+        let dot_git = current.join(".git");
+        let git_dir = if dot_git.is_dir() {
+            dot_git
+        } else if dot_git.is_file() {
+            let content = fs::read_to_string(&dot_git).ok()?;
+            current.join(content.trim().strip_prefix("gitdir:")?.trim())
+        } else {
+            directory = current.parent();
+            continue;
+        };
+        let common_dir = fs::read_to_string(git_dir.join("commondir"))
+            .map(|common| git_dir.join(common.trim()))
+            .unwrap_or_else(|_| git_dir.clone());
+        return Some(Repository {
+            git_dir,
+            common_dir,
+            work_tree: current.to_path_buf(),
+        });
+    }
+    None
+}
+
+fn packed_ref(repository: &Repository, name: &str) -> Option<ObjectId> {
+    let packed = fs::read_to_string(repository.common_dir.join("packed-refs")).ok()?;
+    packed
+        .lines()
+        .filter(|line| !line.starts_with('#') && !line.starts_with('^'))
+        .filter_map(|line| line.split_once(' '))
+        .find(|(_, reference)| *reference == name)
+        .and_then(|(id, _)| parse_id(id))
+}
+
+fn resolve_ref(repository: &Repository, name: &str) -> Option<ObjectId> {
+    let mut name = name.to_string();
+    for _ in 0..8 {
+        let content = fs::read_to_string(repository.git_dir.join(&name))
+            .or_else(|_| fs::read_to_string(repository.common_dir.join(&name)));
+        let Ok(content) = content else {
+            return packed_ref(repository, &name);
+        };
+        match content.trim().strip_prefix("ref: ") {
+            Some(target) => name = target.to_string(),
+            None => return parse_id(content.trim()),
+        }
+    }
+    None
+}
+
+fn upstream_ref(repository: &Repository, branch: &str) -> Option<String> {
+    let config = fs::read_to_string(repository.common_dir.join("config")).ok()?;
+    let section = format!("[branch \"{}\"]", branch);
+    let mut inside = false;
+    let mut remote = None;
+    let mut merge = None;
+    for line in config.lines().map(str::trim) {
+        if line.starts_with('[') {
+            inside = line == section;
+        } else if inside && let Some((key, value)) = line.split_once('=') {
+            match key.trim().to_ascii_lowercase().as_str() {
+                "remote" => remote = Some(value.trim().to_string()),
+                "merge" => merge = Some(value.trim().to_string()),
+                _ => {}
+            }
+        }
+    }
+    let (remote, merge) = (remote?, merge?);
+    if remote == "." {
+        return Some(merge);
+    }
+    let branch = merge.strip_prefix("refs/heads/").unwrap_or(&merge);
+    Some(format!("refs/remotes/{}/{}", remote, branch))
+}
+
+fn operation(git_dir: &Path) -> Option<&'static str> {
+    [
+        ("rebase-merge", "REBASE"),
+        ("rebase-apply/rebasing", "REBASE"),
+        ("rebase-apply/applying", "AM"),
+        ("rebase-apply", "AM/REBASE"),
+        ("MERGE_HEAD", "MERGING"),
+        ("CHERRY_PICK_HEAD", "CHERRY-PICKING"),
+        ("REVERT_HEAD", "REVERTING"),
+        ("BISECT_LOG", "BISECTING"),
+    ]
+    .into_iter()
+    .find(|(file, _)| git_dir.join(file).exists())
+    .map(|(_, name)| name)
+}
+
+fn read_index(path: &Path, deadline: Instant) -> Option<Index> {
+    let modified = fs::metadata(path).ok()?.modified().ok()?;
+    let data = fs::read(path).ok()?;
+    if data.get(..4)? != b"DIRC" {
+        return None;
+    }
+    let version = be32(&data, 4)?;
+    let count = be32(&data, 8)? as usize;
+    let mut entries = Vec::with_capacity(count);
+    let mut pos = 12;
+    let mut previous: Vec<u8> = Vec::new();
+    for number in 0..count {
+        if number % 256 == 0 && Instant::now() > deadline {
+            return None;
+        }
+        let start = pos;
+        let flags = u16::from_be_bytes(data.get(start + 60..start + 62)?.try_into().ok()?);
+        let extended = version >= 3 && flags & 0x4000 != 0;
+        let extended_flags = if extended {
+            u16::from_be_bytes(data.get(start + 62..start + 64)?.try_into().ok()?)
+        } else {
+            0
+        };
+        pos = start + if extended { 64 } else { 62 };
+        let path = if version == 4 {
+            let mut byte = *data.get(pos)?;
+            pos += 1;
+            let mut strip = (byte & 0x7f) as usize;
+            while byte & 0x80 != 0 {
+                byte = *data.get(pos)?;
+                pos += 1;
+                strip = ((strip + 1) << 7) | (byte & 0x7f) as usize;
+            }
+            let end = pos + data.get(pos..)?.iter().position(|&byte| byte == 0)?;
+            let mut path = previous.get(..previous.len().checked_sub(strip)?)?.to_vec();
+            path.extend_from_slice(&data[pos..end]);
+            pos = end + 1;
+            path
+        } else {
+            let end = pos + data.get(pos..)?.iter().position(|&byte| byte == 0)?;
+            let path = data[pos..end].to_vec();
+            pos = start + (end - start + 8) / 8 * 8;
+            path
+        };
+        entries.push(IndexEntry {
+            mtime: be32(&data, start + 8)?,
+            mode: be32(&data, start + 24)?,
+            size: be32(&data, start + 36)?,
+            id: data.get(start + 40..start + 60)?.try_into().ok()?,
+            stage: (flags >> 12) & 3,
+            skip: flags & 0x8000 != 0 || extended_flags & 0x4000 != 0,
+            path: path.clone(),
+        });
+        previous = path;
+    }
+
+    let mut tree = None;
+    while pos + 8 <= data.len().saturating_sub(20) {
+        let size = be32(&data, pos + 4)? as usize;
+        let extension = data.get(pos + 8..pos + 8 + size)?;
+        if &data[pos..pos + 4] == b"TREE" && extension.first() == Some(&0) {
+            let line_end = extension.iter().position(|&byte| byte == b'\n')?;
+            let header = String::from_utf8_lossy(&extension[1..line_end]);
+            if !header.starts_with('-') {
+                tree = extension
+                    .get(line_end + 1..line_end + 21)
+                    .and_then(|id| id.try_into().ok());
+            }
+        }
+        pos += 8 + size;
+    }
+    Some(Index {
+        entries,
+        tree,
+        modified,
+    })
+}
+
+fn unstaged_changes(work_tree: &Path, index: &Index, deadline: Instant) -> Option<bool> {
+    for (number, entry) in index.entries.iter().enumerate() {
+        if number % 256 == 0 && Instant::now() > deadline {
+            return None;
+        }
+        if entry.skip || entry.stage != 0 || entry.mode & 0o170000 == 0o160000 {
+            continue;
+        }
+        let path = work_tree.join(OsStr::from_bytes(&entry.path));
+        match fs::symlink_metadata(path) {
+            Ok(metadata)
+                if metadata.mtime() as u32 == entry.mtime
+                    && metadata.size() as u32 == entry.size => {}
+            _ => return Some(true),
+        }
+    }
+    Some(false)
+}
+
+fn staged_changes(
+    objects: &Objects,
+    index: &Index,
+    head: Option<&ObjectId>,
+    deadline: Instant,
+) -> Option<bool> {
+    let Some(head) = head else {
+        return Some(!index.entries.is_empty());
+    };
+    let tree = objects.commit(head)?.tree?;
+    if index.tree == Some(tree) {
+        return Some(false);
+    }
+    let mut entries = HashMap::new();
+    objects.flatten_tree(&tree, b"", &mut entries, deadline)?;
+    if entries.len() != index.entries.len() {
+        return Some(true);
+    }
+    Some(
+        index
+            .entries
+            .iter()
+            .any(|entry| entry.stage != 0 || entries.get(&entry.path) != Some(&entry.id)),
+    )
+}
+
+fn cached_staged_changes(
+    repository: &Repository,
+    objects: &Objects,
+    index: &Index,
+    head: Option<&ObjectId>,
+    deadline: Instant,
+) -> Option<bool> {
+    let key = (repository.git_dir.clone(), index.modified, head.copied());
+    let mut cache = STAGED_CACHE.lock().ok()?;
+    if let Some((cached, staged)) = cache.as_ref()
+        && *cached == key
+    {
+        return Some(*staged);
+    }
+    let staged = staged_changes(objects, index, head, deadline)?;
+    *cache = Some((key, staged));
+    Some(staged)
+}
+
+fn ahead_behind(
+    objects: &Objects,
+    local: ObjectId,
+    upstream: ObjectId,
+    deadline: Instant,
+) -> Option<(usize, usize)> {
+    let mut commits: HashMap<ObjectId, Commit> = HashMap::new();
+    let mut flags: HashMap<ObjectId, u8> = HashMap::new();
+    let mut queue = BinaryHeap::new();
+    let mut queued = HashSet::new();
+    let mut unsettled = 0;
+    commits.insert(local, objects.commit(&local)?);
+    commits.insert(upstream, objects.commit(&upstream)?);
+    let mut pending = vec![(local, 1u8), (upstream, 2u8)];
+    let mut settled = false;
+    loop {
+        for (id, flag) in pending.drain(..) {
+            let current = flags.entry(id).or_insert(0);
+            if *current | flag == *current || (settled && *current == 0) {
+                continue;
+            }
+            let was_queued = queued.contains(&id);
+            if was_queued && *current != 3 {
+                unsettled -= 1;
+            }
+            *current |= flag;
+            if *current != 3 {
+                unsettled += 1;
+            }
+            if !was_queued {
+                let commit = commits
+                    .entry(id)
+                    .or_insert_with(|| objects.commit(&id).unwrap_or_default());
+                queue.push((commit.time, id));
+                queued.insert(id);
+            }
+        }
+        settled = settled || unsettled == 0;
+        if Instant::now() > deadline {
+            return None;
+        }
+        let Some((_, id)) = queue.pop() else {
+            break;
+        };
+        queued.remove(&id);
+        let flag = flags[&id];
+        if flag != 3 {
+            unsettled -= 1;
+        }
+        pending.extend(commits[&id].parents.iter().map(|parent| (*parent, flag)));
+    }
+    let ahead = flags.values().filter(|&&flag| flag == 1).count();
+    let behind = flags.values().filter(|&&flag| flag == 2).count();
+    Some((ahead, behind))
+}
+
+pub fn git_segment(directory: &str, timeout: Duration) -> String {
+    let deadline = Instant::now() + timeout;
+    let Some(repository) = find_repository(Path::new(directory)) else {
+        return String::new();
+    };
+    let Ok(head) = fs::read_to_string(repository.git_dir.join("HEAD")) else {
+        return String::new();
+    };
+    let head = head.trim();
+    let head_id = resolve_ref(&repository, "HEAD");
+    let branch = head
+        .strip_prefix("ref: ")
+        .map(|reference| reference.strip_prefix("refs/heads/").unwrap_or(reference));
+    let rebasing = ["rebase-merge/head-name", "rebase-apply/head-name"]
+        .iter()
+        .find_map(|file| fs::read_to_string(repository.git_dir.join(file)).ok())
+        .map(|name| {
+            let name = name.trim();
+            name.strip_prefix("refs/heads/").unwrap_or(name).to_string()
+        });
+
+    let mut name = match (branch, &rebasing) {
+        (_, Some(rebasing)) => rebasing.clone(),
+        (Some(branch), None) => branch.to_string(),
+        (None, None) => match head_id {
+            Some(id) => format!("({}...)", short_id(&id)),
+            None => "(unknown)".to_string(),
+        },
+    };
+    if let Some(operation) = operation(&repository.git_dir) {
+        name.push('|');
+        name.push_str(operation);
+    }
+    let mut parts = vec![name];
+
+    let objects = Objects::open(&repository.common_dir);
+    let index_path = repository.git_dir.join("index");
+    if index_path.exists() {
+        let index = read_index(&index_path, deadline);
+        let unstaged = index
+            .as_ref()
+            .and_then(|index| unstaged_changes(&repository.work_tree, index, deadline));
+        let staged = match (&index, unstaged) {
+            (Some(index), Some(_)) => {
+                cached_staged_changes(&repository, &objects, index, head_id.as_ref(), deadline)
+            }
+            _ => None,
+        };
+        let mut state = String::new();
+        if unstaged == Some(true) {
+            state.push('*');
+        }
+        if staged == Some(true) {
+            state.push('+');
+        }
+        if unstaged.is_none() || staged.is_none() {
+            state.push('?');
+        }
+        if !state.is_empty() {
+            parts.push(state);
+        }
+    }
+
+    if let Some(branch) = branch
+        && rebasing.is_none()
+        && let Some(upstream) = upstream_ref(&repository, branch)
+        && let (Some(local), Some(upstream)) = (head_id, resolve_ref(&repository, &upstream))
+    {
+        parts.push(match ahead_behind(&objects, local, upstream, deadline) {
+            Some((0, 0)) => "u=".to_string(),
+            Some((ahead, 0)) => format!("u+{}", ahead),
+            Some((0, behind)) => format!("u-{}", behind),
+            Some((ahead, behind)) => format!("u+{}-{}", ahead, behind),
+            None => "u?".to_string(),
+        });
+    }
+    format!(" ({})", parts.join(" "))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const STORED_BLOB: &str = "\
+        7801010d00f2ff626c6f6220360068656c6c6f0a1dc50414";
+    const FIXED_BLOB: &str = "\
+        78014bcac94f523063c848cdc9c9e702001dc50414";
+    const DYNAMIC_BLOB: &str = "\
+        7801554d5d0e802008eeb95370059f3a0f4c972e4cb6eca5d38798733d30e0fb252e046e734ba808\
+        6d9025ea2557e272422a0ad538c80177e469f08122083eb0dea6de31e719d03f9aca1161da7c7ffe\
+        bfc9e4cad966cce411accc7ad717a379407d";
+    const DELTA: &str = "\
+        c002b802908a912218013691211991b66391ff0593230114";
+    const INDEX_V4: &str = "\
+        4449524300000004000000046ad59e042837b1666ad59e042837b1660000fe000012a263000081a4\
+        0000000000000000000000078178c76d627cade75005b40711b92f4177bc6cfc000600524541444d\
+        45006ad59e042837b1666ad59e042837b1660000fe000012a264000081a400000000000000000000\
+        0000e69de29bb2d1d6434b8b29ae775ad8c2e48c5391000a067372632f6769742e7273006ad59e04\
+        2837b1666ad59e042837b1660000fe000012a265000081a4000000000000000000000000e69de29b\
+        b2d1d6434b8b29ae775ad8c2e48c5391000b066d61696e2e7273006ad59e042842c3d26ad59e0428\
+        37b1660000fe000012a266000081ed000000000000000000000000e69de29bb2d1d6434b8b29ae77\
+        5ad8c2e48c5391000b06656e752e7273005452454500000035003420310abd734034f8db499d87fd\
+        f83878e27b03c69081c4737263003320300ab0185bf22409afa7baa1e464c6e7fe3a3031066cc896\
+        bdc374739638b478a3c20f68b7a51adc1b8c";
+    const COMMITS: [(&str, &str); 7] = [
+        (
+            "7715c69366a7962e51adfcc7dce842452cbaa3c1",
+            "tree c8aeb034b6b1d3f1d84cc0545c83f3a1b0ea9115\nauthor A <a@example.com> 1700000000 +0000\ncommitter A <a@example.com> 1700000000 +0000\n\nbase\n",
+        ),
+        (
+            "b522bb44da051529cab49c22edecc7b92b720dd0",
+            "tree a1cc279a836134a783d1e0bf9d1082cb7da2b2d6\nparent 7715c69366a7962e51adfcc7dce842452cbaa3c1\nauthor A <a@example.com> 1700000000 +0000\ncommitter A <a@example.com> 1700000000 +0000\n\nshared\n",
+        ),
+        (
+            "94aee3677ee7a4dd53b81ddfb4c0f0d7259b16d7",
+            "tree ccaf42a2cb155c2b9e104cef522df3da55a0c5eb\nparent b522bb44da051529cab49c22edecc7b92b720dd0\nauthor A <a@example.com> 1700000000 +0000\ncommitter A <a@example.com> 1700000000 +0000\n\nup1\n",
+        ),
+        (
+            "20ef7b8e0eae9e3f241a48f643a756db6de36f0f",
+            "tree 6c6a60ee6a90e4da762c4dc332e85e90a4edee82\nparent 94aee3677ee7a4dd53b81ddfb4c0f0d7259b16d7\nauthor A <a@example.com> 1700000000 +0000\ncommitter A <a@example.com> 1700000000 +0000\n\nup2\n",
+        ),
+        (
+            "3d2f112a0f5efb1bbcbe3bcb9d3e0c76591dca10",
+            "tree 4c414d53156cc545dbe961fc937bc120cf37663d\nparent b522bb44da051529cab49c22edecc7b92b720dd0\nauthor A <a@example.com> 1700000000 +0000\ncommitter A <a@example.com> 1700000000 +0000\n\nlocal1\n",
+        ),
+        (
+            "0ff7fd22111dc1ca821cbb6060591b5ac33c40b6",
+            "tree ccaf42a2cb155c2b9e104cef522df3da55a0c5eb\nparent 3d2f112a0f5efb1bbcbe3bcb9d3e0c76591dca10\nparent 94aee3677ee7a4dd53b81ddfb4c0f0d7259b16d7\nauthor A <a@example.com> 1700000000 +0000\ncommitter A <a@example.com> 1700000000 +0000\n\nMerge branch 'up'\n",
+        ),
+        (
+            "452735c5f09945f95790b5800e72f718f3294341",
+            "tree cb94290bed375516661bd54f11bab22bd8d425a6\nparent 0ff7fd22111dc1ca821cbb6060591b5ac33c40b6\nauthor A <a@example.com> 1700000000 +0000\ncommitter A <a@example.com> 1700000000 +0000\n\nlocal2\n",
+        ),
+    ];
+
+    fn bytes(hex: &str) -> Vec<u8> {
+        (0..hex.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).unwrap())
+            .collect()
+    }
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let directory =
+            std::env::temp_dir().join(format!("shell-git-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&directory);
+        fs::create_dir_all(&directory).unwrap();
+        directory
+    }
+
+    fn write_loose(objects: &Path, id: &str, kind: &str, body: &str) {
+        let mut object = format!("{} {}\0{}", kind, body.len(), body).into_bytes();
+        let (mut a, mut b) = (1u32, 0u32);
+        for &byte in &object {
+            a = (a + byte as u32) % 65521;
+            b = (b + a) % 65521;
+        }
+        let len = object.len() as u16;
+        let mut data = vec![0x78, 0x01, 0x01];
+        data.extend_from_slice(&len.to_le_bytes());
+        data.extend_from_slice(&(!len).to_le_bytes());
+        data.append(&mut object);
+        data.extend_from_slice(&((b << 16) | a).to_be_bytes());
+        let directory = objects.join(&id[..2]);
+        fs::create_dir_all(&directory).unwrap();
+        fs::write(directory.join(&id[2..]), data).unwrap();
+    }
+
+    #[test]
+    fn inflate_stored_block() {
+        assert_eq!(
+            inflate(&bytes(STORED_BLOB), 13),
+            Some(b"blob 6\0hello\n".to_vec())
+        );
+    }
+
+    #[test]
+    fn inflate_fixed_block() {
+        assert_eq!(
+            inflate(&bytes(FIXED_BLOB), 13),
+            Some(b"blob 6\0hello\n".to_vec())
+        );
+    }
+
+    #[test]
+    fn inflate_dynamic_block() {
+        let text = "eta eta alpha epsilon iota theta eta epsilon theta zeta kappa delta iota gamma \
+            epsilon gamma beta kappa epsilon iota mu kappa gamma epsilon beta mu beta lambda zeta theta\n";
+        assert_eq!(
+            inflate(&bytes(DYNAMIC_BLOB), 180),
+            Some(format!("blob {}\0{}", text.len(), text).into_bytes())
+        );
+    }
+
+    #[test]
+    fn inflate_rejects_missing_header() {
+        assert_eq!(inflate(&bytes(FIXED_BLOB)[2..], 13), None);
+    }
+
+    #[test]
+    fn apply_delta_copies_and_inserts() {
+        let original: String = (0..12)
+            .map(|line| format!("line {:02} of the delta base\n", line))
+            .collect();
+        let base = original
+            .replace("line 05 of the delta base", "line 05 was changed")
+            .replace("line 11", "last line 11")
+            + "appended\n";
+        assert_eq!(
+            apply_delta(base.as_bytes(), &bytes(DELTA)),
+            Some(original.into_bytes())
+        );
+        assert_eq!(apply_delta(&base.as_bytes()[1..], &bytes(DELTA)), None);
+    }
+
+    #[test]
+    fn apply_delta_rejects_oversized_lengths() {
+        assert_eq!(apply_delta(b"", &[0xff; 16]), None);
+        assert_eq!(apply_delta(b"", &[0x00, 0xff, 0xff]), None);
+    }
+
+    #[test]
+    fn read_index_stops_at_deadline() {
+        let directory = temp_dir("deadline");
+        let path = directory.join("index");
+        fs::write(&path, bytes(INDEX_V4)).unwrap();
+        let index = read_index(&path, Instant::now() - Duration::from_secs(1));
+        fs::remove_dir_all(&directory).unwrap();
+        assert!(index.is_none());
+    }
+
+    #[test]
+    fn read_index_v4_paths() {
+        let directory = temp_dir("index");
+        let path = directory.join("index");
+        fs::write(&path, bytes(INDEX_V4)).unwrap();
+        let index = read_index(&path, Instant::now() + Duration::from_secs(10)).unwrap();
+        fs::remove_dir_all(&directory).unwrap();
+
+        let paths: Vec<&[u8]> = index.entries.iter().map(|entry| &entry.path[..]).collect();
+        assert_eq!(
+            paths,
+            [
+                &b"README"[..],
+                b"src/git.rs",
+                b"src/main.rs",
+                b"src/menu.rs"
+            ]
+        );
+        let modes: Vec<u32> = index.entries.iter().map(|entry| entry.mode).collect();
+        assert_eq!(modes, [0o100644, 0o100644, 0o100644, 0o100755]);
+        assert_eq!(index.entries[0].size, 7);
+        assert_eq!(
+            Some(index.entries[0].id),
+            parse_id("8178c76d627cade75005b40711b92f4177bc6cfc")
+        );
+        assert_eq!(
+            index.tree,
+            parse_id("bd734034f8db499d87fdf83878e27b03c69081c4")
+        );
+    }
+
+    #[test]
+    fn ahead_behind_with_merge_and_equal_times() {
+        let directory = temp_dir("commits");
+        for (id, body) in COMMITS {
+            write_loose(&directory.join("objects"), id, "commit", body);
+        }
+        let objects = Objects::open(&directory);
+        let deadline = Instant::now() + Duration::from_secs(10);
+        let local = parse_id("452735c5f09945f95790b5800e72f718f3294341").unwrap();
+        let upstream = parse_id("20ef7b8e0eae9e3f241a48f643a756db6de36f0f").unwrap();
+        let counts = ahead_behind(&objects, local, upstream, deadline);
+        let same = ahead_behind(&objects, local, local, deadline);
+        fs::remove_dir_all(&directory).unwrap();
+
+        assert_eq!(counts, Some((3, 1)));
+        assert_eq!(same, Some((0, 0)));
+    }
+}
@@ -0,0 +1,287 @@
+use crate::builtins::is_builtin;
+use crate::shell::Shell;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::io::Write;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+pub fn is_executable(metadata: &fs::Metadata) -> bool {
+    metadata.is_file() && (metadata.permissions().mode() & 0o111 != 0)
+}
+
+fn is_executable_path(path: &Path) -> bool {
+    fs::metadata(path).is_ok_and(|metadata| is_executable(&metadata))
+}
+
+struct DirectoryCache {
+    dir: PathBuf,
+    modified: Option<SystemTime>,
+    executables: BTreeSet<String>,
+}
+
+impl DirectoryCache {
+    fn scan(dir: &str) -> Self {
+        let mut cache = DirectoryCache {
+            dir: PathBuf::from(if dir.is_empty() { "." } else { dir }),
+            modified: None,
+            executables: BTreeSet::new(),
+        };
+        cache.rescan();
+        cache
+    }
+
+    fn rescan(&mut self) {
+        self.modified = fs::metadata(&self.dir).and_then(|m| m.modified()).ok();
+        self.executables.clear();
+
+        if let Ok(entries) = fs::read_dir(&self.dir) {
+            for entry in entries.flatten() {
+                if let Ok(metadata) = fs::metadata(entry.path())
+                    && is_executable(&metadata)
+                {
+                    self.executables
+                        .insert(entry.file_name().to_string_lossy().into_owned());
+                }
+            }
+        }
+    }
+
+    fn refresh(&mut self) {
+        if self.dir.is_relative() {
+            return;
+        }
+        let modified = fs::metadata(&self.dir).and_then(|m| m.modified()).ok();
+        if modified != self.modified {
+            self.rescan();
+        }
+    }
+
+    fn contains(&self, name: &str) -> bool {
+        if self.dir.is_relative() {
+            is_executable_path(&self.dir.join(name))
+        } else {
+            self.executables.contains(name)
+        }
+    }
+}
+
+struct HashedCommand {
+    path: PathBuf,
+    hits: usize,
+}
+
+#[derive(Default)]
+pub struct CommandHash {
+    path_var: Option<String>,
+    directories: Vec<DirectoryCache>,
+    commands: BTreeMap<String, HashedCommand>,
+}
+
+impl CommandHash {
+    fn refresh(&mut self, path_var: &str) {
+        if self.path_var.as_deref() != Some(path_var) {
+            self.directories = path_var.split(':').map(DirectoryCache::scan).collect();
+            self.commands.clear();
+            self.path_var = Some(path_var.to_string());
+        } else {
+            for directory in self.directories.iter_mut() {
+                directory.refresh();
+            }
+        }
+    }
+
+    fn search(&self, name: &str) -> Option<PathBuf> {
+        self.directories
+            .iter()
+            .find(|directory| directory.contains(name))
+            .map(|directory| directory.dir.join(name))
+            .filter(|path| is_executable_path(path))
+    }
+
+    pub fn lookup(&mut self, path_var: &str, name: &str) -> Option<PathBuf> {
+        if name.contains('/') {
+            let path = PathBuf::from(name);
+            return is_executable_path(&path).then_some(path);
+        }
+
+        self.refresh(path_var);
+        match self.commands.get(name) {
+            Some(hashed) if is_executable_path(&hashed.path) => Some(hashed.path.clone()),
+            _ => self.search(name),
+        }
+    }
+
+    fn remember(&mut self, path_var: &str, name: &str, hit: bool) -> Option<PathBuf> {
+        let path = self.lookup(path_var, name)?;
+        if name.contains('/') {
+            return Some(path);
+        }
+
+        let hashed = self
+            .commands
+            .entry(name.to_string())
+            .or_insert_with(|| HashedCommand {
+                path: path.clone(),
+                hits: 0,
+            });
+        if hashed.path != path {
+            hashed.path = path.clone();
+            hashed.hits = 0;
+        }
+        if hit {
+            hashed.hits += 1;
+        }
+        Some(path)
+    }
+
+    pub fn record_hit(&mut self, path_var: &str, name: &str) -> Option<PathBuf> {
+        self.remember(path_var, name, true)
+    }
+
+    pub fn executables_with_prefix(&mut self, path_var: &str, prefix: &str) -> BTreeSet<String> {
+        self.refresh(path_var);
+        for directory in self.directories.iter_mut() {
+            if directory.dir.is_relative() {
+                directory.rescan();
+            }
+        }
+        self.directories
+            .iter()
+            .flat_map(|directory| {
+                directory
+                    .executables
+                    .range(prefix.to_string()..)
+                    .take_while(|name| name.starts_with(prefix))
+            })
+            .cloned()
+            .collect()
+    }
+}
+
+pub fn hash_builtin(
+    args: &[String],
+    shell: &mut Shell,
+    out: &mut dyn Write,
+    err: &mut dyn Write,
+) -> i32 {
+    let path_var = shell.variables.get("PATH").unwrap_or_default().to_string();
+    let hash = &mut shell.command_hash;
+
+    let mut reset = false;
+    let mut delete = false;
+    let mut reusable = false;
+    let mut print_path = false;
+    let mut explicit_path: Option<String> = None;
+    let mut names: Vec<&String> = Vec::new();
+
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        if !names.is_empty() || !arg.starts_with('-') || arg.len() == 1 {
+            names.push(arg);
+            continue;
+        }
+        for flag in arg[1..].chars() {
+            match flag {
+                'r' => reset = true,
+                'd' => delete = true,
+                'l' => reusable = true,
+                't' => print_path = true,
+                'p' => match iter.next() {
+                    Some(path) => explicit_path = Some(path.clone()),
+                    None => {
+                        let _ = writeln!(err, "hash: -p: option requires an argument");
+                        return 1;
+                    }
+                },
+                _ => {
+                    let _ = writeln!(err, "hash: -{}: invalid option", flag);
+                    let _ = writeln!(
+                        err,
+                        "hash: usage: hash [-lr] [-p pathname] [-dt] [name ...]"
+                    );
+                    return 2;
+                }
+            }
+        }
+    }
+
+    if reset {
+        hash.commands.clear();
+    }
+
+    if let Some(path) = explicit_path {
+        for name in names {
+            hash.commands.insert(
+                name.clone(),
+                HashedCommand {
+                    path: PathBuf::from(&path),
+                    hits: 0,
+                },
+            );
+        }
+        return 0;
+    }
+
+    let mut status = 0;
+    if delete {
+        for name in names {
+            if hash.commands.remove(name.as_str()).is_none() {
+                let _ = writeln!(err, "hash: {}: not found", name);
+                status = 1;
+            }
+        }
+        return status;
+    }
+
+    if print_path {
+        for name in &names {
+            match hash.commands.get(name.as_str()) {
+                Some(hashed) if names.len() > 1 => {
+                    let _ = writeln!(out, "{}\t{}", name, hashed.path.display());
+                }
+                Some(hashed) => {
+                    let _ = writeln!(out, "{}", hashed.path.display());
+                }
+                None => {
+                    let _ = writeln!(err, "hash: {}: not found", name);
+                    status = 1;
+                }
+            }
+        }
+        return status;
+    }
+
+    if names.is_empty() {
+        if reset {
+            return 0;
+        }
+        if hash.commands.is_empty() {
+            let _ = writeln!(err, "hash: hash table empty");
+            return 0;
+        }
+        if !reusable {
+            let _ = writeln!(out, "hits\tcommand");
+        }
+        for (name, hashed) in &hash.commands {
+            if reusable {
+                let _ = writeln!(out, "builtin hash -p {} {}", hashed.path.display(), name);
+            } else {
+                let _ = writeln!(out, "{:4}\t{}", hashed.hits, hashed.path.display());
+            }
+        }
+        return 0;
+    }
+
+    for name in names {
+        if is_builtin(name) {
+            continue;
+        }
+        if hash.remember(&path_var, name, false).is_none() {
+            let _ = writeln!(err, "hash: {}: not found", name);
+            status = 1;
+        }
+    }
+    status
+}
@@ -0,0 +1,963 @@
+use crate::executor::execute_command_line;
+use crate::shell::Shell;
+use crate::terminal::{disable_raw_mode, enable_raw_mode};
+use std::env;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufRead, BufReader, Write};
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::PathBuf;
+use std::process::Command;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+pub fn load_history_file(filepath: &str, history: &mut Vec<String>) -> io::Result<()> {
+    let reader = BufReader::new(File::open(filepath)?);
+    for line in reader.lines() {
+        let cmd = line?;
+        let trimmed = cmd.trim();
+        if !trimmed.is_empty() {
+            history.push(trimmed.to_string());
+        }
+    }
+    Ok(())
+}
+
+fn parse_offset(offset: &str, len: usize) -> Option<usize> {
+    let value: i64 = offset.parse().ok()?;
+    let index = if value < 0 {
+        len as i64 + value
+    } else {
+        value - 1
+    };
+    if index < 0 || index as usize >= len {
+        None
+    } else {
+        Some(index as usize)
+    }
+}
+
+fn delete_entries(
+    spec: &str,
+    history: &mut Vec<String>,
+    last_saved_index: &mut usize,
+    err: &mut dyn Write,
+) -> bool {
+    let len = history.len();
+    let unsigned = spec.strip_prefix('-').unwrap_or(spec);
+    let sign = &spec[..spec.len() - unsigned.len()];
+    let range = match unsigned.split_once('-') {
+        Some((start, end)) => {
+            parse_offset(&format!("{}{}", sign, start), len).zip(parse_offset(end, len))
+        }
+        None => parse_offset(spec, len).map(|index| (index, index)),
+    };
+
+    match range {
+        Some((start, end)) if start <= end => {
+            history.drain(start..=end);
+            if *last_saved_index > start {
+                *last_saved_index = last_saved_index.saturating_sub(end + 1 - start).max(start);
+            }
+            true
+        }
+        _ => {
+            let _ = writeln!(err, "history: {}: history position out of range", spec);
+            false
+        }
+    }
+}
+
+fn take_current_line(shell: &mut Shell) -> Option<String> {
+    if !std::mem::take(&mut shell.current_line_added) {
+        return None;
+    }
+    shell.history.pop()
+}
+
+pub fn history_builtin(
+    args: &[String],
+    shell: &mut Shell,
+    out: &mut dyn Write,
+    err: &mut dyn Write,
+) -> i32 {
+    if matches!(args.first().map(|s| s.as_str()), Some("-s" | "-p")) {
+        take_current_line(shell);
+    }
+    let history = &mut shell.history;
+    let last_saved_index = &mut shell.last_saved_index;
+    let mut status = 0;
+    match args.first().map(|s| s.as_str()) {
+        Some("-c") => {
+            history.clear();
+            *last_saved_index = 0;
+        }
+        Some("-d") => match args.get(1) {
+            Some(spec) if !spec.is_empty() => {
+                if !delete_entries(spec, history, last_saved_index, err) {
+                    status = 1;
+                }
+            }
+            _ => {
+                let _ = writeln!(err, "history: -d: option requires an argument");
+                status = 2;
+            }
+        },
+        Some("-s") => {
+            if args.len() > 1 {
+                history.push(args[1..].join(" "));
+            }
+        }
+        Some("-p") => {
+            for arg in &args[1..] {
+                match expand_history(arg, history) {
+                    Ok((expanded, _)) => {
+                        let _ = writeln!(out, "{}", expanded);
+                    }
+                    Err(e) => {
+                        let _ = writeln!(err, "history: {}", e);
+                        status = 1;
+                        break;
+                    }
+                }
+            }
+        }
+        Some("-r") => {
+            if let Some(filepath) = args.get(1) {
+                if let Err(e) = load_history_file(filepath, history) {
+                    let _ = writeln!(err, "history: {}: {}", filepath, e);
+                    status = 1;
+                }
+            } else {
+                let _ = writeln!(err, "history: -r requires a filename argument");
+                status = 1;
+            }
+        }
+        Some("-w") => {
+            if let Some(filepath) = args.get(1) {
+                match File::create(filepath) {
+                    Ok(mut file) => {
+                        for cmd in history.iter() {
+                            if let Err(e) = writeln!(file, "{}", cmd) {
+                                let _ = writeln!(err, "Error writing to history file: {}", e);
+                                status = 1;
+                                break;
+                            }
+                        }
+                    }
+                    Err(e) => {
+                        let _ = writeln!(err, "history: {}: {}", filepath, e);
+                        status = 1;
+                    }
+                }
+            } else {
+                let _ = writeln!(err, "history: -w requires a filename argument");
+                status = 1;
+            }
+        }
+        Some("-a") => {
+            if let Some(filepath) = args.get(1) {
+                match OpenOptions::new().create(true).append(true).open(filepath) {
+                    Ok(mut file) => {
+                        for cmd in history.iter().skip(*last_saved_index) {
+                            if let Err(e) = writeln!(file, "{}", cmd) {
+                                let _ = writeln!(err, "Error writing to history file: {}", e);
+                                status = 1;
+                                break;
+                            }
+                        }
+                        *last_saved_index = history.len();
+                    }
+                    Err(e) => {
+                        let _ = writeln!(err, "history: {}: {}", filepath, e);
+                        status = 1;
+                    }
+                }
+            } else {
+                let _ = writeln!(err, "history: -a requires a filename argument");
+                status = 1;
+            }
+        }
+        arg => {
+            let n: usize = arg.and_then(|s| s.parse().ok()).unwrap_or(history.len());
+            let start = history.len().saturating_sub(n);
+            for (i, cmd) in history.iter().enumerate().skip(start) {
+                let _ = writeln!(out, "{:5}  {}", i + 1, cmd);
+            }
+        }
+    }
+    status
+}
+
+fn find_event(spec: &str, history: &[String]) -> Option<usize> {
+    if history.is_empty() {
+        return None;
+    }
+    if let Ok(value) = spec.parse::<i64>() {
+        let index = if value < 0 {
+            history.len() as i64 + value
+        } else {
+            value - 1
+        };
+        return Some(index.clamp(0, history.len() as i64 - 1) as usize);
+    }
+    history.iter().rposition(|cmd| cmd.starts_with(spec))
+}
+
+fn run_fc_commands(commands: Vec<String>, shell: &mut Shell) -> i32 {
+    for cmd in commands {
+        let trimmed = cmd.trim();
+        if trimmed.is_empty() {
+            continue;
+        }
+        println!("{}", trimmed);
+        shell.history.push(trimmed.to_string());
+        if execute_command_line(trimmed, shell) {
+            break;
+        }
+    }
+    shell.variables.status()
+}
+
+fn fc_substitute(args: &[String], shell: &mut Shell, err: &mut dyn Write) -> i32 {
+    let (substitutions, rest): (Vec<&String>, Vec<&String>) =
+        args.iter().partition(|arg| arg.contains('='));
+    let spec = rest.first().map(|s| s.as_str()).unwrap_or("-1");
+
+    let Some(index) = find_event(spec, &shell.history) else {
+        let _ = writeln!(err, "fc: no command found");
+        return 1;
+    };
+
+    let mut cmd = shell.history[index].clone();
+    for substitution in substitutions {
+        if let Some((old, new)) = substitution.split_once('=')
+            && !old.is_empty()
+        {
+            cmd = cmd.replace(old, new);
+        }
+    }
+    run_fc_commands(vec![cmd], shell)
+}
+
+struct FcOptions {
+    list: bool,
+    numbered: bool,
+    reverse: bool,
+    substitute: bool,
+    editor: Option<String>,
+    operands: Vec<String>,
+}
+
+fn parse_fc_options(args: &[String], err: &mut dyn Write) -> Option<FcOptions> {
+    let mut options = FcOptions {
+        list: false,
+        numbered: true,
+        reverse: false,
+        substitute: false,
+        editor: None,
+        operands: Vec::new(),
+    };
+
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        if !options.operands.is_empty()
+            || !arg.starts_with('-')
+            || arg.len() == 1
+            || arg[1..].parse::<i64>().is_ok()
+        {
+            options.operands.push(arg.clone());
+            continue;
+        }
+        for flag in arg[1..].chars() {
+            match flag {
+                'l' => options.list = true,
+                'n' => options.numbered = false,
+                'r' => options.reverse = true,
+                's' => options.substitute = true,
+                'e' => match iter.next() {
+                    Some(name) => options.editor = Some(name.clone()),
+                    None => {
+                        let _ = writeln!(err, "fc: -e: option requires an argument");
+                        return None;
+                    }
+                },
+                _ => {
+                    let _ = writeln!(err, "fc: -{}: invalid option", flag);
+                    let _ = writeln!(
+                        err,
+                        "fc: usage: fc [-e ename] [-lnr] [first] [last] or fc -s [pat=rep] [command]"
+                    );
+                    return None;
+                }
+            }
+        }
+    }
+    Some(options)
+}
+
+fn select_fc_range(
+    operands: &[String],
+    list: bool,
+    reverse: bool,
+    history: &[String],
+    err: &mut dyn Write,
+) -> Option<Vec<(usize, String)>> {
+    if history.is_empty() {
+        let _ = writeln!(err, "fc: history specification out of range");
+        return None;
+    }
+
+    let default_first = if list { "-16" } else { "-1" };
+    let first = operands
+        .first()
+        .map(|s| s.as_str())
+        .unwrap_or(default_first);
+    let last = operands
+        .get(1)
+        .map(|s| s.as_str())
+        .unwrap_or(if list || operands.is_empty() {
+            "-1"
+        } else {
+            first
+        });
+
+    let (Some(mut start), Some(mut end)) = (find_event(first, history), find_event(last, history))
+    else {
+        let _ = writeln!(err, "fc: history specification out of range");
+        return None;
+    };
+
+    let mut descending = reverse;
+    if start > end {
+        std::mem::swap(&mut start, &mut end);
+        descending = !descending;
+    }
+
+    let mut selected: Vec<(usize, String)> =
+        (start..=end).map(|i| (i + 1, history[i].clone())).collect();
+    if descending {
+        selected.reverse();
+    }
+    Some(selected)
+}
+
+fn create_temp_file(contents: &str) -> io::Result<PathBuf> {
+    let mut seed = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_nanos() as u64)
+        ^ ((std::process::id() as u64) << 32)
+        | 1;
+    loop {
+        seed ^= seed << 13;
+        seed ^= seed >> 7;
+        seed ^= seed << 17;
+        let path = env::temp_dir().join(format!("shell-fc-{:016x}.sh", seed));
+        match OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .mode(0o600)
+            .open(&path)
+        {
+            Ok(mut file) => {
+                if let Err(e) = file.write_all(contents.as_bytes()) {
+                    let _ = fs::remove_file(&path);
+                    return Err(e);
+                }
+                return Ok(path);
+            }
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists => continue,
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+pub fn fc_builtin(
+    args: &[String],
+    shell: &mut Shell,
+    out: &mut dyn Write,
+    err: &mut dyn Write,
+) -> i32 {
+    let Some(FcOptions {
+        list,
+        numbered,
+        reverse,
+        substitute,
+        editor,
+        operands,
+    }) = parse_fc_options(args, err)
+    else {
+        return 2;
+    };
+
+    let current = take_current_line(shell);
+
+    if substitute || editor.as_deref() == Some("-") {
+        return fc_substitute(&operands, shell, err);
+    }
+
+    let selected = select_fc_range(&operands, list, reverse, &shell.history, err);
+
+    if list {
+        if let Some(current) = current {
+            shell.history.push(current);
+            shell.current_line_added = true;
+        }
+        for (number, cmd) in selected.iter().flatten() {
+            if numbered {
+                let _ = writeln!(out, "{}\t {}", number, cmd);
+            } else {
+                let _ = writeln!(out, "\t {}", cmd);
+            }
+        }
+        return if selected.is_some() { 0 } else { 1 };
+    }
+
+    let Some(selected) = selected else {
+        return 1;
+    };
+
+    let editor = editor
+        .or_else(|| shell.variables.get("FCEDIT").map(|s| s.to_string()))
+        .or_else(|| shell.variables.get("EDITOR").map(|s| s.to_string()))
+        .unwrap_or_else(|| "vi".to_string());
+
+    let contents: String = selected
+        .iter()
+        .map(|(_, cmd)| format!("{}\n", cmd))
+        .collect();
+    let path = match create_temp_file(&contents) {
+        Ok(path) => path,
+        Err(e) => {
+            let _ = writeln!(err, "fc: cannot create temp file: {}", e);
+            return 1;
+        }
+    };
+
+    let mut editor_words = editor.split_whitespace();
+    let program = editor_words.next().unwrap_or("vi");
+
+    disable_raw_mode();
+    let status = Command::new(program)
+        .args(editor_words)
+        .arg(&path)
+        .env_clear()
+        .envs(shell.variables.exported())
+        .status();
+    enable_raw_mode();
+
+    let edited = match status {
+        Ok(status) if status.success() => fs::read_to_string(&path),
+        Ok(_) => {
+            let _ = fs::remove_file(&path);
+            return 1;
+        }
+        Err(e) => {
+            let _ = writeln!(err, "fc: {}: {}", program, e);
+            let _ = fs::remove_file(&path);
+            return 1;
+        }
+    };
+    let _ = fs::remove_file(&path);
+
+    match edited {
+        Ok(text) => run_fc_commands(text.lines().map(|line| line.to_string()).collect(), shell),
+        Err(e) => {
+            let _ = writeln!(err, "fc: {}", e);
+            1
+        }
+    }
+}
+
+fn split_history_words(line: &str) -> Vec<String> {
+    let mut words = Vec::new();
+    let mut current = String::new();
+    let mut quote: Option<char> = None;
+    let mut escaped = false;
+
+    for c in line.chars() {
+        if escaped {
+            current.push(c);
+            escaped = false;
+        } else if let Some(q) = quote {
+            current.push(c);
+            if c == q {
+                quote = None;
+            }
+        } else if c == '\\' {
+            current.push(c);
+            escaped = true;
+        } else if c == '\'' || c == '"' {
+            current.push(c);
+            quote = Some(c);
+        } else if c.is_whitespace() {
+            if !current.is_empty() {
+                words.push(std::mem::take(&mut current));
+            }
+        } else {
+            current.push(c);
+        }
+    }
+
+    if !current.is_empty() {
+        words.push(current);
+    }
+    words
+}
+
+fn parse_word_index(
+    chars: &[char],
+    pos: &mut usize,
+    last: usize,
+    matched: Option<usize>,
+) -> Option<usize> {
+    match chars.get(*pos) {
+        Some('%') => {
+            *pos += 1;
+            matched
+        }
+        Some('^') => {
+            *pos += 1;
+            Some(1)
+        }
+        Some('$') => {
+            *pos += 1;
+            Some(last)
+        }
+        Some(c) if c.is_ascii_digit() => {
+            let start = *pos;
+            while chars.get(*pos).is_some_and(|c| c.is_ascii_digit()) {
+                *pos += 1;
+            }
+            chars[start..*pos].iter().collect::<String>().parse().ok()
+        }
+        _ => None,
+    }
+}
+
+fn select_words(
+    words: &[String],
+    chars: &[char],
+    pos: &mut usize,
+    matched: Option<usize>,
+) -> Result<String, String> {
+    let start_pos = *pos;
+    let last = words.len().saturating_sub(1);
+    let bad_specifier = |pos: usize| {
+        let spec: String = chars[start_pos..pos.min(chars.len())].iter().collect();
+        format!("{}: bad word specifier", spec)
+    };
+
+    let (first, end) = if chars.get(*pos) == Some(&'*') {
+        *pos += 1;
+        if words.len() < 2 {
+            return Ok(String::new());
+        }
+        (1, last)
+    } else if chars.get(*pos) == Some(&'-') {
+        *pos += 1;
+        let end = parse_word_index(chars, pos, last, matched).unwrap_or(last.saturating_sub(1));
+        (0, end)
+    } else {
+        let Some(first) = parse_word_index(chars, pos, last, matched) else {
+            return Err(bad_specifier(*pos + 1));
+        };
+        match chars.get(*pos) {
+            Some('*') => {
+                *pos += 1;
+                (first, last)
+            }
+            Some('-') => {
+                *pos += 1;
+                let end =
+                    parse_word_index(chars, pos, last, matched).unwrap_or(last.saturating_sub(1));
+                (first, end)
+            }
+            _ => (first, first),
+        }
+    };
+
+    if first > end || end > last || words.is_empty() {
+        return Err(bad_specifier(*pos));
+    }
+    Ok(words[first..=end].join(" "))
+}
+
+fn parse_delimited(chars: &[char], pos: &mut usize, delimiter: char) -> String {
+    let mut text = String::new();
+    while let Some(&c) = chars.get(*pos) {
+        *pos += 1;
+        if c == delimiter {
+            break;
+        }
+        if c == '\\' && chars.get(*pos) == Some(&delimiter) {
+            text.push(delimiter);
+            *pos += 1;
+        } else {
+            text.push(c);
+        }
+    }
+    text
+}
+
+fn apply_substitution(text: &str, old: &str, new: &str, global: bool) -> Result<String, String> {
+    if old.is_empty() || !text.contains(old) {
+        return Err(":s: substitution failed".to_string());
+    }
+    let replacement = new.replace('&', old);
+    if global {
+        Ok(text.replace(old, &replacement))
+    } else {
+        Ok(text.replacen(old, &replacement, 1))
+    }
+}
+
+fn apply_modifiers(
+    mut text: String,
+    chars: &[char],
+    pos: &mut usize,
+    last_substitution: &mut Option<(String, String)>,
+    print_only: &mut bool,
+) -> Result<String, String> {
+    while chars.get(*pos) == Some(&':') {
+        let Some(&modifier) = chars.get(*pos + 1) else {
+            break;
+        };
+        *pos += 2;
+
+        let (global, modifier) = if modifier == 'g' || modifier == 'a' {
+            let next = chars.get(*pos).copied().unwrap_or(' ');
+            *pos += 1;
+            (true, next)
+        } else {
+            (false, modifier)
+        };
+
+        match modifier {
+            'h' => {
+                if let Some(index) = text.rfind('/') {
+                    text.truncate(index.max(1));
+                }
+            }
+            't' => {
+                if let Some(index) = text.rfind('/') {
+                    text = text[index + 1..].to_string();
+                }
+            }
+            'r' => {
+                if let Some(index) = text.rfind('.')
+                    && !text[index..].contains('/')
+                {
+                    text.truncate(index);
+                }
+            }
+            'e' => {
+                text = match text.rfind('.') {
+                    Some(index) if !text[index..].contains('/') => text[index..].to_string(),
+                    _ => String::new(),
+                };
+            }
+            'p' => *print_only = true,
+            'q' => text = format!("'{}'", text.replace('\'', "'\\''")),
+            's' => {
+                let Some(&delimiter) = chars.get(*pos) else {
+                    return Err(":s: substitution failed".to_string());
+                };
+                *pos += 1;
+                let mut old = parse_delimited(chars, pos, delimiter);
+                let new = parse_delimited(chars, pos, delimiter);
+                if old.is_empty() {
+                    old = last_substitution
+                        .as_ref()
+                        .map(|s| s.0.clone())
+                        .unwrap_or_default();
+                }
+                text = apply_substitution(&text, &old, &new, global)?;
+                *last_substitution = Some((old, new));
+            }
+            '&' => {
+                let Some((old, new)) = last_substitution.clone() else {
+                    return Err(":&: no previous substitution".to_string());
+                };
+                text = apply_substitution(&text, &old, &new, global)?;
+            }
+            other => return Err(format!(":{}: unrecognized history modifier", other)),
+        }
+    }
+    Ok(text)
+}
+
+fn find_event_line<'a>(
+    chars: &[char],
+    pos: &mut usize,
+    history: &'a [String],
+    current: &str,
+    last_search: &mut Option<String>,
+) -> Result<std::borrow::Cow<'a, str>, String> {
+    let start = *pos;
+    let not_found = |pos: usize| {
+        let spec: String = chars[start - 1..pos].iter().collect();
+        format!("{}: event not found", spec)
+    };
+
+    match chars.get(*pos) {
+        Some('!') => {
+            *pos += 1;
+            history
+                .last()
+                .map(|cmd| cmd.as_str().into())
+                .ok_or_else(|| not_found(*pos))
+        }
+        Some('#') => {
+            *pos += 1;
+            Ok(current.to_string().into())
+        }
+        Some('?') => {
+            *pos += 1;
+            let needle: String = chars[*pos..].iter().take_while(|&&c| c != '?').collect();
+            *pos += needle.chars().count();
+            if chars.get(*pos) == Some(&'?') {
+                *pos += 1;
+            }
+            *last_search = Some(needle.clone());
+            history
+                .iter()
+                .rev()
+                .find(|cmd| cmd.contains(&needle))
+                .map(|cmd| cmd.as_str().into())
+                .ok_or_else(|| not_found(*pos))
+        }
+        Some(c) if c.is_ascii_digit() || *c == '-' => {
+            *pos += 1;
+            while chars.get(*pos).is_some_and(|c| c.is_ascii_digit()) {
+                *pos += 1;
+            }
+            let number: i64 = chars[start..*pos]
+                .iter()
+                .collect::<String>()
+                .parse()
+                .map_err(|_| not_found(*pos))?;
+            let index = if number < 0 {
+                history.len() as i64 + number
+            } else {
+                number - 1
+            };
+            usize::try_from(index)
+                .ok()
+                .and_then(|index| history.get(index))
+                .map(|cmd| cmd.as_str().into())
+                .ok_or_else(|| not_found(*pos))
+        }
+        _ => {
+            while chars
+                .get(*pos)
+                .is_some_and(|c| !c.is_whitespace() && !":;|&()<>'\"".contains(*c))
+            {
+                *pos += 1;
+            }
+            let prefix: String = chars[start..*pos].iter().collect();
+            history
+                .iter()
+                .rev()
+                .find(|cmd| cmd.starts_with(&prefix))
+                .map(|cmd| cmd.as_str().into())
+                .ok_or_else(|| not_found(*pos))
+        }
+    }
+}
+
+pub fn expand_history(line: &str, history: &[String]) -> Result<(String, bool), String> {
+    let mut print_only = false;
+    let mut last_substitution: Option<(String, String)> = None;
+    let mut last_search: Option<String> = None;
+
+    if let Some(rest) = line.strip_prefix('^') {
+        let chars: Vec<char> = rest.chars().collect();
+        let mut pos = 0;
+        let old = parse_delimited(&chars, &mut pos, '^');
+        let new = parse_delimited(&chars, &mut pos, '^');
+        let Some(previous) = history.last() else {
+            return Err("^: event not found".to_string());
+        };
+        let mut expanded = apply_substitution(previous, &old, &new, false)?;
+        last_substitution = Some((old, new));
+        let tail: String = chars[pos..].iter().collect();
+        let tail_chars: Vec<char> = tail.chars().collect();
+        let mut tail_pos = 0;
+        expanded = apply_modifiers(
+            expanded,
+            &tail_chars,
+            &mut tail_pos,
+            &mut last_substitution,
+            &mut print_only,
+        )?;
+        expanded.extend(&tail_chars[tail_pos..]);
+        return Ok((expanded, print_only));
+    }
+
+    let chars: Vec<char> = line.chars().collect();
+    let mut result = String::new();
+    let mut in_single_quote = false;
+    let mut in_double_quote = false;
+    let mut pos = 0;
+
+    while pos < chars.len() {
+        let c = chars[pos];
+
+        if c == '\\' && !in_single_quote {
+            result.push(c);
+            if let Some(&next) = chars.get(pos + 1) {
+                result.push(next);
+            }
+            pos += 2;
+            continue;
+        }
+        if c == '\'' && !in_double_quote {
+            in_single_quote = !in_single_quote;
+        } else if c == '"' && !in_single_quote {
+            in_double_quote = !in_double_quote;
+        }
+
+        let next = chars.get(pos + 1).copied();
+        let expands = c == '!'
+            && !in_single_quote
+            && !result.ends_with('$')
+            && next.is_some_and(|n| !n.is_whitespace() && n != '=' && n != '(')
+            && !(in_double_quote && next == Some('"'));
+        if !expands {
+            result.push(c);
+            pos += 1;
+            continue;
+        }
+
+        pos += 1;
+        let shorthand = matches!(chars.get(pos), Some('$' | '^' | '*' | ':'));
+        let event = if shorthand {
+            history
+                .last()
+                .map(|cmd| cmd.as_str().into())
+                .ok_or_else(|| "!: event not found".to_string())?
+        } else {
+            find_event_line(&chars, &mut pos, history, &result, &mut last_search)?
+        };
+
+        let words = split_history_words(&event);
+        let designator_follows = match chars.get(pos) {
+            Some('^' | '$' | '*' | '%') => true,
+            Some('-') => !shorthand,
+            Some(':') => chars
+                .get(pos + 1)
+                .is_some_and(|c| c.is_ascii_digit() || "^$*-%".contains(*c)),
+            _ => false,
+        };
+        let mut text = if designator_follows {
+            if chars[pos] == ':' {
+                pos += 1;
+            }
+            let matched = last_search
+                .as_ref()
+                .and_then(|needle| words.iter().position(|word| word.contains(needle.as_str())));
+            select_words(&words, &chars, &mut pos, matched)?
+        } else {
+            event.into_owned()
+        };
+
+        text = apply_modifiers(
+            text,
+            &chars,
+            &mut pos,
+            &mut last_substitution,
+            &mut print_only,
+        )?;
+        result.push_str(&text);
+    }
+
+    Ok((result, print_only))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn history() -> Vec<String> {
+        [
+            "ls -l /usr/lib",
+            "echo alpha foobar gamma",
+            "cat notes.txt.bak",
+        ]
+        .iter()
+        .map(|line| line.to_string())
+        .collect()
+    }
+
+    fn expand(line: &str) -> Result<(String, bool), String> {
+        expand_history(line, &history())
+    }
+
+    #[test]
+    fn event_designators() {
+        assert_eq!(expand("!!").unwrap().0, "cat notes.txt.bak");
+        assert_eq!(expand("!1").unwrap().0, "ls -l /usr/lib");
+        assert_eq!(expand("!-2").unwrap().0, "echo alpha foobar gamma");
+        assert_eq!(expand("!ec").unwrap().0, "echo alpha foobar gamma");
+        assert_eq!(expand("!?notes?").unwrap().0, "cat notes.txt.bak");
+        assert!(expand("!nothing").is_err());
+    }
+
+    #[test]
+    fn word_designators() {
+        assert_eq!(expand("!!:0").unwrap().0, "cat");
+        assert_eq!(expand("!2:1-2").unwrap().0, "alpha foobar");
+        assert_eq!(expand("!2:2*").unwrap().0, "foobar gamma");
+        assert_eq!(expand("!2:^ !2:$").unwrap().0, "alpha gamma");
+        assert_eq!(expand("!2:*").unwrap().0, "alpha foobar gamma");
+        assert!(expand("!2:9").is_err());
+    }
+
+    #[test]
+    fn search_word_designator() {
+        assert_eq!(expand("echo !?foo?%").unwrap().0, "echo foobar");
+        assert_eq!(expand("!?gam?:%").unwrap().0, "gamma");
+        assert_eq!(expand("!?alp?:%-$").unwrap().0, "alpha foobar gamma");
+        assert!(expand("!!:%").is_err());
+    }
+
+    #[test]
+    fn modifiers() {
+        assert_eq!(expand("!1:$:h").unwrap().0, "/usr");
+        assert_eq!(expand("!1:$:t").unwrap().0, "lib");
+        assert_eq!(expand("!!:$:r").unwrap().0, "notes.txt");
+        assert_eq!(expand("!!:$:e").unwrap().0, ".bak");
+        assert_eq!(
+            expand("!2:s/alpha/beta/").unwrap().0,
+            "echo beta foobar gamma"
+        );
+        assert_eq!(expand("!2:gs/a/o/").unwrap().0, "echo olpho foobor gommo");
+        assert_eq!(expand("!!:q").unwrap().0, "'cat notes.txt.bak'");
+        assert_eq!(
+            expand("!!:p").unwrap(),
+            ("cat notes.txt.bak".to_string(), true)
+        );
+        assert_eq!(expand("^notes^todo").unwrap().0, "cat todo.txt.bak");
+        assert!(expand("!!:s/missing/x/").is_err());
+    }
+
+    #[test]
+    fn delete_ranges() {
+        let mut err = Vec::new();
+        let mut entries = history();
+        let mut saved = 3;
+        assert!(delete_entries("1-2", &mut entries, &mut saved, &mut err));
+        assert_eq!(entries, ["cat notes.txt.bak"]);
+        assert_eq!(saved, 1);
+
+        let mut entries = history();
+        assert!(delete_entries("-2--1", &mut entries, &mut saved, &mut err));
+        assert_eq!(entries, ["ls -l /usr/lib"]);
+
+        let mut entries = history();
+        for spec in ["-é", "é-2", "2-é", "3-1", "0", "4"] {
+            assert!(!delete_entries(spec, &mut entries, &mut saved, &mut err));
+        }
+        assert_eq!(entries.len(), 3);
+        assert!(String::from_utf8(err).unwrap().starts_with("history: -é: "));
+    }
+}
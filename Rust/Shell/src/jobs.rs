@@ -0,0 +1,797 @@
+use crate::directory::error_reason;
+use crate::shell::Shell;
+use crate::terminal::{disable_raw_mode, enable_raw_mode};
+use crate::trap::{list_signals, signal_name, signal_number};
+use std::ffi::CStr;
+use std::io::Write;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum JobState {
+    Running,
+    Stopped,
+    Done(libc::c_int),
+}
+
+pub struct Job {
+    pub id: usize,
+    pub pid: libc::pid_t,
+    pub command: String,
+    pub state: JobState,
+    pub nohup: bool,
+    changed: bool,
+}
+
+#[derive(Default)]
+pub struct JobTable {
+    jobs: Vec<Job>,
+    pub exit_warned: bool,
+    pub foreground: String,
+}
+
+const JOB_CONTROL_SIGNALS: [libc::c_int; 5] = [
+    libc::SIGINT,
+    libc::SIGQUIT,
+    libc::SIGTSTP,
+    libc::SIGTTIN,
+    libc::SIGTTOU,
+];
+
+pub fn status_code(status: libc::c_int) -> i32 {
+    if libc::WIFSIGNALED(status) {
+        128 + libc::WTERMSIG(status)
+    } else {
+        libc::WEXITSTATUS(status)
+    }
+}
+
+fn wait_for(pid: libc::pid_t, options: libc::c_int) -> Option<JobState> {
+    let mut status: libc::c_int = 0;
+    let result = unsafe { libc::waitpid(pid, &mut status, options) };
+    if result <= 0 {
+        return None;
+    }
+    Some(if libc::WIFSTOPPED(status) {
+        JobState::Stopped
+    } else if libc::WIFCONTINUED(status) {
+        JobState::Running
+    } else {
+        JobState::Done(status)
+    })
+}
+
+fn state_description(state: JobState) -> String {
+    match state {
+        JobState::Running => "Running".to_string(),
+        JobState::Stopped => "Stopped".to_string(),
+        JobState::Done(status) if libc::WIFSIGNALED(status) => unsafe {
+            let description = libc::strsignal(libc::WTERMSIG(status));
+            if description.is_null() {
+                format!("Signal {}", libc::WTERMSIG(status))
+            } else {
+                CStr::from_ptr(description).to_string_lossy().into_owned()
+            }
+        },
+        JobState::Done(status) => match libc::WEXITSTATUS(status) {
+            0 => "Done".to_string(),
+            code => format!("Exit {}", code),
+        },
+    }
+}
+
+pub fn start_job_control(shell: &mut Shell) {
+    unsafe {
+        for signal in JOB_CONTROL_SIGNALS {
+            libc::signal(signal, libc::SIG_IGN);
+        }
+        let pid = libc::getpid();
+        if libc::getpgrp() != pid {
+            libc::setpgid(0, 0);
+        }
+        libc::tcsetpgrp(libc::STDIN_FILENO, pid);
+    }
+    shell.job_control = true;
+}
+
+pub fn restored_signals(shell: &Shell) -> Vec<libc::c_int> {
+    if !shell.job_control {
+        return Vec::new();
+    }
+    JOB_CONTROL_SIGNALS
+        .into_iter()
+        .filter(|signal| {
+            !shell
+                .traps
+                .get(signal)
+                .is_some_and(|command| command.is_empty())
+        })
+        .collect()
+}
+
+pub fn join_process_group(pgid: libc::pid_t, foreground: bool, signals: &[libc::c_int]) {
+    unsafe {
+        libc::setpgid(0, pgid);
+        if foreground {
+            libc::tcsetpgrp(libc::STDIN_FILENO, libc::getpgrp());
+        }
+        for &signal in signals {
+            libc::signal(signal, libc::SIG_DFL);
+        }
+    }
+}
+
+pub fn leave_job_control(shell: &mut Shell, pgid: libc::pid_t, foreground: bool) {
+    if shell.job_control {
+        join_process_group(pgid, foreground, &restored_signals(shell));
+    }
+    shell.job_control = false;
+}
+
+pub fn place_in_group(shell: &Shell, pid: libc::pid_t, pgid: libc::pid_t) {
+    if shell.job_control {
+        unsafe {
+            libc::setpgid(pid, pgid);
+        }
+    }
+}
+
+pub fn wait_foreground(shell: &mut Shell, pids: &[libc::pid_t]) -> Vec<i32> {
+    let Some(&pgid) = pids.first() else {
+        return Vec::new();
+    };
+    let options = if shell.job_control {
+        unsafe {
+            libc::tcsetpgrp(libc::STDIN_FILENO, pgid);
+        }
+        libc::WUNTRACED
+    } else {
+        0
+    };
+
+    let mut statuses = Vec::new();
+    let mut stopped = None;
+    for &pid in pids {
+        let mut status: libc::c_int = 0;
+        let result = unsafe { libc::waitpid(pid, &mut status, options) };
+        if result > 0 && libc::WIFSTOPPED(status) {
+            stopped = Some(128 + libc::WSTOPSIG(status));
+            break;
+        }
+        if shell.job_control && libc::WIFSIGNALED(status) && libc::WTERMSIG(status) == libc::SIGINT
+        {
+            eprintln!();
+        }
+        statuses.push(status_code(status));
+    }
+
+    if shell.job_control {
+        unsafe {
+            libc::tcsetpgrp(libc::STDIN_FILENO, libc::getpgrp());
+        }
+    }
+    match stopped {
+        Some(status) => {
+            statuses.resize(pids.len(), status);
+            let command = shell.jobs.foreground.clone();
+            let index = shell.jobs.stop(pgid, &command);
+            let mut err = std::io::stderr();
+            let _ = writeln!(err);
+            shell.jobs.print_job(&mut err, index, false);
+        }
+        None => shell.jobs.jobs.retain(|job| job.pid != pgid),
+    }
+    statuses
+}
+
+impl JobTable {
+    fn next_id(&self) -> usize {
+        self.jobs.iter().map(|job| job.id).max().unwrap_or(0) + 1
+    }
+
+    pub fn add(&mut self, pid: libc::pid_t, command: &str) -> usize {
+        let id = self.next_id();
+        self.jobs.push(Job {
+            id,
+            pid,
+            command: command.to_string(),
+            state: JobState::Running,
+            nohup: false,
+            changed: false,
+        });
+        id
+    }
+
+    fn stop(&mut self, pid: libc::pid_t, command: &str) -> usize {
+        let mut job = match self.jobs.iter().position(|job| job.pid == pid) {
+            Some(index) => self.jobs.remove(index),
+            None => Job {
+                id: self.next_id(),
+                pid,
+                command: command.to_string(),
+                state: JobState::Running,
+                nohup: false,
+                changed: false,
+            },
+        };
+        job.state = JobState::Stopped;
+        job.changed = false;
+        self.jobs.push(job);
+        self.jobs.len() - 1
+    }
+
+    pub fn names(&self) -> impl Iterator<Item = String> + '_ {
+        self.jobs
+            .iter()
+            .filter_map(|job| job.command.split_whitespace().next())
+            .map(|name| name.to_string())
+    }
+
+    pub fn update(&mut self) {
+        for job in &mut self.jobs {
+            if matches!(job.state, JobState::Done(_)) {
+                continue;
+            }
+            let options = libc::WNOHANG | libc::WUNTRACED | libc::WCONTINUED;
+            if let Some(state) = wait_for(job.pid, options)
+                && state != job.state
+            {
+                job.state = state;
+                job.changed = true;
+            }
+        }
+    }
+
+    pub fn count(&self) -> usize {
+        self.jobs.len()
+    }
+
+    pub fn exit_blocked(&mut self, interactive: bool, err: &mut dyn Write) -> bool {
+        self.update();
+        if interactive && self.has_stopped() && !self.exit_warned {
+            let _ = writeln!(err, "There are stopped jobs.");
+            self.exit_warned = true;
+            return true;
+        }
+        false
+    }
+
+    pub fn has_stopped(&self) -> bool {
+        self.jobs.iter().any(|job| job.state == JobState::Stopped)
+    }
+
+    fn marker(&self, index: usize) -> char {
+        let len = self.jobs.len();
+        if index + 1 == len {
+            '+'
+        } else if index + 2 == len {
+            '-'
+        } else {
+            ' '
+        }
+    }
+
+    fn print_job(&self, out: &mut dyn Write, index: usize, long: bool) {
+        let job = &self.jobs[index];
+        let pid = if long {
+            format!(" {}", job.pid)
+        } else {
+            " ".to_string()
+        };
+        let background = if job.state == JobState::Running {
+            " &"
+        } else {
+            ""
+        };
+        let _ = writeln!(
+            out,
+            "[{}]{}{} {:<24}{}{}",
+            job.id,
+            self.marker(index),
+            pid,
+            state_description(job.state),
+            job.command,
+            background
+        );
+    }
+
+    pub fn notify(&mut self, out: &mut dyn Write) {
+        self.update();
+        for index in 0..self.jobs.len() {
+            if self.jobs[index].changed {
+                self.print_job(out, index, false);
+                self.jobs[index].changed = false;
+            }
+        }
+        self.jobs
+            .retain(|job| !matches!(job.state, JobState::Done(_)));
+    }
+
+    pub fn hangup(&self) {
+        for job in &self.jobs {
+            if job.nohup || matches!(job.state, JobState::Done(_)) {
+                continue;
+            }
+            unsafe {
+                libc::killpg(job.pid, libc::SIGHUP);
+                if job.state == JobState::Stopped {
+                    libc::killpg(job.pid, libc::SIGCONT);
+                }
+            }
+        }
+    }
+
+    fn find(&self, builtin: &str, spec: &str) -> Result<usize, String> {
+        let no_such_job = || format!("{}: {}: no such job", builtin, spec);
+        let Some(spec) = spec.strip_prefix('%') else {
+            let pid: libc::pid_t = spec.parse().map_err(|_| no_such_job())?;
+            return self
+                .jobs
+                .iter()
+                .position(|job| job.pid == pid)
+                .ok_or_else(no_such_job);
+        };
+
+        let len = self.jobs.len();
+        let found: Vec<usize> = match spec {
+            "" | "%" | "+" => len.checked_sub(1).into_iter().collect(),
+            "-" => len
+                .checked_sub(2)
+                .or(len.checked_sub(1))
+                .into_iter()
+                .collect(),
+            _ => {
+                if let Ok(id) = spec.parse::<usize>() {
+                    self.jobs
+                        .iter()
+                        .position(|job| job.id == id)
+                        .into_iter()
+                        .collect()
+                } else {
+                    let matches = |job: &Job| match spec.strip_prefix('?') {
+                        Some(text) => job.command.contains(text),
+                        None => job.command.starts_with(spec),
+                    };
+                    (0..len)
+                        .filter(|&index| matches(&self.jobs[index]))
+                        .collect()
+                }
+            }
+        };
+        match found.as_slice() {
+            [index] => Ok(*index),
+            [] => Err(no_such_job()),
+            _ => Err(format!("{}: %{}: ambiguous job spec", builtin, spec)),
+        }
+    }
+
+    fn wait_job(&mut self, index: usize) -> i32 {
+        let job = &mut self.jobs[index];
+        while !matches!(job.state, JobState::Done(_)) {
+            match wait_for(job.pid, 0) {
+                Some(state) => job.state = state,
+                None => job.state = JobState::Done(127 << 8),
+            }
+        }
+        let JobState::Done(status) = self.jobs.remove(index).state else {
+            unreachable!()
+        };
+        status_code(status)
+    }
+
+    fn wait_next(&mut self) -> i32 {
+        if let Some(index) = self
+            .jobs
+            .iter()
+            .position(|job| matches!(job.state, JobState::Done(_)))
+        {
+            return self.wait_job(index);
+        }
+        while self.jobs.iter().any(|job| job.state == JobState::Running) {
+            let mut status: libc::c_int = 0;
+            let pid = unsafe { libc::waitpid(-1, &mut status, 0) };
+            if pid <= 0 {
+                break;
+            }
+            if let Some(index) = self.jobs.iter().position(|job| job.pid == pid) {
+                self.jobs[index].state = JobState::Done(status);
+                return self.wait_job(index);
+            }
+        }
+        127
+    }
+}
+
+pub fn fg_builtin(
+    args: &[String],
+    shell: &mut Shell,
+    out: &mut dyn Write,
+    err: &mut dyn Write,
+) -> i32 {
+    if !shell.job_control {
+        let _ = writeln!(err, "fg: no job control");
+        return 1;
+    }
+    shell.jobs.update();
+    let spec = args.first().map_or("%+", |spec| spec.as_str());
+    let index = match shell.jobs.find("fg", spec) {
+        Ok(index) => index,
+        Err(e) => {
+            let _ = writeln!(err, "{}", e);
+            return 1;
+        }
+    };
+    if let JobState::Done(status) = shell.jobs.jobs[index].state {
+        shell.jobs.jobs.remove(index);
+        return status_code(status);
+    }
+
+    let job = &mut shell.jobs.jobs[index];
+    let _ = writeln!(out, "{}", job.command);
+    let _ = out.flush();
+    job.state = JobState::Running;
+    job.changed = false;
+    let pid = job.pid;
+    shell.jobs.foreground = job.command.clone();
+    disable_raw_mode();
+    unsafe {
+        libc::tcsetpgrp(libc::STDIN_FILENO, pid);
+        libc::killpg(pid, libc::SIGCONT);
+    }
+    let status = wait_foreground(shell, &[pid]).pop().unwrap_or(0);
+    enable_raw_mode();
+    status
+}
+
+pub fn bg_builtin(
+    args: &[String],
+    shell: &mut Shell,
+    out: &mut dyn Write,
+    err: &mut dyn Write,
+) -> i32 {
+    if !shell.job_control {
+        let _ = writeln!(err, "bg: no job control");
+        return 1;
+    }
+    shell.jobs.update();
+    let default = ["%+".to_string()];
+    let specs = if args.is_empty() { &default[..] } else { args };
+    let mut status = 0;
+    for spec in specs {
+        let index = match shell.jobs.find("bg", spec) {
+            Ok(index) => index,
+            Err(e) => {
+                let _ = writeln!(err, "{}", e);
+                status = 1;
+                continue;
+            }
+        };
+        let marker = shell.jobs.marker(index);
+        let job = &mut shell.jobs.jobs[index];
+        match job.state {
+            JobState::Stopped => {
+                unsafe {
+                    libc::killpg(job.pid, libc::SIGCONT);
+                }
+                job.state = JobState::Running;
+                job.changed = false;
+                let _ = writeln!(out, "[{}]{} {} &", job.id, marker, job.command);
+            }
+            JobState::Running => {
+                let _ = writeln!(err, "bg: job {} already in background", job.id);
+            }
+            JobState::Done(_) => {
+                let _ = writeln!(err, "bg: job has terminated");
+                status = 1;
+            }
+        }
+    }
+    status
+}
+
+pub fn jobs_builtin(
+    args: &[String],
+    shell: &mut Shell,
+    out: &mut dyn Write,
+    err: &mut dyn Write,
+) -> i32 {
+    let mut long = false;
+    let mut pids = false;
+    let mut specs = Vec::new();
+    for arg in args {
+        if !specs.is_empty() || !arg.starts_with('-') || arg.len() == 1 {
+            specs.push(arg);
+            continue;
+        }
+        for flag in arg[1..].chars() {
+            match flag {
+                'l' => long = true,
+                'p' => pids = true,
+                _ => {
+                    let _ = writeln!(err, "jobs: -{}: invalid option", flag);
+                    let _ = writeln!(err, "jobs: usage: jobs [-lp] [jobspec ...]");
+                    return 2;
+                }
+            }
+        }
+    }
+
+    let table = &mut shell.jobs;
+    table.update();
+    let mut indices = Vec::new();
+    let mut status = 0;
+    if specs.is_empty() {
+        indices.extend(0..table.jobs.len());
+    }
+    for spec in specs {
+        match table.find("jobs", spec) {
+            Ok(index) => indices.push(index),
+            Err(e) => {
+                let _ = writeln!(err, "{}", e);
+                status = 1;
+            }
+        }
+    }
+
+    for index in indices {
+        if pids {
+            let _ = writeln!(out, "{}", table.jobs[index].pid);
+        } else {
+            table.print_job(out, index, long);
+        }
+        table.jobs[index].changed = false;
+    }
+    table
+        .jobs
+        .retain(|job| !matches!(job.state, JobState::Done(_)));
+    status
+}
+
+fn list_signal(out: &mut dyn Write, arg: &str) -> bool {
+    let _ = match arg.parse::<i32>() {
+        Ok(number) => {
+            let number = if number > 128 { number - 128 } else { number };
+            match signal_name(number) {
+                Some(name) => writeln!(out, "{}", name),
+                None => return false,
+            }
+        }
+        Err(_) => match signal_number(arg) {
+            Some(number) => writeln!(out, "{}", number),
+            None => return false,
+        },
+    };
+    true
+}
+
+fn kill_usage(err: &mut dyn Write) {
+    let _ = writeln!(
+        err,
+        "kill: usage: kill [-s sigspec | -n signum | -sigspec] pid | jobspec ... or kill -l [sigspec]"
+    );
+}
+
+pub fn kill_builtin(
+    args: &[String],
+    shell: &mut Shell,
+    out: &mut dyn Write,
+    err: &mut dyn Write,
+) -> i32 {
+    let mut signal = libc::SIGTERM;
+    let mut index = 0;
+    while let Some(arg) = args.get(index) {
+        index += 1;
+        match arg.as_str() {
+            "--" => break,
+            "-l" | "-L" => {
+                let rest = &args[index..];
+                if rest.is_empty() {
+                    list_signals(out);
+                    return 0;
+                }
+                let mut status = 0;
+                for arg in rest {
+                    if !list_signal(out, arg) {
+                        let _ = writeln!(err, "kill: {}: invalid signal specification", arg);
+                        status = 1;
+                    }
+                }
+                return status;
+            }
+            "-s" | "-n" => {
+                let Some(spec) = args.get(index) else {
+                    let _ = writeln!(err, "kill: {}: option requires an argument", arg);
+                    kill_usage(err);
+                    return 2;
+                };
+                index += 1;
+                match signal_number(spec) {
+                    Some(number) => signal = number,
+                    None => {
+                        let _ = writeln!(err, "kill: {}: invalid signal specification", spec);
+                        return 1;
+                    }
+                }
+            }
+            _ if arg.starts_with('-') && arg.len() > 1 => match signal_number(&arg[1..]) {
+                Some(number) => signal = number,
+                None => {
+                    let _ = writeln!(err, "kill: {}: invalid signal specification", &arg[1..]);
+                    return 1;
+                }
+            },
+            _ => {
+                index -= 1;
+                break;
+            }
+        }
+    }
+
+    let targets = &args[index..];
+    if targets.is_empty() {
+        kill_usage(err);
+        return 2;
+    }
+
+    let mut status = 0;
+    for target in targets {
+        let result = if target.starts_with('%') {
+            match shell.jobs.find("kill", target) {
+                Ok(index) => {
+                    let job = &shell.jobs.jobs[index];
+                    let result = unsafe { libc::killpg(job.pid, signal) };
+                    if result == 0
+                        && job.state == JobState::Stopped
+                        && matches!(signal, libc::SIGTERM | libc::SIGHUP)
+                    {
+                        unsafe {
+                            libc::killpg(job.pid, libc::SIGCONT);
+                        }
+                    }
+                    result
+                }
+                Err(e) => {
+                    let _ = writeln!(err, "{}", e);
+                    status = 1;
+                    continue;
+                }
+            }
+        } else {
+            match target.parse::<libc::pid_t>() {
+                Ok(pid) => unsafe { libc::kill(pid, signal) },
+                Err(_) => {
+                    let _ = writeln!(
+                        err,
+                        "kill: {}: arguments must be process or job IDs",
+                        target
+                    );
+                    status = 1;
+                    continue;
+                }
+            }
+        };
+        if result != 0 {
+            let e = std::io::Error::last_os_error();
+            let _ = writeln!(err, "kill: ({}) - {}", target, error_reason(&e));
+            status = 1;
+        }
+    }
+    status
+}
+
+pub fn wait_builtin(args: &[String], shell: &mut Shell, err: &mut dyn Write) -> i32 {
+    let mut next = false;
+    let mut index = 0;
+    while let Some(arg) = args.get(index) {
+        match arg.as_str() {
+            "--" => {
+                index += 1;
+                break;
+            }
+            "-n" => next = true,
+            _ if arg.starts_with('-') && arg.len() > 1 => {
+                let _ = writeln!(err, "wait: {}: invalid option", arg);
+                let _ = writeln!(err, "wait: usage: wait [-n] [id ...]");
+                return 2;
+            }
+            _ => break,
+        }
+        index += 1;
+    }
+    let targets = &args[index..];
+
+    let table = &mut shell.jobs;
+    table.update();
+    if next {
+        return table.wait_next();
+    }
+    if targets.is_empty() {
+        while !table.jobs.is_empty() {
+            table.wait_job(0);
+        }
+        return 0;
+    }
+
+    let mut status = 0;
+    for target in targets {
+        status = match table.find("wait", target) {
+            Ok(index) => table.wait_job(index),
+            Err(_) if !target.starts_with('%') => {
+                if target.parse::<libc::pid_t>().is_ok() {
+                    let _ = writeln!(err, "wait: pid {} is not a child of this shell", target);
+                } else {
+                    let _ = writeln!(err, "wait: `{}': not a pid or valid job spec", target);
+                }
+                127
+            }
+            Err(e) => {
+                let _ = writeln!(err, "{}", e);
+                127
+            }
+        };
+    }
+    status
+}
+
+pub fn disown_builtin(args: &[String], shell: &mut Shell, err: &mut dyn Write) -> i32 {
+    let mut all = false;
+    let mut running = false;
+    let mut nohup = false;
+    let mut specs = Vec::new();
+    for arg in args {
+        if !specs.is_empty() || !arg.starts_with('-') || arg.len() == 1 {
+            specs.push(arg);
+            continue;
+        }
+        for flag in arg[1..].chars() {
+            match flag {
+                'a' => all = true,
+                'r' => running = true,
+                'h' => nohup = true,
+                _ => {
+                    let _ = writeln!(err, "disown: -{}: invalid option", flag);
+                    let _ = writeln!(
+                        err,
+                        "disown: usage: disown [-h] [-ar] [jobspec ... | pid ...]"
+                    );
+                    return 2;
+                }
+            }
+        }
+    }
+
+    let table = &mut shell.jobs;
+    table.update();
+    let mut selected = vec![false; table.jobs.len()];
+    let mut status = 0;
+    if all || running {
+        for (index, job) in table.jobs.iter().enumerate() {
+            selected[index] = !running || job.state == JobState::Running;
+        }
+    } else if specs.is_empty() {
+        match table.jobs.len().checked_sub(1) {
+            Some(index) => selected[index] = true,
+            None => {
+                let _ = writeln!(err, "disown: current: no such job");
+                return 1;
+            }
+        }
+    }
+    for spec in specs {
+        match table.find("disown", spec) {
+            Ok(index) => selected[index] = true,
+            Err(e) => {
+                let _ = writeln!(err, "{}", e);
+                status = 1;
+            }
+        }
+    }
+
+    let mut selected = selected.into_iter();
+    if nohup {
+        for job in &mut table.jobs {
+            job.nohup |= selected.next().unwrap_or(false);
+        }
+    } else {
+        table.jobs.retain(|_| !selected.next().unwrap_or(false));
+    }
+    status
+}
@@ -1,178 +1,181 @@
 mod builtins;
+mod completion;
+mod conditional;
+mod directory;
+mod editor;
 mod executor;
+mod git;
+mod hash;
+mod history;
+mod jobs;
+mod menu;
+mod options;
 mod parser;
+mod pattern;
+mod printf;
+mod prompt;
+mod read;
+mod redirect;
+mod shell;
+mod source;
+mod syntax;
 mod terminal;
-
-use crate::executor::{execute_command_line, find_completions, get_longest_common_prefix};
+mod trap;
+mod variables;
+
+use crate::editor::read_line;
+use crate::executor::execute_command_line;
+use crate::history::{expand_history, load_history_file};
+use crate::jobs::start_job_control;
+use crate::options::{ShellOptions, set_flag, set_named_option};
+use crate::prompt::{prompt_string, render_prompt, run_preexec_hook, run_prompt_hooks};
+use crate::shell::Shell;
+use crate::source::load_startup_files;
+use crate::syntax::is_incomplete;
 use crate::terminal::enable_raw_mode;
+use crate::trap::{run_exit_trap, run_pending_traps};
 use std::env;
 use std::fs::File;
-use std::io::{self, BufRead, BufReader, Read, Write};
-
-fn main() {
-    enable_raw_mode();
-
-    print!("$ ");
-    io::stdout().flush().unwrap();
+use std::io::Write;
+use std::time::Instant;
 
-    let mut buffer = String::new();
-    let stdin = io::stdin();
-    let mut handle = stdin.lock();
-
-    let mut tab_press_count = 0;
-    let mut history: Vec<String> = Vec::new();
-    let mut history_index: usize = 0;
-    let mut last_saved_index: usize = 0;
+fn invalid_option(option: &str) -> ! {
+    eprintln!("shell: {}: invalid option", option);
+    std::process::exit(2);
+}
 
-    if let Ok(histfile_path) = env::var("HISTFILE") {
-        if let Ok(file) = File::open(&histfile_path) {
-            let reader = BufReader::new(file);
-            for line in reader.lines() {
-                if let Ok(cmd) = line {
-                    let trimmed = cmd.trim();
-                    if !trimmed.is_empty() {
-                        history.push(trimmed.to_string());
+fn main() {
+    let mut login = env::args().next().is_some_and(|arg0| arg0.starts_with('-'));
+    let mut profile = true;
+    let mut rc = true;
+    let mut options = ShellOptions::default();
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--login" => login = true,
+            "--noprofile" => profile = false,
+            "--norc" => rc = false,
+            _ if arg.len() > 1 && !arg.starts_with("--") && arg.starts_with(['-', '+']) => {
+                let value = arg.starts_with('-');
+                for flag in arg[1..].chars() {
+                    match flag {
+                        'l' if value => login = true,
+                        'o' => match args.next() {
+                            Some(name) if set_named_option(&mut options, &name, value) => {}
+                            Some(name) => invalid_option(&name),
+                            None => {
+                                eprintln!("shell: {}: option requires an argument", arg);
+                                std::process::exit(2);
+                            }
+                        },
+                        _ => match set_flag(&mut options, flag) {
+                            Some(option) => *option = value,
+                            None => invalid_option(&arg),
+                        },
                     }
                 }
             }
-            history_index = history.len();
-            last_saved_index = history.len();
+            _ => invalid_option(&arg),
         }
     }
 
-    loop {
-        let mut byte_buffer = [0u8; 1];
-        if handle.read_exact(&mut byte_buffer).is_err() {
-            break;
-        }
-        let byte = byte_buffer[0];
-
-        match byte {
-            9 => {
-                let matches = find_completions(&buffer);
-
-                if matches.len() == 1 {
-                    let completed = &matches[0];
-                    if completed.len() >= buffer.len() {
-                        let remainder = &completed[buffer.len()..];
-                        print!("{} ", remainder);
-                        io::stdout().flush().unwrap();
-                        buffer.push_str(remainder);
-                        buffer.push(' ');
-                    }
-                    tab_press_count = 0;
-                } else if matches.len() > 1 {
-                    let lcp = get_longest_common_prefix(&matches);
-
-                    if lcp.len() > buffer.len() {
-                        let remainder = &lcp[buffer.len()..];
-                        print!("{}", remainder);
-                        io::stdout().flush().unwrap();
-                        buffer.push_str(remainder);
-                        tab_press_count = 0;
-                    } else {
-                        tab_press_count += 1;
+    enable_raw_mode();
 
-                        if tab_press_count == 1 {
-                            print!("\x07");
-                            io::stdout().flush().unwrap();
-                        } else {
-                            print!("\r\n");
-                            let list = matches.join("  ");
-                            print!("{}", list);
-                            print!("\r\n");
-                            print!("$ {}", buffer);
-                            io::stdout().flush().unwrap();
-                        }
-                    }
-                } else {
-                    print!("\x07");
-                    io::stdout().flush().unwrap();
-                    tab_press_count = 0;
-                }
-            }
+    let mut shell = Shell::new();
+    shell.options = options;
+    if let Some(histfile_path) = shell.variables.get("HISTFILE")
+        && load_history_file(histfile_path, &mut shell.history).is_ok()
+    {
+        shell.last_saved_index = shell.history.len();
+    }
 
-            10 => {
-                tab_press_count = 0;
-                println!();
-                let trimmed = buffer.trim();
-                if !trimmed.is_empty() {
-                    history.push(trimmed.to_string());
-                }
-                history_index = history.len();
-                if execute_command_line(&buffer, &mut history, &mut last_saved_index) {
-                    break;
-                }
-                buffer.clear();
-                print!("$ ");
-                io::stdout().flush().unwrap();
+    if shell.interactive {
+        start_job_control(&mut shell);
+    }
+    load_startup_files(&mut shell, login, profile, rc);
+    while !shell.exiting {
+        run_pending_traps(&mut shell);
+        if shell.interactive {
+            shell.jobs.notify(&mut std::io::stderr());
+            run_prompt_hooks(&mut shell);
+        }
+        let prompt = prompt_string(&shell, "PS1", "$ ");
+        let right_prompt = prompt_string(&shell, "RPROMPT", "");
+        let transient_prompt = shell
+            .variables
+            .get("TRANSIENT_PROMPT")
+            .map(|template| render_prompt(template, &shell));
+        let Some(mut buffer) = read_line(
+            &mut shell,
+            &prompt,
+            &right_prompt,
+            transient_prompt.as_deref(),
+        ) else {
+            if shell
+                .jobs
+                .exit_blocked(shell.interactive, &mut std::io::stderr())
+            {
+                continue;
             }
-
-            27 => {
-                let mut seq = [0u8; 2];
-                if handle.read_exact(&mut seq).is_ok() {
-                    if seq[0] == 91 {
-                        match seq[1] {
-                            65 => {
-                                if !history.is_empty() && history_index > 0 {
-                                    history_index -= 1;
-                                    print!("\r$ ");
-                                    for _ in 0..buffer.len() {
-                                        print!(" ");
-                                    }
-                                    print!("\r$ ");
-                                    buffer = history[history_index].clone();
-                                    print!("{}", buffer);
-                                    io::stdout().flush().unwrap();
-                                }
-                            }
-                            66 => {
-                                if !history.is_empty() && history_index < history.len() {
-                                    history_index += 1;
-                                    print!("\r$ ");
-                                    for _ in 0..buffer.len() {
-                                        print!(" ");
-                                    }
-                                    print!("\r$ ");
-                                    if history_index < history.len() {
-                                        buffer = history[history_index].clone();
-                                    } else {
-                                        buffer.clear();
-                                    }
-                                    print!("{}", buffer);
-                                    io::stdout().flush().unwrap();
-                                }
-                            }
-                            _ => {}
-                        }
-                    }
+            break;
+        };
+        while is_incomplete(&buffer) {
+            let prompt = prompt_string(&shell, "PS2", "> ");
+            match read_line(&mut shell, &prompt, "", None) {
+                Some(more) => {
+                    buffer.push('\n');
+                    buffer.push_str(&more);
                 }
+                None => break,
             }
-
-            127 => {
-                tab_press_count = 0;
-                if !buffer.is_empty() {
-                    buffer.pop();
-                    print!("\x08 \x08");
-                    io::stdout().flush().unwrap();
+        }
+        if shell.options.verbose {
+            eprintln!("{}", buffer);
+        }
+        let (line, print_only) = match expand_history(&buffer, &shell.history) {
+            Ok((line, print_only)) => {
+                if line != buffer {
+                    println!("{}", line);
                 }
+                (line, print_only)
             }
-
-            c => {
-                tab_press_count = 0;
-                let char = c as char;
-                print!("{}", char);
-                io::stdout().flush().unwrap();
-                buffer.push(char);
+            Err(e) => {
+                eprintln!("{}", e);
+                (String::new(), true)
             }
+        };
+        let trimmed = line.trim();
+        if !trimmed.is_empty() {
+            shell.history.push(trimmed.to_string());
+        }
+        let exit_warned = shell.jobs.exit_warned;
+        if !print_only && shell.interactive && !trimmed.is_empty() {
+            run_preexec_hook(&mut shell, trimmed);
+        }
+        if !print_only {
+            shell.current_line_added = shell.interactive && !trimmed.is_empty();
+            let started = Instant::now();
+            execute_command_line(&line, &mut shell);
+            let elapsed = started.elapsed().as_millis().to_string();
+            let _ = shell.variables.set("CMD_DURATION", &elapsed);
+            shell.current_line_added = false;
+        }
+        if exit_warned {
+            shell.jobs.exit_warned = false;
         }
     }
 
-    if let Ok(histfile_path) = env::var("HISTFILE") {
-        if let Ok(mut file) = File::create(&histfile_path) {
-            for cmd in history.iter() {
-                let _ = writeln!(file, "{}", cmd);
-            }
+    run_exit_trap(&mut shell);
+    if shell.interactive {
+        shell.jobs.hangup();
+    }
+    if let Some(histfile_path) = shell.variables.get("HISTFILE")
+        && let Ok(mut file) = File::create(histfile_path)
+    {
+        for cmd in shell.history.iter() {
+            let _ = writeln!(file, "{}", cmd);
         }
     }
+
+    std::process::exit(shell.variables.status());
 }
@@ -0,0 +1,158 @@
+use crate::completion::{Candidate, CandidateKind, Completion};
+
+fn kind_color(kind: CandidateKind) -> &'static str {
+    match kind {
+        CandidateKind::Builtin => "\x1b[1;33m",
+        CandidateKind::Alias => "\x1b[36m",
+        CandidateKind::Directory => "\x1b[1;34m",
+        CandidateKind::Command | CandidateKind::File | CandidateKind::Word => "",
+    }
+}
+
+fn kind_label(kind: CandidateKind) -> &'static str {
+    match kind {
+        CandidateKind::Builtin => "builtin",
+        CandidateKind::Alias => "alias",
+        CandidateKind::Command => "command",
+        CandidateKind::Directory => "directory",
+        CandidateKind::File => "file",
+        CandidateKind::Word => "word",
+    }
+}
+
+fn layout(candidates: &[Candidate], width: usize) -> (usize, usize, usize) {
+    let longest = candidates
+        .iter()
+        .map(|candidate| candidate.display.chars().count())
+        .max()
+        .unwrap_or(0);
+    let column_width = longest + 2;
+    let columns = ((width + 2) / column_width).max(1);
+    let rows = candidates.len().div_ceil(columns).max(1);
+    (rows, candidates.len().div_ceil(rows), column_width)
+}
+
+pub fn format_columns(
+    candidates: &[Candidate],
+    width: usize,
+    selected: Option<usize>,
+) -> Vec<String> {
+    let (rows, columns, column_width) = layout(candidates, width);
+    let mut lines = Vec::new();
+
+    for row in 0..rows {
+        let mut line = String::new();
+        let mut pending_padding = 0;
+
+        for column in 0..columns {
+            let index = column * rows + row;
+            let Some(candidate) = candidates.get(index) else {
+                break;
+            };
+
+            line.push_str(&" ".repeat(pending_padding));
+            let color = if selected == Some(index) {
+                "\x1b[7m"
+            } else {
+                kind_color(candidate.kind)
+            };
+            if color.is_empty() {
+                line.push_str(&candidate.display);
+            } else {
+                line.push_str(&format!("{}{}\x1b[0m", color, candidate.display));
+            }
+            pending_padding = column_width - candidate.display.chars().count();
+        }
+        lines.push(line);
+    }
+    lines
+}
+
+pub struct CompletionMenu {
+    pub completion: Completion,
+    pub selected: usize,
+    rows: usize,
+    top: usize,
+}
+
+impl CompletionMenu {
+    pub fn new(completion: Completion, width: usize) -> Self {
+        let (rows, _, _) = layout(&completion.candidates, width);
+        CompletionMenu {
+            completion,
+            selected: 0,
+            rows,
+            top: 0,
+        }
+    }
+
+    pub fn current(&self) -> &Candidate {
+        &self.completion.candidates[self.selected]
+    }
+
+    fn len(&self) -> usize {
+        self.completion.candidates.len()
+    }
+
+    pub fn next(&mut self) {
+        self.selected = (self.selected + 1) % self.len();
+    }
+
+    pub fn previous(&mut self) {
+        self.selected = (self.selected + self.len() - 1) % self.len();
+    }
+
+    pub fn right(&mut self) {
+        if self.selected + self.rows < self.len() {
+            self.selected += self.rows;
+        } else {
+            self.selected %= self.rows;
+        }
+    }
+
+    pub fn left(&mut self) {
+        if self.selected >= self.rows {
+            self.selected -= self.rows;
+        } else {
+            let last_column = (self.len() - 1) / self.rows;
+            let target = last_column * self.rows + self.selected;
+            self.selected = if target < self.len() {
+                target
+            } else {
+                target - self.rows
+            };
+        }
+    }
+
+    pub fn render(&mut self, width: usize, height: usize) -> Vec<String> {
+        let lines = format_columns(&self.completion.candidates, width, Some(self.selected));
+        let visible = height.saturating_sub(1).clamp(1, lines.len());
+        let row = self.selected % self.rows;
+        if row < self.top {
+            self.top = row;
+        } else if row >= self.top + visible {
+            self.top = row + 1 - visible;
+        }
+        self.top = self.top.min(lines.len() - visible);
+
+        let mut lines = lines[self.top..self.top + visible].to_vec();
+        let current = self.current();
+        let mut status = format!(
+            "{} ({}) {}/{}",
+            current.display,
+            kind_label(current.kind),
+            self.selected + 1,
+            self.len()
+        );
+        if visible < self.rows {
+            status.push_str(&format!(
+                " rows {}-{} of {}",
+                self.top + 1,
+                self.top + visible,
+                self.rows
+            ));
+        }
+        lines.push(format!("\x1b[2m{}\x1b[0m", status));
+        lines
+    }
+}
@@ -0,0 +1,244 @@
+use crate::shell::Shell;
+use crate::variables::print_variables;
+use std::io::Write;
+
+#[derive(Default)]
+pub struct ShellOptions {
+    pub errexit: bool,
+    pub noclobber: bool,
+    pub noexec: bool,
+    pub noglob: bool,
+    pub nounset: bool,
+    pub pipefail: bool,
+    pub verbose: bool,
+    pub xtrace: bool,
+    pub lastpipe: bool,
+}
+
+const SHOPT_NAMES: [&str; 1] = ["lastpipe"];
+const SET_OPTION_NAMES: [&str; 8] = [
+    "errexit",
+    "noclobber",
+    "noexec",
+    "noglob",
+    "nounset",
+    "pipefail",
+    "verbose",
+    "xtrace",
+];
+
+type OptionLookup = for<'a> fn(&'a mut ShellOptions, &str) -> Option<&'a mut bool>;
+type OptionPrinter = fn(&mut dyn Write, &str, bool, bool);
+
+fn shopt_option<'a>(options: &'a mut ShellOptions, name: &str) -> Option<&'a mut bool> {
+    match name {
+        "lastpipe" => Some(&mut options.lastpipe),
+        _ => None,
+    }
+}
+
+fn set_option<'a>(options: &'a mut ShellOptions, name: &str) -> Option<&'a mut bool> {
+    match name {
+        "errexit" => Some(&mut options.errexit),
+        "noclobber" => Some(&mut options.noclobber),
+        "noexec" => Some(&mut options.noexec),
+        "noglob" => Some(&mut options.noglob),
+        "nounset" => Some(&mut options.nounset),
+        "pipefail" => Some(&mut options.pipefail),
+        "verbose" => Some(&mut options.verbose),
+        "xtrace" => Some(&mut options.xtrace),
+        _ => None,
+    }
+}
+
+pub fn set_flag(options: &mut ShellOptions, flag: char) -> Option<&mut bool> {
+    match flag {
+        'e' => Some(&mut options.errexit),
+        'C' => Some(&mut options.noclobber),
+        'n' => Some(&mut options.noexec),
+        'f' => Some(&mut options.noglob),
+        'u' => Some(&mut options.nounset),
+        'v' => Some(&mut options.verbose),
+        'x' => Some(&mut options.xtrace),
+        _ => None,
+    }
+}
+
+pub fn set_named_option(options: &mut ShellOptions, name: &str, value: bool) -> bool {
+    match set_option(options, name) {
+        Some(option) => {
+            *option = value;
+            true
+        }
+        None => false,
+    }
+}
+
+fn print_shopt(out: &mut dyn Write, name: &str, value: bool, reusable: bool) {
+    let _ = if reusable {
+        writeln!(out, "shopt {} {}", if value { "-s" } else { "-u" }, name)
+    } else {
+        writeln!(out, "{:<15}\t{}", name, if value { "on" } else { "off" })
+    };
+}
+
+fn print_set_option(out: &mut dyn Write, name: &str, value: bool, reusable: bool) {
+    let _ = if reusable {
+        writeln!(out, "set {}o {}", if value { '-' } else { '+' }, name)
+    } else {
+        writeln!(out, "{:<15}\t{}", name, if value { "on" } else { "off" })
+    };
+}
+
+fn print_set_options(out: &mut dyn Write, options: &mut ShellOptions, reusable: bool) {
+    for name in SET_OPTION_NAMES {
+        let value = set_option(options, name).is_some_and(|value| *value);
+        print_set_option(out, name, value, reusable);
+    }
+}
+
+fn set_usage(err: &mut dyn Write) {
+    let _ = writeln!(
+        err,
+        "set: usage: set [-eufnvxC] [-o option-name] [--] [-] [arg ...]"
+    );
+}
+
+pub fn set_builtin(
+    args: &[String],
+    shell: &mut Shell,
+    out: &mut dyn Write,
+    err: &mut dyn Write,
+) -> i32 {
+    if args.is_empty() {
+        print_variables(&shell.variables, out);
+        return 0;
+    }
+
+    let mut iter = args.iter().peekable();
+    let mut arguments: Option<Vec<String>> = None;
+    while let Some(arg) = iter.next() {
+        if arg == "--" {
+            arguments = Some(iter.by_ref().cloned().collect());
+            break;
+        }
+        if arg == "-" {
+            shell.options.xtrace = false;
+            shell.options.verbose = false;
+            let rest: Vec<String> = iter.by_ref().cloned().collect();
+            if !rest.is_empty() {
+                arguments = Some(rest);
+            }
+            break;
+        }
+        let value = arg.starts_with('-');
+        if !(value || arg.starts_with('+')) || arg.len() == 1 {
+            arguments = Some(std::iter::once(arg).chain(iter.by_ref()).cloned().collect());
+            break;
+        }
+
+        for flag in arg[1..].chars() {
+            if flag == 'o' {
+                let Some(name) = iter.next_if(|name| !name.starts_with(['-', '+'])) else {
+                    print_set_options(out, &mut shell.options, !value);
+                    continue;
+                };
+                if !set_named_option(&mut shell.options, name, value) {
+                    let _ = writeln!(err, "set: {}: invalid option name", name);
+                    set_usage(err);
+                    return 2;
+                }
+                continue;
+            }
+            match set_flag(&mut shell.options, flag) {
+                Some(option) => *option = value,
+                None => {
+                    let _ = writeln!(
+                        err,
+                        "set: {}{}: invalid option",
+                        if value { '-' } else { '+' },
+                        flag
+                    );
+                    set_usage(err);
+                    return 2;
+                }
+            }
+        }
+    }
+
+    if let Some(arguments) = arguments {
+        shell.variables.set_arguments(arguments);
+    }
+    0
+}
+
+pub fn shopt_builtin(
+    args: &[String],
+    shell: &mut Shell,
+    out: &mut dyn Write,
+    err: &mut dyn Write,
+) -> i32 {
+    let mut set: Option<bool> = None;
+    let mut print = false;
+    let mut quiet = false;
+    let mut set_options = false;
+    let mut names: Vec<&String> = Vec::new();
+
+    for arg in args {
+        if !names.is_empty() || !arg.starts_with('-') || arg.len() == 1 {
+            names.push(arg);
+            continue;
+        }
+        for flag in arg[1..].chars() {
+            match flag {
+                's' => set = Some(true),
+                'u' => set = Some(false),
+                'p' => print = true,
+                'q' => quiet = true,
+                'o' => set_options = true,
+                _ => {
+                    let _ = writeln!(err, "shopt: -{}: invalid option", flag);
+                    let _ = writeln!(err, "shopt: usage: shopt [-pqsu] [-o] [optname ...]");
+                    return 2;
+                }
+            }
+        }
+    }
+
+    let (all_names, lookup, print_option): (&[&str], OptionLookup, OptionPrinter) = if set_options {
+        (&SET_OPTION_NAMES, set_option, print_set_option)
+    } else {
+        (&SHOPT_NAMES, shopt_option, print_shopt)
+    };
+
+    if names.is_empty() {
+        for name in all_names {
+            let value = lookup(&mut shell.options, name).is_some_and(|value| *value);
+            if set.is_none_or(|set| set == value) {
+                print_option(out, name, value, print);
+            }
+        }
+        return 0;
+    }
+
+    let mut status = 0;
+    for name in names {
+        let Some(option) = lookup(&mut shell.options, name) else {
+            let _ = writeln!(err, "shopt: {}: invalid shell option name", name);
+            status = 1;
+            continue;
+        };
+        match set {
+            Some(value) => *option = value,
+            None => {
+                if !*option {
+                    status = 1;
+                }
+                if !quiet {
+                    print_option(out, name, *option, print);
+                }
+            }
+        }
+    }
+    status
+}
@@ -1,85 +1,475 @@
-pub fn parse_input(input: &str) -> Vec<String> {
-    let mut args = Vec::new();
-    let mut current_arg = String::new();
+use crate::pattern::{GLOB_SPECIAL, glob};
+use crate::variables::Variables;
+use std::ffi::{CStr, CString};
+
+pub const OPERATORS: [&str; 8] = ["|", "||", "&", "&&", ";", "\n", "(", ")"];
+
+pub fn is_operator(word: &str) -> bool {
+    OPERATORS.contains(&word)
+}
+
+pub fn split_words(input: &str) -> Vec<String> {
+    scan_words(input).0
+}
+
+pub fn has_open_quote(input: &str) -> bool {
+    scan_words(input).1
+}
+
+fn scan_words(input: &str) -> (Vec<String>, bool) {
+    let mut words = Vec::new();
+    let mut current_word = String::new();
     let mut in_single_quote = false;
     let mut in_double_quote = false;
     let mut escaped = false;
+    let mut in_array = false;
+    let mut in_conditional = false;
+    let mut started = false;
+
+    let mut chars = input.chars().peekable();
+    while let Some(c) = chars.next() {
+        if escaped {
+            current_word.push(c);
+            escaped = false;
+            continue;
+        }
 
-    for c in input.chars() {
         if in_single_quote {
             if c == '\'' {
                 in_single_quote = false;
-            } else {
-                current_arg.push(c);
             }
+            current_word.push(c);
         } else if in_double_quote {
-            if escaped {
-                if c == '"' || c == '\\' {
-                    current_arg.push(c);
-                } else {
-                    current_arg.push('\\');
-                    current_arg.push(c);
-                }
-                escaped = false
-            } else if c == '\\' {
+            if c == '\\' {
                 escaped = true;
             } else if c == '"' {
-                in_double_quote = false
-            } else {
-                current_arg.push(c);
+                in_double_quote = false;
+            }
+            current_word.push(c);
+        } else if in_array && c != '\\' && c != '\'' && c != '"' {
+            in_array = c != ')';
+            current_word.push(c);
+        } else if matches!(c, ';' | '\n')
+            || (c == '|'
+                && (!current_word.ends_with('>') || current_word.ends_with("\\>"))
+                && (!in_conditional || chars.peek() == Some(&'|')))
+            || (c == '&'
+                && (chars.peek() == Some(&'&')
+                    || (chars.peek() != Some(&'>') && !current_word.ends_with(['<', '>']))))
+            || (!in_conditional && c == ')')
+            || (!in_conditional
+                && c == '('
+                && !current_word.strip_suffix('=').is_some_and(is_valid_name))
+        {
+            if started {
+                push_word(&mut words, &mut current_word, &mut in_conditional);
+                started = false;
+            }
+            let mut operator = c.to_string();
+            if matches!(c, '|' | '&') && chars.next_if_eq(&c).is_some() {
+                operator.push(c);
+            }
+            in_conditional &= c != ';' && c != '\n';
+            words.push(operator);
+        } else if c.is_whitespace() {
+            if started {
+                push_word(&mut words, &mut current_word, &mut in_conditional);
+                started = false;
             }
         } else {
-            if escaped {
-                current_arg.push(c);
-                escaped = false;
-            } else if c == '\\' {
-                escaped = true;
-            } else if c == '\'' {
-                in_single_quote = true;
-            } else if c == '"' {
-                in_double_quote = true;
-            } else if c == '|' {
-                if !current_arg.is_empty() {
-                    args.push(current_arg);
-                    current_arg = String::new();
+            match c {
+                '\\' => escaped = true,
+                '\'' => in_single_quote = true,
+                '"' => in_double_quote = true,
+                '(' => {
+                    in_array = current_word.strip_suffix('=').is_some_and(is_valid_name);
                 }
-                args.push("|".to_string());
-            } else if c.is_whitespace() {
-                if !current_arg.is_empty() {
-                    args.push(current_arg);
-                    current_arg = String::new();
-                }
-            } else {
-                current_arg.push(c);
+                _ => {}
+            }
+            current_word.push(c);
+            started = true;
+        }
+    }
+
+    if started {
+        words.push(current_word);
+    }
+
+    (words, in_single_quote || in_double_quote)
+}
+
+fn push_word(words: &mut Vec<String>, word: &mut String, in_conditional: &mut bool) {
+    match word.as_str() {
+        "[[" => *in_conditional = true,
+        "]]" => *in_conditional = false,
+        _ => {}
+    }
+    words.push(std::mem::take(word));
+}
+
+pub fn is_valid_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    matches!(chars.next(), Some(c) if c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+pub fn assignment_name(word: &str) -> Option<&str> {
+    let (name, _) = word.split_once('=')?;
+    is_valid_name(name).then_some(name)
+}
+
+pub fn home_directory(user: &str) -> Option<String> {
+    let name = CString::new(user).ok()?;
+    unsafe {
+        let entry = libc::getpwnam(name.as_ptr());
+        if entry.is_null() {
+            return None;
+        }
+        Some(
+            CStr::from_ptr((*entry).pw_dir)
+                .to_string_lossy()
+                .into_owned(),
+        )
+    }
+}
+
+fn tilde_prefix(prefix: &str, variables: &Variables) -> Option<String> {
+    match prefix {
+        "" => variables.get("HOME").map(|home| home.to_string()),
+        "+" => variables.get("PWD").map(|pwd| pwd.to_string()),
+        "-" => variables.get("OLDPWD").map(|old_pwd| old_pwd.to_string()),
+        user => home_directory(user),
+    }
+}
+
+fn expand_tilde(
+    chars: &mut std::iter::Peekable<std::str::Chars>,
+    assignment: bool,
+    variables: Option<&Variables>,
+    expanded: &mut String,
+) {
+    let prefix: String = chars
+        .clone()
+        .take_while(|&c| c != '/' && !(assignment && c == ':'))
+        .collect();
+    let quoted = prefix.contains(['\\', '\'', '"', '$']);
+
+    match variables
+        .filter(|_| !quoted)
+        .and_then(|variables| tilde_prefix(&prefix, variables))
+    {
+        Some(home) => {
+            expanded.push_str(&home);
+            for _ in prefix.chars() {
+                chars.next();
+            }
+        }
+        None => expanded.push('~'),
+    }
+}
+
+pub fn expand_tilde_prefix(word: &str, variables: &Variables) -> String {
+    let Some(rest) = word.strip_prefix('~') else {
+        return word.to_string();
+    };
+    let mut chars = rest.chars().peekable();
+    let mut expanded = String::new();
+    expand_tilde(&mut chars, false, Some(variables), &mut expanded);
+    expanded.extend(chars);
+    expanded
+}
+
+fn array_subscript(parameter: &str) -> (&str, Option<&str>) {
+    match parameter.strip_suffix(']').and_then(|p| p.split_once('[')) {
+        Some((name, subscript)) => (name, Some(subscript)),
+        None => (parameter, None),
+    }
+}
+
+fn special_parameter(name: &str, variables: &Variables) -> Option<String> {
+    match name {
+        "?" | "!" => variables.get(name).map(|value| value.to_string()),
+        "$" => Some(std::process::id().to_string()),
+        "#" => Some(variables.arguments().len().to_string()),
+        "@" | "*" => Some(variables.arguments().join(" ")),
+        _ => {
+            let index: usize = name.parse().ok()?;
+            Some(variables.positional(index).unwrap_or("").to_string())
+        }
+    }
+}
+
+fn braced_parameter(parameter: &str, variables: &Variables) -> Option<String> {
+    if let Some(value) = special_parameter(parameter, variables) {
+        return Some(value);
+    }
+
+    if let Some(parameter) = parameter.strip_prefix('#') {
+        let (name, subscript) = array_subscript(parameter);
+        if !is_valid_name(name) {
+            return None;
+        }
+        return Some(
+            match subscript {
+                Some("@" | "*") => variables.get_array(name).map_or(0, |values| values.len()),
+                _ => variables.get(name).map_or(0, |value| value.chars().count()),
             }
+            .to_string(),
+        );
+    }
+
+    let (name, subscript) = array_subscript(parameter);
+    if !is_valid_name(name) {
+        return None;
+    }
+    let values = variables.get_array(name).unwrap_or_default();
+    Some(match subscript {
+        None => variables.get(name).unwrap_or("").to_string(),
+        Some("@" | "*") => values.join(" "),
+        Some(index) => {
+            let index: i64 = index.trim().parse().ok()?;
+            let index = if index < 0 {
+                values.len() as i64 + index
+            } else {
+                index
+            };
+            usize::try_from(index)
+                .ok()
+                .and_then(|index| values.get(index))
+                .cloned()
+                .unwrap_or_default()
         }
+    })
+}
+
+fn parameter_unset(parameter: &str, variables: &Variables) -> bool {
+    let parameter = parameter
+        .strip_prefix('#')
+        .filter(|name| !name.is_empty())
+        .unwrap_or(parameter);
+    if let Ok(index) = parameter.parse::<usize>() {
+        return index > 0 && variables.positional(index).is_none();
     }
+    let (name, _) = array_subscript(parameter);
+    is_valid_name(name) && variables.get(name).is_none() && variables.get_array(name).is_none()
+}
+
+fn expand_parameter(
+    chars: &mut std::iter::Peekable<std::str::Chars>,
+    variables: Option<&Variables>,
+    nounset: bool,
+    expanded: &mut String,
+) -> Result<(), String> {
+    let Some(variables) = variables else {
+        expanded.push('$');
+        return Ok(());
+    };
+    let check = |parameter: &str| {
+        if nounset && parameter_unset(parameter, variables) {
+            Err(format!("shell: {}: unbound variable", parameter))
+        } else {
+            Ok(())
+        }
+    };
 
-    if !current_arg.is_empty() {
-        args.push(current_arg);
+    match chars.peek() {
+        Some(&c) if "$?!#@*".contains(c) || c.is_ascii_digit() => {
+            check(&c.to_string())?;
+            chars.next();
+            expanded.push_str(&special_parameter(&c.to_string(), variables).unwrap_or_default());
+        }
+        Some('{') => {
+            let rest: String = chars.clone().skip(1).collect();
+            match rest
+                .find('}')
+                .and_then(|end| braced_parameter(&rest[..end], variables).map(|v| (end, v)))
+            {
+                Some((end, value)) => {
+                    check(&rest[..end])?;
+                    expanded.push_str(&value);
+                    for _ in 0..rest[..end].chars().count() + 2 {
+                        chars.next();
+                    }
+                }
+                None => expanded.push('$'),
+            }
+        }
+        Some(&c) if c.is_ascii_alphabetic() || c == '_' => {
+            let mut name = String::new();
+            while let Some(&c) = chars.peek() {
+                if !c.is_ascii_alphanumeric() && c != '_' {
+                    break;
+                }
+                name.push(c);
+                chars.next();
+            }
+            check(&name)?;
+            expanded.push_str(variables.get(&name).unwrap_or(""));
+        }
+        _ => expanded.push('$'),
     }
+    Ok(())
+}
 
-    args
+fn push_literal(expanded: &mut String, c: char, literal: &str) {
+    if literal.contains(c) {
+        expanded.push('\\');
+    }
+    expanded.push(c);
 }
 
-pub fn split_by_pipe(args: Vec<String>) -> Vec<Vec<String>> {
-    let mut commands = Vec::new();
-    let mut current_cmd = Vec::new();
+fn expand(
+    word: &str,
+    variables: Option<&Variables>,
+    literal: &str,
+    nounset: bool,
+) -> Result<Option<String>, String> {
+    let mut expanded = String::new();
+    let mut quoted = false;
+    let mut in_single_quote = false;
+    let mut in_double_quote = false;
+    let assignment = assignment_name(word).is_some();
+    let mut previous: Option<char> = None;
+    let mut chars = word.chars().peekable();
 
-    for arg in args {
-        if arg == "|" {
-            if !current_cmd.is_empty() {
-                commands.push(current_cmd);
-                current_cmd = Vec::new();
+    while let Some(c) = chars.next() {
+        let tilde_allowed =
+            previous.is_none() || (assignment && matches!(previous, Some('=' | ':')));
+        previous = Some(c);
+
+        if in_single_quote {
+            if c == '\'' {
+                in_single_quote = false;
+            } else {
+                push_literal(&mut expanded, c, literal);
+            }
+        } else if in_double_quote {
+            match c {
+                '\\' => match chars.next() {
+                    Some(next @ ('"' | '\\' | '$' | '`')) => {
+                        push_literal(&mut expanded, next, literal)
+                    }
+                    Some(next) => {
+                        push_literal(&mut expanded, '\\', literal);
+                        push_literal(&mut expanded, next, literal);
+                    }
+                    None => push_literal(&mut expanded, '\\', literal),
+                },
+                '"' => in_double_quote = false,
+                '$' => {
+                    let mut value = String::new();
+                    expand_parameter(&mut chars, variables, nounset, &mut value)?;
+                    for c in value.chars() {
+                        push_literal(&mut expanded, c, literal);
+                    }
+                }
+                _ => push_literal(&mut expanded, c, literal),
             }
         } else {
-            current_cmd.push(arg);
+            match c {
+                '\\' => {
+                    quoted = true;
+                    if let Some(next) = chars.next() {
+                        push_literal(&mut expanded, next, literal);
+                    }
+                }
+                '\'' => {
+                    quoted = true;
+                    in_single_quote = true;
+                }
+                '"' => {
+                    quoted = true;
+                    in_double_quote = true;
+                }
+                '$' => expand_parameter(&mut chars, variables, nounset, &mut expanded)?,
+                '~' if tilde_allowed => {
+                    expand_tilde(&mut chars, assignment, variables, &mut expanded)
+                }
+                _ => expanded.push(c),
+            }
         }
     }
 
-    if !current_cmd.is_empty() {
-        commands.push(current_cmd);
+    Ok((quoted || !expanded.is_empty()).then_some(expanded))
+}
+
+pub fn expand_word(word: &str, variables: &Variables) -> Option<String> {
+    expand(word, Some(variables), "", false).ok().flatten()
+}
+
+pub fn expand_word_checked(
+    word: &str,
+    variables: &Variables,
+    nounset: bool,
+) -> Result<Option<String>, String> {
+    expand(word, Some(variables), "", nounset)
+}
+
+pub fn expand_pattern(word: &str, variables: &Variables, special: &str) -> String {
+    expand(word, Some(variables), special, false)
+        .ok()
+        .flatten()
+        .unwrap_or_default()
+}
+
+fn has_glob_chars(pattern: &str) -> bool {
+    let mut chars = pattern.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => {
+                chars.next();
+            }
+            '*' | '?' | '[' => return true,
+            _ => {}
+        }
+    }
+    false
+}
+
+fn remove_escapes(pattern: &str) -> String {
+    let mut text = String::new();
+    let mut chars = pattern.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => text.extend(chars.next()),
+            _ => text.push(c),
+        }
+    }
+    text
+}
+
+pub fn expand_words_checked(
+    words: &[String],
+    variables: &Variables,
+    nounset: bool,
+    noglob: bool,
+) -> Result<Vec<String>, String> {
+    let mut expanded = Vec::new();
+    for word in words {
+        if noglob {
+            expanded.extend(expand_word_checked(word, variables, nounset)?);
+            continue;
+        }
+        let Some(pattern) = expand(word, Some(variables), GLOB_SPECIAL, nounset)? else {
+            continue;
+        };
+        let matches = if has_glob_chars(&pattern) {
+            glob(&pattern)
+        } else {
+            Vec::new()
+        };
+        if matches.is_empty() {
+            expanded.push(remove_escapes(&pattern));
+        } else {
+            expanded.extend(matches);
+        }
     }
+    Ok(expanded)
+}
 
-    commands
+pub fn parse_input(input: &str) -> Vec<String> {
+    split_words(input)
+        .iter()
+        .filter_map(|word| expand(word, None, "", false).ok().flatten())
+        .collect()
 }
@@ -0,0 +1,94 @@
+use std::ffi::{CStr, CString};
+
+pub const GLOB_SPECIAL: &str = "*?[]\\";
+
+pub fn fnmatch(pattern: &str, text: &str) -> bool {
+    let (Ok(pattern), Ok(text)) = (CString::new(pattern), CString::new(text)) else {
+        return false;
+    };
+    unsafe { libc::fnmatch(pattern.as_ptr(), text.as_ptr(), 0) == 0 }
+}
+
+pub fn glob(pattern: &str) -> Vec<String> {
+    let Ok(c_pattern) = CString::new(pattern) else {
+        return Vec::new();
+    };
+
+    let mut paths = Vec::new();
+    unsafe {
+        let mut glob_buf: libc::glob_t = std::mem::zeroed();
+        if libc::glob(c_pattern.as_ptr(), 0, None, &mut glob_buf) == 0 {
+            for i in 0..glob_buf.gl_pathc {
+                let path = *glob_buf.gl_pathv.add(i);
+                paths.push(CStr::from_ptr(path).to_string_lossy().into_owned());
+            }
+        }
+        libc::globfree(&mut glob_buf);
+    }
+    paths
+}
+
+fn regex_groups(pattern: &str) -> usize {
+    let mut groups = 0;
+    let mut in_bracket = false;
+    let mut chars = pattern.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => {
+                chars.next();
+            }
+            '[' if !in_bracket => {
+                in_bracket = true;
+                if let Some(']') = chars.clone().next() {
+                    chars.next();
+                }
+            }
+            ']' if in_bracket => in_bracket = false,
+            '(' if !in_bracket => groups += 1,
+            _ => {}
+        }
+    }
+    groups
+}
+
+pub fn regex_match(pattern: &str, text: &str) -> Result<Option<Vec<String>>, String> {
+    let (Ok(c_pattern), Ok(c_text)) = (CString::new(pattern), CString::new(text)) else {
+        return Ok(None);
+    };
+
+    unsafe {
+        let mut regex: libc::regex_t = std::mem::zeroed();
+        let result = libc::regcomp(&mut regex, c_pattern.as_ptr(), libc::REG_EXTENDED);
+        if result != 0 {
+            let mut message = [0 as libc::c_char; 256];
+            libc::regerror(result, &regex, message.as_mut_ptr(), message.len());
+            return Err(CStr::from_ptr(message.as_ptr())
+                .to_string_lossy()
+                .into_owned());
+        }
+
+        let mut matches = vec![std::mem::zeroed::<libc::regmatch_t>(); regex_groups(pattern) + 1];
+        let found = libc::regexec(
+            &regex,
+            c_text.as_ptr(),
+            matches.len(),
+            matches.as_mut_ptr(),
+            0,
+        ) == 0;
+        libc::regfree(&mut regex);
+
+        if !found {
+            return Ok(None);
+        }
+        let groups = matches
+            .iter()
+            .map(
+                |m| match (usize::try_from(m.rm_so), usize::try_from(m.rm_eo)) {
+                    (Ok(start), Ok(end)) => text.get(start..end).unwrap_or("").to_string(),
+                    _ => String::new(),
+                },
+            )
+            .collect();
+        Ok(Some(groups))
+    }
+}
@@ -0,0 +1,553 @@
+use crate::parser::is_valid_name;
+use crate::variables::Variables;
+use std::ffi::CString;
+use std::io::Write;
+
+const FIELD_LIMIT: i64 = 1 << 20;
+
+fn push_char(output: &mut Vec<u8>, c: char) {
+    output.extend_from_slice(c.encode_utf8(&mut [0; 4]).as_bytes());
+}
+
+fn push_code_point(output: &mut Vec<u8>, digits: &str) {
+    if let Some(c) = u32::from_str_radix(digits, 16)
+        .ok()
+        .and_then(char::from_u32)
+    {
+        push_char(output, c);
+    }
+}
+
+fn take_digits(chars: &mut std::iter::Peekable<std::str::Chars>, radix: u32, max: usize) -> String {
+    let mut digits = String::new();
+    while digits.len() < max
+        && let Some(&c) = chars.peek()
+        && c.is_digit(radix)
+    {
+        digits.push(c);
+        chars.next();
+    }
+    digits
+}
+
+pub fn expand_escapes(text: &str, octal_needs_zero: bool) -> (Vec<u8>, bool) {
+    let mut output = Vec::new();
+    let mut chars = text.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            push_char(&mut output, c);
+            continue;
+        }
+        let Some(next) = chars.next() else {
+            output.push(b'\\');
+            break;
+        };
+        match next {
+            'a' => output.push(b'\x07'),
+            'b' => output.push(b'\x08'),
+            'c' => return (output, true),
+            'e' | 'E' => output.push(b'\x1b'),
+            'f' => output.push(b'\x0c'),
+            'n' => output.push(b'\n'),
+            'r' => output.push(b'\r'),
+            't' => output.push(b'\t'),
+            'v' => output.push(b'\x0b'),
+            '\\' => output.push(b'\\'),
+            '"' if !octal_needs_zero => output.push(b'"'),
+            '0'..='7' if next == '0' || !octal_needs_zero => {
+                let mut digits = if next == '0' && octal_needs_zero {
+                    String::new()
+                } else {
+                    next.to_string()
+                };
+                let remaining = 3 - digits.len();
+                digits.push_str(&take_digits(&mut chars, 8, remaining));
+                let value = u32::from_str_radix(&digits, 8).unwrap_or(0);
+                output.push((value & 0xff) as u8);
+            }
+            'x' => {
+                let digits = take_digits(&mut chars, 16, 2);
+                match u8::from_str_radix(&digits, 16) {
+                    Ok(byte) => output.push(byte),
+                    Err(_) => output.extend_from_slice(b"\\x"),
+                }
+            }
+            'u' | 'U' => {
+                let digits = take_digits(&mut chars, 16, if next == 'u' { 4 } else { 8 });
+                if digits.is_empty() {
+                    output.push(b'\\');
+                    push_char(&mut output, next);
+                } else {
+                    push_code_point(&mut output, &digits);
+                }
+            }
+            _ => {
+                output.push(b'\\');
+                push_char(&mut output, next);
+            }
+        }
+    }
+    (output, false)
+}
+
+pub fn echo_builtin(args: &[String], out: &mut dyn Write, err: &mut dyn Write) -> i32 {
+    let mut newline = true;
+    let mut escapes = false;
+    let mut words = args;
+
+    while let Some(flag) = words.first().and_then(|arg| arg.strip_prefix('-')) {
+        if flag.is_empty() || !flag.chars().all(|c| matches!(c, 'n' | 'e' | 'E')) {
+            break;
+        }
+        for c in flag.chars() {
+            match c {
+                'n' => newline = false,
+                'e' => escapes = true,
+                _ => escapes = false,
+            }
+        }
+        words = &words[1..];
+    }
+
+    let mut output = words.join(" ").into_bytes();
+    if escapes {
+        let (expanded, stop) = expand_escapes(&String::from_utf8_lossy(&output), true);
+        output = expanded;
+        newline &= !stop;
+    }
+    if newline {
+        output.push(b'\n');
+    }
+    if let Err(e) = out.write_all(&output) {
+        let _ = writeln!(err, "echo: write error: {}", e);
+    }
+    0
+}
+
+fn shell_quote(text: &str) -> String {
+    if text.is_empty() {
+        return "''".to_string();
+    }
+    if text.chars().any(|c| c.is_control()) {
+        let mut quoted = String::from("$'");
+        for c in text.chars() {
+            match c {
+                '\n' => quoted.push_str("\\n"),
+                '\t' => quoted.push_str("\\t"),
+                '\r' => quoted.push_str("\\r"),
+                '\x1b' => quoted.push_str("\\E"),
+                '\'' | '\\' => {
+                    quoted.push('\\');
+                    quoted.push(c);
+                }
+                c if c.is_control() => quoted.push_str(&format!("\\{:03o}", c as u32)),
+                c => quoted.push(c),
+            }
+        }
+        quoted.push('\'');
+        return quoted;
+    }
+
+    let mut quoted = String::new();
+    for c in text.chars() {
+        if !(c.is_alphanumeric() || "_-./:,+@%^=".contains(c)) {
+            quoted.push('\\');
+        }
+        quoted.push(c);
+    }
+    quoted
+}
+
+enum Number {
+    Signed(i64),
+    Unsigned(u64),
+    Float(f64),
+}
+
+fn parse_integer(arg: &str) -> Result<i64, String> {
+    let trimmed = arg.trim();
+    if let Some(c) = trimmed
+        .strip_prefix('\'')
+        .or_else(|| trimmed.strip_prefix('"'))
+    {
+        return Ok(c.chars().next().map_or(0, |c| c as i64));
+    }
+    let (negative, digits) = match trimmed.strip_prefix('-') {
+        Some(rest) => (true, rest),
+        None => (false, trimmed.strip_prefix('+').unwrap_or(trimmed)),
+    };
+    let value = if let Some(hex) = digits
+        .strip_prefix("0x")
+        .or_else(|| digits.strip_prefix("0X"))
+    {
+        i64::from_str_radix(hex, 16)
+    } else if digits.len() > 1 && digits.starts_with('0') {
+        i64::from_str_radix(&digits[1..], 8)
+    } else {
+        digits.parse()
+    };
+    value
+        .map(|value| if negative { -value } else { value })
+        .map_err(|_| format!("{}: invalid number", arg))
+}
+
+fn parse_float(arg: &str) -> Result<f64, String> {
+    let trimmed = arg.trim();
+    if trimmed.starts_with('\'') || trimmed.starts_with('"') {
+        return parse_integer(trimmed).map(|value| value as f64);
+    }
+    trimmed
+        .parse()
+        .map_err(|_| format!("{}: invalid number", arg))
+}
+
+fn c_format(spec: &str, number: Number) -> Vec<u8> {
+    let Ok(format) = CString::new(spec) else {
+        return Vec::new();
+    };
+    let mut buffer = vec![0u8; 512];
+    loop {
+        let length = unsafe {
+            let ptr = buffer.as_mut_ptr().cast();
+            match number {
+                Number::Signed(value) => libc::snprintf(ptr, buffer.len(), format.as_ptr(), value),
+                Number::Unsigned(value) => {
+                    libc::snprintf(ptr, buffer.len(), format.as_ptr(), value)
+                }
+                Number::Float(value) => libc::snprintf(ptr, buffer.len(), format.as_ptr(), value),
+            }
+        };
+        let Ok(length) = usize::try_from(length) else {
+            return Vec::new();
+        };
+        if length < buffer.len() {
+            buffer.truncate(length);
+            return buffer;
+        }
+        buffer.resize(length + 1, 0);
+    }
+}
+
+fn pad(text: &[u8], width: Option<usize>, left_align: bool) -> Vec<u8> {
+    let length = text.iter().filter(|&&byte| byte & 0xc0 != 0x80).count();
+    let padding = vec![b' '; width.unwrap_or(0).saturating_sub(length)];
+    if left_align {
+        [text, &padding].concat()
+    } else {
+        [&padding, text].concat()
+    }
+}
+
+fn truncate(text: &[u8], precision: Option<usize>) -> &[u8] {
+    let Some(precision) = precision else {
+        return text;
+    };
+    let end = text
+        .iter()
+        .enumerate()
+        .filter(|(_, byte)| **byte & 0xc0 != 0x80)
+        .nth(precision)
+        .map_or(text.len(), |(index, _)| index);
+    &text[..end]
+}
+
+struct Formatter<'a> {
+    args: &'a [String],
+    err: &'a mut dyn Write,
+    next: usize,
+    status: i32,
+    stop: bool,
+}
+
+impl Formatter<'_> {
+    fn next_arg(&mut self) -> Option<&str> {
+        let arg = self.args.get(self.next).map(|s| s.as_str());
+        if arg.is_some() {
+            self.next += 1;
+        }
+        arg
+    }
+
+    fn report<T: Default>(&mut self, result: Result<T, String>) -> T {
+        result.unwrap_or_else(|e| {
+            let _ = writeln!(self.err, "printf: {}", e);
+            self.status = 1;
+            T::default()
+        })
+    }
+
+    fn star(&mut self) -> i64 {
+        let arg = self.next_arg().unwrap_or("0").to_string();
+        let result = parse_integer(&arg);
+        self.report(result)
+    }
+
+    fn conversion(&mut self, chars: &mut std::iter::Peekable<std::str::Chars>) -> Vec<u8> {
+        let mut flags = String::new();
+        while let Some(&c) = chars.peek()
+            && "-+ #0".contains(c)
+        {
+            flags.push(c);
+            chars.next();
+        }
+
+        let mut width: Option<i64> = None;
+        if chars.next_if_eq(&'*').is_some() {
+            width = Some(self.star());
+        } else {
+            let digits = take_digits(chars, 10, usize::MAX);
+            width = digits.parse().ok().or(width);
+        }
+        if let Some(w) = width.filter(|w| *w < 0) {
+            flags.push('-');
+            width = Some(-w);
+        }
+
+        let mut precision: Option<i64> = None;
+        if chars.next_if_eq(&'.').is_some() {
+            precision = Some(if chars.next_if_eq(&'*').is_some() {
+                self.star()
+            } else {
+                take_digits(chars, 10, usize::MAX).parse().unwrap_or(0)
+            });
+        }
+
+        let Some(conversion) = chars.next() else {
+            let _ = writeln!(self.err, "printf: `%': missing format character");
+            self.status = 1;
+            self.stop = true;
+            return Vec::new();
+        };
+
+        for (value, name) in [(width, "field width"), (precision, "precision")] {
+            if let Some(value) = value.filter(|value| *value > FIELD_LIMIT) {
+                let _ = writeln!(self.err, "printf: `{}': invalid {}", value, name);
+                self.status = 1;
+                self.stop = true;
+                return Vec::new();
+            }
+        }
+
+        let mut spec = format!("%{}", flags);
+        if let Some(width) = width {
+            spec.push_str(&width.to_string());
+        }
+        if let Some(precision) = precision.filter(|p| *p >= 0) {
+            spec.push_str(&format!(".{}", precision));
+        }
+
+        let left_align = flags.contains('-');
+        let width = width.map(|w| w as usize);
+        let precision = precision.and_then(|p| usize::try_from(p).ok());
+
+        match conversion {
+            '%' => b"%".to_vec(),
+            's' => {
+                let arg = self.next_arg().unwrap_or("");
+                pad(truncate(arg.as_bytes(), precision), width, left_align)
+            }
+            'b' => {
+                let arg = self.next_arg().unwrap_or("").to_string();
+                let (expanded, stop) = expand_escapes(&arg, true);
+                self.stop |= stop;
+                pad(truncate(&expanded, precision), width, left_align)
+            }
+            'q' => {
+                let arg = self.next_arg().unwrap_or("").to_string();
+                pad(shell_quote(&arg).as_bytes(), width, left_align)
+            }
+            'c' => {
+                let arg = self.next_arg().unwrap_or("");
+                pad(truncate(arg.as_bytes(), Some(1)), width, left_align)
+            }
+            'd' | 'i' => {
+                let arg = self.next_arg().unwrap_or("0").to_string();
+                let value = parse_integer(&arg);
+                let value = self.report(value);
+                c_format(&format!("{}lld", spec), Number::Signed(value))
+            }
+            'u' | 'x' | 'X' | 'o' => {
+                let arg = self.next_arg().unwrap_or("0").to_string();
+                let value = parse_integer(&arg);
+                let value = self.report(value);
+                c_format(
+                    &format!("{}ll{}", spec, conversion),
+                    Number::Unsigned(value as u64),
+                )
+            }
+            'f' | 'F' | 'e' | 'E' | 'g' | 'G' | 'a' | 'A' => {
+                let arg = self.next_arg().unwrap_or("0").to_string();
+                let value = parse_float(&arg);
+                let value = self.report(value);
+                c_format(&format!("{}{}", spec, conversion), Number::Float(value))
+            }
+            _ => {
+                let _ = writeln!(
+                    self.err,
+                    "printf: `{}': invalid format character",
+                    conversion
+                );
+                self.status = 1;
+                self.stop = true;
+                Vec::new()
+            }
+        }
+    }
+
+    fn format_once(&mut self, format: &str) -> Vec<u8> {
+        let mut output = Vec::new();
+        let mut chars = format.chars().peekable();
+
+        while let Some(c) = chars.next() {
+            match c {
+                '%' => output.extend(self.conversion(&mut chars)),
+                '\\' => {
+                    let mut escape = String::from('\\');
+                    escape.extend(chars.next());
+                    if escape.ends_with(['x', 'u', 'U']) {
+                        let radix_len = match escape.chars().last() {
+                            Some('x') => 2,
+                            Some('u') => 4,
+                            _ => 8,
+                        };
+                        escape.push_str(&take_digits(&mut chars, 16, radix_len));
+                    } else if escape.ends_with(|c: char| c.is_digit(8)) {
+                        escape.push_str(&take_digits(&mut chars, 8, 2));
+                    }
+                    let (expanded, stop) = expand_escapes(&escape, false);
+                    output.extend(expanded);
+                    self.stop |= stop;
+                }
+                _ => push_char(&mut output, c),
+            }
+            if self.stop {
+                break;
+            }
+        }
+        output
+    }
+}
+
+pub fn printf_builtin(
+    args: &[String],
+    variables: &mut Variables,
+    out: &mut dyn Write,
+    err: &mut dyn Write,
+) -> i32 {
+    let (target, args) = match args {
+        [flag, name, rest @ ..] if flag == "-v" => (Some(name), rest),
+        [flag] if flag == "-v" => {
+            let _ = writeln!(err, "printf: -v: option requires an argument");
+            return 2;
+        }
+        [flag, rest @ ..] if flag == "--" => (None, rest),
+        _ => (None, args),
+    };
+    if let Some(name) = target
+        && !is_valid_name(name)
+    {
+        let _ = writeln!(err, "printf: `{}': not a valid identifier", name);
+        return 2;
+    }
+
+    let Some((format, args)) = args.split_first() else {
+        let _ = writeln!(err, "printf: usage: printf [-v var] format [arguments]");
+        return 2;
+    };
+
+    let mut formatter = Formatter {
+        args,
+        err,
+        next: 0,
+        status: 0,
+        stop: false,
+    };
+    let mut output = formatter.format_once(format);
+    while !formatter.stop && formatter.next > 0 && formatter.next < args.len() {
+        output.extend(formatter.format_once(format));
+    }
+
+    match target {
+        Some(name) => {
+            if let Err(e) = variables.set(name, &String::from_utf8_lossy(&output)) {
+                let _ = writeln!(err, "printf: {}", e);
+                return 1;
+            }
+        }
+        None => {
+            if let Err(e) = out.write_all(&output) {
+                let _ = writeln!(err, "printf: write error: {}", e);
+                return 1;
+            }
+            let _ = out.flush();
+        }
+    }
+    formatter.status
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn printf(args: &[&str]) -> (i32, Vec<u8>, String) {
+        let args: Vec<String> = args.iter().map(|arg| arg.to_string()).collect();
+        let mut variables = Variables::default();
+        let mut out = Vec::new();
+        let mut err = Vec::new();
+        let status = printf_builtin(&args, &mut variables, &mut out, &mut err);
+        (status, out, String::from_utf8(err).unwrap())
+    }
+
+    #[test]
+    fn escapes_are_raw_bytes() {
+        assert_eq!(printf(&["\\xff\\101\\0101"]).1, b"\xffA\x081");
+        assert_eq!(printf(&["%b", "\\xfe\\0101"]).1, b"\xfeA");
+        assert_eq!(printf(&["\\u00e9"]).1, "é".as_bytes());
+        assert_eq!(printf(&["a\\cb"]).1, b"a");
+    }
+
+    #[test]
+    fn echo_escapes() {
+        let mut out = Vec::new();
+        let args = ["-e".to_string(), "x\\ty\\c".to_string(), "z".to_string()];
+        echo_builtin(&args, &mut out, &mut Vec::new());
+        assert_eq!(out, b"x\ty");
+    }
+
+    #[test]
+    fn format_is_reused_for_extra_arguments() {
+        assert_eq!(printf(&["%s-%d,", "a", "1", "b"]).1, b"a-1,b-0,");
+        assert_eq!(printf(&["[%5s|%-3s]", "ab", "c"]).1, b"[   ab|c  ]");
+        assert_eq!(printf(&["%.2s %c", "héllo", "xyz"]).1, "hé x".as_bytes());
+        assert_eq!(printf(&["%*d", "4", "7"]).1, b"   7");
+        assert_eq!(printf(&["%x %o %%", "255", "8"]).1, b"ff 10 %");
+    }
+
+    #[test]
+    fn oversized_field_width_is_rejected() {
+        let (status, out, err) = printf(&["%999999999d", "1"]);
+        assert_eq!((status, out), (1, Vec::new()));
+        assert_eq!(err, "printf: `999999999': invalid field width\n");
+        let (status, _, err) = printf(&["%.*s", "99999999", "x"]);
+        assert_eq!(status, 1);
+        assert_eq!(err, "printf: `99999999': invalid precision\n");
+    }
+
+    #[test]
+    fn invalid_numbers_and_conversions() {
+        let (status, out, err) = printf(&["%d|", "x"]);
+        assert_eq!((status, out), (1, b"0|".to_vec()));
+        assert_eq!(err, "printf: x: invalid number\n");
+        let (status, _, err) = printf(&["%z"]);
+        assert_eq!(status, 1);
+        assert_eq!(err, "printf: `z': invalid format character\n");
+    }
+
+    #[test]
+    fn invalid_target_name() {
+        let (status, _, err) = printf(&["-v", "1bad", "x"]);
+        assert_eq!(status, 2);
+        assert_eq!(err, "printf: `1bad': not a valid identifier\n");
+    }
+}
@@ -0,0 +1,259 @@
+use crate::builtins::is_builtin;
+use crate::executor::execute_command_line;
+use crate::git::git_segment;
+use crate::parser::expand_word;
+use crate::shell::Shell;
+use std::ffi::CStr;
+use std::time::Duration;
+
+pub const START_IGNORE: char = '\x01';
+pub const END_IGNORE: char = '\x02';
+
+fn format_time(format: &str) -> String {
+    let Ok(format) = std::ffi::CString::new(format) else {
+        return String::new();
+    };
+    let mut buffer = [0u8; 256];
+    unsafe {
+        let now = libc::time(std::ptr::null_mut());
+        let mut local = std::mem::zeroed::<libc::tm>();
+        libc::localtime_r(&now, &mut local);
+        let len = libc::strftime(
+            buffer.as_mut_ptr().cast(),
+            buffer.len(),
+            format.as_ptr(),
+            &local,
+        );
+        String::from_utf8_lossy(&buffer[..len]).into_owned()
+    }
+}
+
+fn host_name() -> String {
+    let mut buffer = [0u8; 256];
+    let result = unsafe { libc::gethostname(buffer.as_mut_ptr().cast(), buffer.len()) };
+    if result != 0 {
+        return String::new();
+    }
+    let end = buffer.iter().position(|&b| b == 0).unwrap_or(buffer.len());
+    String::from_utf8_lossy(&buffer[..end]).into_owned()
+}
+
+fn user_name(shell: &Shell) -> String {
+    if let Some(user) = shell.variables.get("USER") {
+        return user.to_string();
+    }
+    unsafe {
+        let entry = libc::getpwuid(libc::geteuid());
+        if entry.is_null() {
+            return String::new();
+        }
+        CStr::from_ptr((*entry).pw_name)
+            .to_string_lossy()
+            .into_owned()
+    }
+}
+
+fn terminal_name() -> String {
+    unsafe {
+        let name = libc::ttyname(libc::STDIN_FILENO);
+        if name.is_null() {
+            return "tty".to_string();
+        }
+        let name = CStr::from_ptr(name).to_string_lossy();
+        name.rsplit('/').next().unwrap_or_default().to_string()
+    }
+}
+
+fn working_directory(shell: &Shell) -> String {
+    let pwd = shell.variables.get("PWD").unwrap_or_default();
+    match shell.variables.get("HOME") {
+        Some(home) if !home.is_empty() && pwd == home => "~".to_string(),
+        Some(home) if !home.is_empty() && pwd.starts_with(&format!("{}/", home)) => {
+            format!("~{}", &pwd[home.len()..])
+        }
+        _ => pwd.to_string(),
+    }
+}
+
+fn prompt_escape(
+    c: char,
+    chars: &mut std::iter::Peekable<std::str::Chars>,
+    shell: &Shell,
+) -> String {
+    match c {
+        'a' => "\x07".to_string(),
+        'd' => format_time("%a %b %d"),
+        'D' if chars.peek() == Some(&'{') => {
+            chars.next();
+            let format: String = chars.by_ref().take_while(|&c| c != '}').collect();
+            format_time(if format.is_empty() { "%X" } else { &format })
+        }
+        'e' => "\x1b".to_string(),
+        'g' => {
+            let timeout = shell
+                .variables
+                .get("GIT_PROMPT_TIMEOUT")
+                .and_then(|timeout| timeout.parse().ok())
+                .unwrap_or(200);
+            git_segment(
+                shell.variables.get("PWD").unwrap_or_default(),
+                Duration::from_millis(timeout),
+            )
+        }
+        'h' => host_name()
+            .split('.')
+            .next()
+            .unwrap_or_default()
+            .to_string(),
+        'H' => host_name(),
+        'j' => shell.jobs.count().to_string(),
+        'l' => terminal_name(),
+        'n' => "\n".to_string(),
+        'r' => "\r".to_string(),
+        's' => {
+            let name = shell.variables.positional(0).unwrap_or("shell");
+            name.rsplit('/').next().unwrap_or(name).to_string()
+        }
+        't' => format_time("%H:%M:%S"),
+        'T' => format_time("%I:%M:%S"),
+        '@' => format_time("%I:%M %p"),
+        'A' => format_time("%H:%M"),
+        'u' => user_name(shell),
+        'v' => env!("CARGO_PKG_VERSION")
+            .rsplit_once('.')
+            .map_or(env!("CARGO_PKG_VERSION"), |(version, _)| version)
+            .to_string(),
+        'V' => env!("CARGO_PKG_VERSION").to_string(),
+        'w' => working_directory(shell),
+        'W' => {
+            let directory = working_directory(shell);
+            match directory.rsplit_once('/') {
+                Some((_, name)) if !name.is_empty() => name.to_string(),
+                _ => directory,
+            }
+        }
+        '!' => (shell.history.len() + 1).to_string(),
+        '?' => shell.variables.status().to_string(),
+        '$' => if unsafe { libc::geteuid() } == 0 {
+            "#"
+        } else {
+            "$"
+        }
+        .to_string(),
+        '[' => START_IGNORE.to_string(),
+        ']' => END_IGNORE.to_string(),
+        '\\' => "\\".to_string(),
+        '0'..='7' => {
+            let mut value = c.to_digit(8).unwrap_or(0);
+            for _ in 0..2 {
+                match chars.peek().and_then(|c| c.to_digit(8)) {
+                    Some(digit) => {
+                        value = value * 8 + digit;
+                        chars.next();
+                    }
+                    None => break,
+                }
+            }
+            char::from_u32(value).map(String::from).unwrap_or_default()
+        }
+        _ => format!("\\{}", c),
+    }
+}
+
+fn quote_expansion(text: &str, expanded: &mut String) {
+    for c in text.chars() {
+        if matches!(c, '$' | '`' | '"' | '\\') {
+            expanded.push('\\');
+        }
+        expanded.push(c);
+    }
+}
+
+pub fn render_prompt(template: &str, shell: &Shell) -> String {
+    let mut decoded = String::new();
+    let mut chars = template.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => match chars.next() {
+                Some(escape) => {
+                    let text = prompt_escape(escape, &mut chars, shell);
+                    quote_expansion(&text, &mut decoded);
+                }
+                None => decoded.push_str("\\\\"),
+            },
+            '"' | '`' => {
+                decoded.push('\\');
+                decoded.push(c);
+            }
+            _ => decoded.push(c),
+        }
+    }
+    expand_word(&format!("\"{}\"", decoded), &shell.variables).unwrap_or_default()
+}
+
+pub fn prompt_string(shell: &Shell, name: &str, default: &str) -> String {
+    match shell.variables.get(name) {
+        Some(template) => render_prompt(template, shell),
+        None => default.to_string(),
+    }
+}
+
+pub fn prompt_width(prompt: &str) -> (String, usize) {
+    let mut visible = String::new();
+    let mut width = 0;
+    let mut ignoring = false;
+    for c in prompt.chars() {
+        match c {
+            START_IGNORE => ignoring = true,
+            END_IGNORE => ignoring = false,
+            '\n' | '\r' => {
+                visible.push(c);
+                width = 0;
+            }
+            _ => {
+                visible.push(c);
+                if !ignoring {
+                    width += 1;
+                }
+            }
+        }
+    }
+    (visible, width)
+}
+
+fn hook_exists(shell: &mut Shell, name: &str) -> bool {
+    let path_var = shell.variables.get("PATH").unwrap_or_default().to_string();
+    shell.aliases.contains_key(name)
+        || shell.functions.contains_key(name)
+        || is_builtin(name)
+        || shell.command_hash.lookup(&path_var, name).is_some()
+}
+
+fn run_hook(shell: &mut Shell, command: &str) {
+    let status = shell.variables.status();
+    execute_command_line(command, shell);
+    if !shell.exiting {
+        shell.variables.set_status(status);
+    }
+}
+
+pub fn run_prompt_hooks(shell: &mut Shell) {
+    let commands: Vec<String> = shell
+        .variables
+        .get_array("PROMPT_COMMAND")
+        .unwrap_or_default()
+        .to_vec();
+    for command in commands {
+        run_hook(shell, &command);
+    }
+    if hook_exists(shell, "precmd") {
+        run_hook(shell, "precmd");
+    }
+}
+
+pub fn run_preexec_hook(shell: &mut Shell, line: &str) {
+    if hook_exists(shell, "preexec") {
+        let quoted = line.replace('\'', "'\\''");
+        run_hook(shell, &format!("preexec '{}'", quoted));
+    }
+}
@@ -0,0 +1,344 @@
+use crate::editor::read_line;
+use crate::parser::is_valid_name;
+use crate::shell::Shell;
+use crate::terminal::{enable_raw_mode, set_input_mode, wait_for_input};
+use std::io::Write;
+use std::os::unix::io::RawFd;
+use std::time::{Duration, Instant};
+
+const TIMEOUT_STATUS: i32 = 142;
+
+#[derive(Default)]
+struct ReadOptions {
+    raw: bool,
+    prompt: Option<String>,
+    silent: bool,
+    nchars: Option<usize>,
+    exact: bool,
+    delimiter: Option<u8>,
+    timeout: Option<Duration>,
+    array: Option<String>,
+    editor: bool,
+    names: Vec<String>,
+}
+
+fn parse_read_options(args: &[String]) -> Result<ReadOptions, String> {
+    let mut options = ReadOptions::default();
+    let mut iter = args.iter();
+
+    while let Some(arg) = iter.next() {
+        if !options.names.is_empty() || !arg.starts_with('-') || arg.len() == 1 {
+            options.names.push(arg.clone());
+            continue;
+        }
+        if arg == "--" {
+            options.names.extend(iter.by_ref().cloned());
+            break;
+        }
+
+        let flags: Vec<char> = arg[1..].chars().collect();
+        for (index, &flag) in flags.iter().enumerate() {
+            if !"pnNdta".contains(flag) {
+                match flag {
+                    'r' => options.raw = true,
+                    's' => options.silent = true,
+                    'e' => options.editor = true,
+                    _ => return Err(format!("-{}: invalid option", flag)),
+                }
+                continue;
+            }
+
+            let attached: String = flags[index + 1..].iter().collect();
+            let value = if attached.is_empty() {
+                iter.next()
+                    .cloned()
+                    .ok_or_else(|| format!("-{}: option requires an argument", flag))?
+            } else {
+                attached
+            };
+            match flag {
+                'p' => options.prompt = Some(value),
+                'n' | 'N' => {
+                    let count = value
+                        .parse()
+                        .map_err(|_| format!("{}: invalid number", value))?;
+                    options.nchars = Some(count);
+                    options.exact = flag == 'N';
+                }
+                'd' => options.delimiter = Some(value.bytes().next().unwrap_or(0)),
+                't' => {
+                    let seconds: f64 =
+                        value
+                            .parse()
+                            .ok()
+                            .filter(|seconds: &f64| *seconds >= 0.0)
+                            .ok_or_else(|| format!("{}: invalid timeout specification", value))?;
+                    options.timeout = Some(Duration::from_secs_f64(seconds));
+                }
+                _ => {
+                    if !is_valid_name(&value) {
+                        return Err(format!("`{}': not a valid identifier", value));
+                    }
+                    options.array = Some(value);
+                }
+            }
+            break;
+        }
+    }
+
+    if let Some(name) = options.names.iter().find(|name| !is_valid_name(name)) {
+        return Err(format!("`{}': not a valid identifier", name));
+    }
+    Ok(options)
+}
+
+enum ReadResult {
+    Complete,
+    EndOfFile,
+    TimedOut,
+}
+
+fn read_byte(fd: RawFd) -> Option<u8> {
+    let mut byte = 0u8;
+    let count = unsafe { libc::read(fd, (&mut byte as *mut u8).cast(), 1) };
+    (count == 1).then_some(byte)
+}
+
+fn read_input(fd: RawFd, options: &ReadOptions) -> (Vec<(char, bool)>, ReadResult) {
+    let delimiter = options.delimiter.unwrap_or(b'\n');
+    let deadline = options.timeout.map(|timeout| Instant::now() + timeout);
+    let mut bytes: Vec<(u8, bool)> = Vec::new();
+    let mut escaped = false;
+    let mut chars = 0;
+
+    let result = loop {
+        if options.nchars.is_some_and(|nchars| chars >= nchars) {
+            break ReadResult::Complete;
+        }
+        if let Some(deadline) = deadline {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if !wait_for_input(fd, remaining) {
+                break ReadResult::TimedOut;
+            }
+        }
+        let Some(byte) = read_byte(fd) else {
+            break ReadResult::EndOfFile;
+        };
+
+        if byte & 0xC0 != 0x80 {
+            chars += 1;
+        }
+        if escaped {
+            escaped = false;
+            if byte != b'\n' {
+                bytes.push((byte, true));
+            }
+            continue;
+        }
+        if !options.exact && byte == delimiter {
+            break ReadResult::Complete;
+        }
+        if byte == b'\\' && !options.raw {
+            escaped = true;
+            continue;
+        }
+        bytes.push((byte, false));
+    };
+
+    let text =
+        String::from_utf8_lossy(&bytes.iter().map(|(b, _)| *b).collect::<Vec<_>>()).into_owned();
+    let mut escapes = bytes
+        .iter()
+        .filter(|(b, _)| b & 0xC0 != 0x80)
+        .map(|(_, e)| *e);
+    let chars = text
+        .chars()
+        .map(|c| (c, escapes.next().unwrap_or(false)))
+        .collect();
+    (chars, result)
+}
+
+fn split_fields(input: &[(char, bool)], ifs: &str, max_fields: usize) -> Vec<String> {
+    let is_whitespace = |c: char| ifs.contains(c) && c.is_whitespace();
+    let is_separator = |&(c, escaped): &(char, bool)| !escaped && ifs.contains(c);
+
+    let mut fields = Vec::new();
+    let mut index = 0;
+    while index < input.len() && !input[index].1 && is_whitespace(input[index].0) {
+        index += 1;
+    }
+
+    while index < input.len() {
+        if max_fields > 0 && fields.len() + 1 == max_fields {
+            let mut rest = &input[index..];
+            while let Some((last, remaining)) = rest.split_last() {
+                if last.1 || !is_whitespace(last.0) {
+                    break;
+                }
+                rest = remaining;
+            }
+            fields.push(rest.iter().map(|(c, _)| c).collect());
+            return fields;
+        }
+
+        let start = index;
+        while index < input.len() && !is_separator(&input[index]) {
+            index += 1;
+        }
+        fields.push(input[start..index].iter().map(|(c, _)| c).collect());
+
+        while index < input.len() && !input[index].1 && is_whitespace(input[index].0) {
+            index += 1;
+        }
+        if index < input.len() && is_separator(&input[index]) {
+            index += 1;
+            while index < input.len() && !input[index].1 && is_whitespace(input[index].0) {
+                index += 1;
+            }
+        }
+    }
+    fields
+}
+
+fn assign_fields(
+    input: &[(char, bool)],
+    options: &ReadOptions,
+    shell: &mut Shell,
+    err: &mut dyn Write,
+) {
+    let ifs = shell.variables.get("IFS").unwrap_or(" \t\n").to_string();
+    let variables = &mut shell.variables;
+
+    let result = if let Some(array) = &options.array {
+        variables.set_array(array, split_fields(input, &ifs, 0))
+    } else if options.names.is_empty() {
+        variables.set("REPLY", &input.iter().map(|(c, _)| c).collect::<String>())
+    } else {
+        let mut fields = split_fields(input, &ifs, options.names.len()).into_iter();
+        options
+            .names
+            .iter()
+            .try_for_each(|name| variables.set(name, &fields.next().unwrap_or_default()))
+    };
+    if let Err(e) = result {
+        let _ = writeln!(err, "read: {}", e);
+    }
+}
+
+pub fn read_builtin(args: &[String], shell: &mut Shell, fd: RawFd, err: &mut dyn Write) -> i32 {
+    let options = match parse_read_options(args) {
+        Ok(options) => options,
+        Err(e) => {
+            let _ = writeln!(err, "read: {}", e);
+            let _ = writeln!(
+                err,
+                "read: usage: read [-ers] [-a array] [-d delim] [-n nchars] [-N nchars] [-p prompt] [-t timeout] [name ...]"
+            );
+            return 2;
+        }
+    };
+
+    let terminal = unsafe { libc::isatty(fd) == 1 };
+
+    if options.timeout == Some(Duration::ZERO) {
+        return if wait_for_input(fd, Duration::ZERO) {
+            0
+        } else {
+            1
+        };
+    }
+
+    if terminal && options.editor && fd == libc::STDIN_FILENO {
+        let prompt = options.prompt.clone().unwrap_or_default();
+        let Some(line) = read_line(shell, &prompt, "", None) else {
+            return 1;
+        };
+        let input: Vec<(char, bool)> = line.chars().map(|c| (c, false)).collect();
+        assign_fields(&input, &options, shell, err);
+        return 0;
+    }
+
+    if terminal && let Some(prompt) = &options.prompt {
+        let _ = write!(err, "{}", prompt);
+        let _ = err.flush();
+    }
+
+    if terminal {
+        let canonical = options.nchars.is_none() && options.delimiter.is_none();
+        set_input_mode(!options.silent, canonical);
+    }
+    let (input, result) = read_input(fd, &options);
+    if terminal {
+        enable_raw_mode();
+    }
+
+    assign_fields(&input, &options, shell, err);
+    match result {
+        ReadResult::Complete => 0,
+        ReadResult::EndOfFile => 1,
+        ReadResult::TimedOut => TIMEOUT_STATUS,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn plain(text: &str) -> Vec<(char, bool)> {
+        text.chars().map(|c| (c, false)).collect()
+    }
+
+    fn split(text: &str, ifs: &str, max_fields: usize) -> Vec<String> {
+        split_fields(&plain(text), ifs, max_fields)
+    }
+
+    #[test]
+    fn whitespace_ifs_collapses_runs() {
+        assert_eq!(split("  a \t b  c  ", " \t\n", 0), ["a", "b", "c"]);
+        assert_eq!(split("", " \t\n", 0), Vec::<String>::new());
+    }
+
+    #[test]
+    fn last_field_takes_the_rest() {
+        assert_eq!(split(" a b  c d  ", " \t\n", 2), ["a", "b  c d"]);
+        assert_eq!(split("a:b:c:", ":", 2), ["a", "b:c:"]);
+    }
+
+    #[test]
+    fn non_whitespace_ifs_keeps_empty_fields() {
+        assert_eq!(split("a::b", ":", 0), ["a", "", "b"]);
+        assert_eq!(split(":a", ":", 0), ["", "a"]);
+        assert_eq!(split("a : b", ": ", 0), ["a", "b"]);
+    }
+
+    #[test]
+    fn escaped_separators_are_literal() {
+        let mut input = plain("a b");
+        input[1].1 = true;
+        assert_eq!(split_fields(&input, " ", 0), ["a b"]);
+    }
+
+    #[test]
+    fn empty_ifs_does_not_split() {
+        assert_eq!(split(" a b ", "", 0), [" a b "]);
+    }
+
+    #[test]
+    fn option_errors() {
+        let args =
+            |line: &str| -> Vec<String> { line.split(' ').map(|word| word.to_string()).collect() };
+        assert!(parse_read_options(&args("-r -p prompt a b")).is_ok());
+        assert_eq!(
+            parse_read_options(&args("-n x")).err(),
+            Some("x: invalid number".to_string())
+        );
+        assert_eq!(
+            parse_read_options(&args("1a")).err(),
+            Some("`1a': not a valid identifier".to_string())
+        );
+        assert_eq!(
+            parse_read_options(&args("-t -1")).err(),
+            Some("-1: invalid timeout specification".to_string())
+        );
+    }
+}
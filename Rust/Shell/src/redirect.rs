@@ -0,0 +1,288 @@
+use crate::directory::error_reason;
+use crate::parser::expand_word;
+use crate::shell::Shell;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::os::unix::fs::OpenOptionsExt;
+use std::os::unix::io::{IntoRawFd, RawFd};
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum RedirectOp {
+    Read,
+    Write,
+    Clobber,
+    Append,
+    Duplicate,
+}
+
+#[derive(Clone)]
+pub struct Redirect {
+    pub fd: RawFd,
+    pub op: RedirectOp,
+    pub target: String,
+}
+
+fn redirect_operator(word: &str) -> Option<(Option<RawFd>, &str, &str)> {
+    for op in ["&>>", "&>"] {
+        if let Some(rest) = word.strip_prefix(op) {
+            return Some((None, op, rest));
+        }
+    }
+
+    let digits = word.len() - word.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+    let fd = word[..digits].parse().ok();
+    let rest = &word[digits..];
+    ["<&", ">>", ">&", ">|", "<", ">"]
+        .into_iter()
+        .find_map(|op| rest.strip_prefix(op).map(|target| (fd, op, target)))
+}
+
+pub fn is_redirect(word: &str) -> bool {
+    redirect_operator(word).is_some()
+}
+
+pub fn split_redirects(words: &[String]) -> Result<(Vec<Redirect>, Vec<String>), String> {
+    let mut redirects = Vec::new();
+    let mut rest = Vec::new();
+    let mut iter = words.iter();
+
+    while let Some(word) = iter.next() {
+        let Some((fd, op, target)) = redirect_operator(word) else {
+            rest.push(word.clone());
+            continue;
+        };
+        let target = if target.is_empty() {
+            iter.next()
+                .filter(|next| !is_redirect(next))
+                .ok_or_else(|| "newline".to_string())?
+                .clone()
+        } else {
+            target.to_string()
+        };
+
+        let is_fd = target == "-" || target.chars().all(|c| c.is_ascii_digit());
+        match op {
+            "&>" | "&>>" => {
+                let op = if op == "&>" {
+                    RedirectOp::Write
+                } else {
+                    RedirectOp::Append
+                };
+                redirects.push(Redirect { fd: 1, op, target });
+                redirects.push(Redirect {
+                    fd: 2,
+                    op: RedirectOp::Duplicate,
+                    target: "1".to_string(),
+                });
+            }
+            ">&" if fd.is_none() && !is_fd => {
+                redirects.push(Redirect {
+                    fd: 1,
+                    op: RedirectOp::Write,
+                    target,
+                });
+                redirects.push(Redirect {
+                    fd: 2,
+                    op: RedirectOp::Duplicate,
+                    target: "1".to_string(),
+                });
+            }
+            _ => {
+                let (default_fd, op) = match op {
+                    "<" => (0, RedirectOp::Read),
+                    ">" => (1, RedirectOp::Write),
+                    ">|" => (1, RedirectOp::Clobber),
+                    ">>" => (1, RedirectOp::Append),
+                    "<&" => (0, RedirectOp::Duplicate),
+                    _ => (1, RedirectOp::Duplicate),
+                };
+                redirects.push(Redirect {
+                    fd: fd.unwrap_or(default_fd),
+                    op,
+                    target,
+                });
+            }
+        }
+    }
+    Ok((redirects, rest))
+}
+
+pub struct SavedFds(Vec<(RawFd, RawFd)>);
+
+fn open_without_clobber(filename: &str) -> io::Result<File> {
+    match OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .mode(0o666)
+        .open(filename)
+    {
+        Err(e) if e.kind() == io::ErrorKind::AlreadyExists => {
+            if fs::metadata(filename).is_ok_and(|metadata| metadata.is_file()) {
+                return Err(e);
+            }
+            OpenOptions::new().write(true).open(filename)
+        }
+        result => result,
+    }
+}
+
+fn open_target(op: RedirectOp, filename: &str, noclobber: bool) -> Result<RawFd, String> {
+    let result = match op {
+        RedirectOp::Read => File::open(filename),
+        RedirectOp::Write if noclobber => open_without_clobber(filename),
+        RedirectOp::Write | RedirectOp::Clobber => File::create(filename),
+        _ => OpenOptions::new().create(true).append(true).open(filename),
+    };
+    result.map(|file| file.into_raw_fd()).map_err(|e| {
+        let reason = if noclobber && e.kind() == io::ErrorKind::AlreadyExists {
+            "cannot overwrite existing file".to_string()
+        } else {
+            error_reason(&e)
+        };
+        format!("shell: {}: {}", filename, reason)
+    })
+}
+
+fn redirect_fd(redirect: &Redirect, shell: &Shell) -> Result<Option<RawFd>, String> {
+    let target = expand_word(&redirect.target, &shell.variables).unwrap_or_default();
+    if redirect.op != RedirectOp::Duplicate {
+        return open_target(redirect.op, &target, shell.options.noclobber).map(Some);
+    }
+    if target == "-" {
+        return Ok(None);
+    }
+    match target.parse::<RawFd>() {
+        Ok(fd) if unsafe { libc::fcntl(fd, libc::F_GETFD) } != -1 => {
+            let copy = unsafe { libc::dup(fd) };
+            Ok(Some(copy))
+        }
+        _ => Err(format!("shell: {}: Bad file descriptor", target)),
+    }
+}
+
+pub fn apply_redirects(redirects: &[Redirect], shell: &Shell) -> Result<SavedFds, ()> {
+    let mut saved = SavedFds(Vec::new());
+    for redirect in redirects {
+        let source = match redirect_fd(redirect, shell) {
+            Ok(source) => source,
+            Err(e) => {
+                eprintln!("{}", e);
+                restore_fds(saved);
+                return Err(());
+            }
+        };
+
+        let _ = io::stdout().flush();
+        if !saved.0.iter().any(|(fd, _)| *fd == redirect.fd) {
+            let copy = unsafe { libc::fcntl(redirect.fd, libc::F_DUPFD_CLOEXEC, 10) };
+            saved.0.push((redirect.fd, copy));
+        }
+        unsafe {
+            match source {
+                Some(source) if source != redirect.fd => {
+                    libc::dup2(source, redirect.fd);
+                    libc::close(source);
+                }
+                Some(_) => {}
+                None => {
+                    libc::close(redirect.fd);
+                }
+            }
+        }
+    }
+    Ok(saved)
+}
+
+pub fn restore_fds(saved: SavedFds) {
+    let _ = io::stdout().flush();
+    for (fd, copy) in saved.0.into_iter().rev() {
+        unsafe {
+            if copy >= 0 {
+                libc::dup2(copy, fd);
+                libc::close(copy);
+            } else {
+                libc::close(fd);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    fn words(line: &str) -> Vec<String> {
+        line.split(' ').map(|word| word.to_string()).collect()
+    }
+
+    fn parsed(line: &str) -> Vec<(RawFd, RedirectOp, String)> {
+        let (redirects, _) = split_redirects(&words(line)).unwrap();
+        redirects
+            .into_iter()
+            .map(|redirect| (redirect.fd, redirect.op, redirect.target))
+            .collect()
+    }
+
+    fn temp_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("shell-redirect-{}-{}", std::process::id(), name))
+    }
+
+    #[test]
+    fn splits_words_from_redirects() {
+        let (redirects, rest) = split_redirects(&words("echo a >out b 2>>log")).unwrap();
+        assert_eq!(rest, ["echo", "a", "b"]);
+        assert_eq!(redirects.len(), 2);
+        assert!(split_redirects(&words("echo >")).is_err());
+        assert!(split_redirects(&words("echo > <in")).is_err());
+    }
+
+    #[test]
+    fn operators_and_default_descriptors() {
+        assert!(parsed("<in") == [(0, RedirectOp::Read, "in".to_string())]);
+        assert!(parsed("> out") == [(1, RedirectOp::Write, "out".to_string())]);
+        assert!(parsed("3>|f") == [(3, RedirectOp::Clobber, "f".to_string())]);
+        assert!(parsed("2>&1") == [(2, RedirectOp::Duplicate, "1".to_string())]);
+        assert!(parsed("<&-") == [(0, RedirectOp::Duplicate, "-".to_string())]);
+    }
+
+    #[test]
+    fn combined_output_redirects() {
+        let both = |op| {
+            vec![
+                (1, op, "f".to_string()),
+                (2, RedirectOp::Duplicate, "1".to_string()),
+            ]
+        };
+        assert!(parsed("&>f") == both(RedirectOp::Write));
+        assert!(parsed("&>>f") == both(RedirectOp::Append));
+        assert!(parsed(">&f") == both(RedirectOp::Write));
+    }
+
+    #[test]
+    fn noclobber_refuses_existing_files() {
+        let path = temp_path("noclobber");
+        let name = path.to_str().unwrap();
+        fs::write(&path, "keep").unwrap();
+
+        assert_eq!(
+            open_target(RedirectOp::Write, name, true).err(),
+            Some(format!("shell: {}: cannot overwrite existing file", name))
+        );
+        let fd = open_target(RedirectOp::Clobber, name, true).unwrap();
+        unsafe { libc::close(fd) };
+        assert_eq!(fs::read_to_string(&path).unwrap(), "");
+
+        fs::remove_file(&path).unwrap();
+        let fd = open_target(RedirectOp::Write, name, true).unwrap();
+        unsafe { libc::close(fd) };
+        assert!(path.exists());
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn noclobber_allows_non_regular_files() {
+        let fd = open_target(RedirectOp::Write, "/dev/null", true).unwrap();
+        unsafe { libc::close(fd) };
+    }
+}
@@ -0,0 +1,61 @@
+use crate::completion::{CompletionSettings, CompletionSpecs};
+use crate::directory::logical_directory;
+use crate::hash::CommandHash;
+use crate::jobs::JobTable;
+use crate::options::ShellOptions;
+use crate::variables::Variables;
+use std::collections::BTreeMap;
+
+pub struct Shell {
+    pub history: Vec<String>,
+    pub last_saved_index: usize,
+    pub current_line_added: bool,
+    pub aliases: BTreeMap<String, String>,
+    pub functions: BTreeMap<String, Vec<String>>,
+    pub completion_specs: CompletionSpecs,
+    pub completion_settings: CompletionSettings,
+    pub command_hash: CommandHash,
+    pub variables: Variables,
+    pub dir_stack: Vec<String>,
+    pub exiting: bool,
+    pub returning: bool,
+    pub call_depth: usize,
+    pub interactive: bool,
+    pub job_control: bool,
+    pub options: ShellOptions,
+    pub errexit_ignored: usize,
+    pub traps: BTreeMap<i32, String>,
+    pub running_trap: bool,
+    pub jobs: JobTable,
+}
+
+impl Shell {
+    pub fn new() -> Self {
+        let mut shell = Shell {
+            history: Vec::new(),
+            last_saved_index: 0,
+            current_line_added: false,
+            aliases: BTreeMap::new(),
+            functions: BTreeMap::new(),
+            completion_specs: CompletionSpecs::default(),
+            completion_settings: CompletionSettings::default(),
+            command_hash: CommandHash::default(),
+            variables: Variables::from_env(),
+            dir_stack: Vec::new(),
+            exiting: false,
+            returning: false,
+            call_depth: 0,
+            interactive: unsafe { libc::isatty(libc::STDIN_FILENO) == 1 },
+            job_control: false,
+            options: ShellOptions::default(),
+            errexit_ignored: 0,
+            traps: BTreeMap::new(),
+            running_trap: false,
+            jobs: JobTable::default(),
+        };
+        if let Some(pwd) = logical_directory(&shell) {
+            let _ = shell.variables.set("PWD", &pwd);
+        }
+        shell
+    }
+}
@@ -0,0 +1,113 @@
+use crate::directory::error_reason;
+use crate::executor::execute_command_line;
+use crate::parser::expand_word;
+use crate::shell::Shell;
+use crate::syntax::is_incomplete;
+use crate::trap::{RETURN_TRAP, run_trap};
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+fn find_source_file(name: &str, shell: &Shell) -> PathBuf {
+    if name.contains('/') {
+        return PathBuf::from(name);
+    }
+    shell
+        .variables
+        .get("PATH")
+        .unwrap_or_default()
+        .split(':')
+        .filter(|dir| !dir.is_empty())
+        .map(|dir| Path::new(dir).join(name))
+        .find(|path| path.is_file())
+        .unwrap_or_else(|| PathBuf::from(name))
+}
+
+pub fn source_file(path: &Path, shell: &mut Shell) -> io::Result<()> {
+    let contents = fs::read_to_string(path)?;
+    let mut buffer = String::new();
+    for line in contents.lines() {
+        if shell.options.verbose {
+            eprintln!("{}", line);
+        }
+        if buffer.is_empty() && line.trim_start().starts_with('#') {
+            continue;
+        }
+        if !buffer.is_empty() {
+            buffer.push('\n');
+        }
+        buffer.push_str(line);
+        if is_incomplete(&buffer) {
+            continue;
+        }
+        if execute_command_line(&buffer, shell) || shell.returning {
+            return Ok(());
+        }
+        buffer.clear();
+    }
+    if !buffer.is_empty() {
+        execute_command_line(&buffer, shell);
+    }
+    Ok(())
+}
+
+pub fn source_builtin(
+    builtin: &str,
+    args: &[String],
+    shell: &mut Shell,
+    err: &mut dyn Write,
+) -> i32 {
+    let Some((name, arguments)) = args.split_first() else {
+        let _ = writeln!(err, "{}: filename argument required", builtin);
+        let _ = writeln!(err, "{}: usage: {} filename [arguments]", builtin, builtin);
+        return 2;
+    };
+
+    let path = find_source_file(name, shell);
+    let previous =
+        (!arguments.is_empty()).then(|| shell.variables.set_arguments(arguments.to_vec()));
+    let current_line_added = std::mem::take(&mut shell.current_line_added);
+    shell.call_depth += 1;
+    let result = source_file(&path, shell);
+    shell.call_depth -= 1;
+    shell.returning = false;
+    shell.current_line_added = current_line_added;
+    run_trap(shell, RETURN_TRAP);
+    if let Some(previous) = previous {
+        shell.variables.set_arguments(previous);
+    }
+
+    match result {
+        Ok(()) => shell.variables.status(),
+        Err(e) => {
+            let _ = writeln!(err, "{}: {}: {}", builtin, name, error_reason(&e));
+            1
+        }
+    }
+}
+
+fn startup_file(shell: &mut Shell, path: &str) {
+    match source_file(Path::new(path), shell) {
+        Err(e) if e.kind() != io::ErrorKind::NotFound => {
+            eprintln!("shell: {}: {}", path, error_reason(&e));
+        }
+        _ => {}
+    }
+}
+
+pub fn load_startup_files(shell: &mut Shell, login: bool, profile: bool, rc: bool) {
+    let home = shell.variables.get("HOME").unwrap_or_default().to_string();
+
+    if login && profile {
+        startup_file(shell, &format!("{}/.shell_profile", home));
+    }
+
+    if shell.exiting || !shell.interactive || !rc {
+        return;
+    }
+    let rc_file = match shell.variables.get("ENV") {
+        Some(env) if !env.is_empty() => expand_word(env, &shell.variables).unwrap_or_default(),
+        _ => format!("{}/.shellrc", home),
+    };
+    startup_file(shell, &rc_file)
+}
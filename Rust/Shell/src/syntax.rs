@@ -0,0 +1,257 @@
+use crate::parser::{has_open_quote, is_operator, is_valid_name, split_words};
+use crate::redirect::{Redirect, split_redirects};
+use std::fmt;
+
+pub struct SimpleCommand {
+    pub words: Vec<String>,
+    pub redirects: Vec<Redirect>,
+}
+
+pub enum Command {
+    Simple(SimpleCommand),
+    Group(List, Vec<Redirect>),
+    Subshell(List, Vec<Redirect>),
+    Function(String, Vec<String>),
+}
+
+pub struct Pipeline {
+    pub negated: bool,
+    pub commands: Vec<Command>,
+    pub text: String,
+}
+
+#[derive(PartialEq)]
+pub enum Connector {
+    And,
+    Or,
+}
+
+pub struct AndOr {
+    pub first: Pipeline,
+    pub rest: Vec<(Connector, Pipeline)>,
+    pub background: bool,
+    pub text: String,
+}
+
+pub type List = Vec<AndOr>;
+
+pub enum ParseError {
+    Incomplete,
+    Unexpected(String),
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ParseError::Incomplete => write!(f, "syntax error: unexpected end of file"),
+            ParseError::Unexpected(token) if token == "\n" => {
+                write!(f, "syntax error near unexpected token `newline'")
+            }
+            ParseError::Unexpected(token) => {
+                write!(f, "syntax error near unexpected token `{}'", token)
+            }
+        }
+    }
+}
+
+struct Parser<'a> {
+    words: &'a [String],
+    pos: usize,
+}
+
+impl Parser<'_> {
+    fn peek(&self) -> Option<&str> {
+        self.words.get(self.pos).map(|s| s.as_str())
+    }
+
+    fn skip_newlines(&mut self) {
+        while self.peek() == Some("\n") {
+            self.pos += 1;
+        }
+    }
+
+    fn list(&mut self, terminator: Option<&str>) -> Result<List, ParseError> {
+        let mut list = Vec::new();
+        loop {
+            self.skip_newlines();
+            match self.peek() {
+                None if terminator.is_some() => return Err(ParseError::Incomplete),
+                None => break,
+                Some(word) if Some(word) == terminator => break,
+                _ => {}
+            }
+
+            let mut and_or = self.and_or()?;
+            match self.peek() {
+                Some(";" | "\n") => self.pos += 1,
+                Some("&") => {
+                    and_or.background = true;
+                    self.pos += 1;
+                }
+                None => {}
+                Some(word) if Some(word) == terminator => {}
+                Some(word) => return Err(ParseError::Unexpected(word.to_string())),
+            }
+            list.push(and_or);
+        }
+
+        if list.is_empty()
+            && let Some(terminator) = terminator
+        {
+            return Err(ParseError::Unexpected(terminator.to_string()));
+        }
+        Ok(list)
+    }
+
+    fn and_or(&mut self) -> Result<AndOr, ParseError> {
+        let start = self.pos;
+        let first = self.pipeline()?;
+        let mut rest = Vec::new();
+        loop {
+            let connector = match self.peek() {
+                Some("&&") => Connector::And,
+                Some("||") => Connector::Or,
+                _ => break,
+            };
+            self.pos += 1;
+            self.skip_newlines();
+            rest.push((connector, self.pipeline()?));
+        }
+        Ok(AndOr {
+            first,
+            rest,
+            background: false,
+            text: self.words[start..self.pos].join(" "),
+        })
+    }
+
+    fn pipeline(&mut self) -> Result<Pipeline, ParseError> {
+        let start = self.pos;
+        let negated = self.peek() == Some("!");
+        if negated {
+            self.pos += 1;
+        }
+        let mut commands = vec![self.command()?];
+        while self.peek() == Some("|") {
+            self.pos += 1;
+            self.skip_newlines();
+            commands.push(self.command()?);
+        }
+        Ok(Pipeline {
+            negated,
+            commands,
+            text: self.words[start..self.pos].join(" "),
+        })
+    }
+
+    fn command(&mut self) -> Result<Command, ParseError> {
+        let close = match self.peek() {
+            None => return Err(ParseError::Incomplete),
+            Some("(") => ")",
+            Some("{") => "}",
+            Some(word) if is_operator(word) => {
+                return Err(ParseError::Unexpected(word.to_string()));
+            }
+            Some("function") => {
+                self.pos += 1;
+                return self.function_definition();
+            }
+            Some(word)
+                if is_valid_name(word)
+                    && self.words.get(self.pos + 1).is_some_and(|word| word == "(") =>
+            {
+                return self.function_definition();
+            }
+            Some(_) => return self.simple_command(),
+        };
+
+        self.pos += 1;
+        let list = self.list(Some(close))?;
+        self.pos += 1;
+        let redirects = self.group_redirects()?;
+        Ok(if close == ")" {
+            Command::Subshell(list, redirects)
+        } else {
+            Command::Group(list, redirects)
+        })
+    }
+
+    fn function_definition(&mut self) -> Result<Command, ParseError> {
+        let name = match self.peek() {
+            None => return Err(ParseError::Incomplete),
+            Some(word) if is_valid_name(word) => word.to_string(),
+            Some(word) => return Err(ParseError::Unexpected(word.to_string())),
+        };
+        self.pos += 1;
+        if self.peek() == Some("(") {
+            self.pos += 1;
+            match self.peek() {
+                Some(")") => self.pos += 1,
+                Some(word) => return Err(ParseError::Unexpected(word.to_string())),
+                None => return Err(ParseError::Incomplete),
+            }
+        }
+
+        self.skip_newlines();
+        match self.peek() {
+            Some("{" | "(") => {}
+            Some(word) => return Err(ParseError::Unexpected(word.to_string())),
+            None => return Err(ParseError::Incomplete),
+        }
+        let start = self.pos;
+        self.command()?;
+        Ok(Command::Function(
+            name,
+            self.words[start..self.pos].to_vec(),
+        ))
+    }
+
+    fn take_words(&mut self) -> Vec<String> {
+        let start = self.pos;
+        while self.peek().is_some_and(|word| !is_operator(word)) {
+            self.pos += 1;
+        }
+        self.words[start..self.pos].to_vec()
+    }
+
+    fn redirects(&self, words: &[String]) -> Result<(Vec<Redirect>, Vec<String>), ParseError> {
+        split_redirects(words)
+            .map_err(|_| ParseError::Unexpected(self.peek().unwrap_or("\n").to_string()))
+    }
+
+    fn group_redirects(&mut self) -> Result<Vec<Redirect>, ParseError> {
+        let words = self.take_words();
+        let (redirects, rest) = self.redirects(&words)?;
+        match rest.into_iter().next() {
+            Some(word) => Err(ParseError::Unexpected(word)),
+            None => Ok(redirects),
+        }
+    }
+
+    fn simple_command(&mut self) -> Result<Command, ParseError> {
+        if self.peek() == Some("[[") {
+            let start = self.pos;
+            while let Some(word) = self.words.get(self.pos) {
+                self.pos += 1;
+                if word == "]]" {
+                    break;
+                }
+            }
+            let words = self.words[start..self.pos].to_vec();
+            let redirects = self.group_redirects()?;
+            return Ok(Command::Simple(SimpleCommand { words, redirects }));
+        }
+
+        let words = self.take_words();
+        let (redirects, words) = self.redirects(&words)?;
+        Ok(Command::Simple(SimpleCommand { words, redirects }))
+    }
+}
+
+pub fn parse(words: &[String]) -> Result<List, ParseError> {
+    Parser { words, pos: 0 }.list(None)
+}
+
+pub fn is_incomplete(input: &str) -> bool {
+    has_open_quote(input) || matches!(parse(&split_words(input)), Err(ParseError::Incomplete))
+}
@@ -1,8 +1,59 @@
-use std::io;
+use std::io::{self, Read};
 use std::os::unix::io::AsRawFd;
+use std::time::Duration;
+
+pub struct TerminalInput;
+
+impl Read for TerminalInput {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let count = unsafe { libc::read(libc::STDIN_FILENO, buf.as_mut_ptr().cast(), buf.len()) };
+        if count < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(count as usize)
+    }
+}
+
+pub fn wait_for_input(fd: libc::c_int, timeout: Duration) -> bool {
+    let mut poll_fd = libc::pollfd {
+        fd,
+        events: libc::POLLIN,
+        revents: 0,
+    };
+    let millis = timeout.as_millis().min(libc::c_int::MAX as u128) as libc::c_int;
+    unsafe { libc::poll(&mut poll_fd, 1, millis) > 0 }
+}
+
+fn in_foreground(fd: libc::c_int) -> bool {
+    unsafe { libc::tcgetpgrp(fd) == libc::getpgrp() }
+}
+
+pub fn set_input_mode(echo: bool, canonical: bool) {
+    let stdin_fd = io::stdin().as_raw_fd();
+    if !in_foreground(stdin_fd) {
+        return;
+    }
+    let mut termios = std::mem::MaybeUninit::<libc::termios>::uninit();
+
+    unsafe {
+        libc::tcgetattr(stdin_fd, termios.as_mut_ptr());
+        let mut termios = termios.assume_init();
+        for (enabled, flag) in [(echo, libc::ECHO), (canonical, libc::ICANON)] {
+            if enabled {
+                termios.c_lflag |= flag;
+            } else {
+                termios.c_lflag &= !flag;
+            }
+        }
+        libc::tcsetattr(stdin_fd, libc::TCSANOW, &termios);
+    }
+}
 
 pub fn enable_raw_mode() {
     let stdin_fd = io::stdin().as_raw_fd();
+    if !in_foreground(stdin_fd) {
+        return;
+    }
     let mut termios = std::mem::MaybeUninit::<libc::termios>::uninit();
 
     unsafe {
@@ -15,6 +66,9 @@ pub fn enable_raw_mode() {
 
 pub fn disable_raw_mode() {
     let stdin_fd = io::stdin().as_raw_fd();
+    if !in_foreground(stdin_fd) {
+        return;
+    }
     let mut termios = std::mem::MaybeUninit::<libc::termios>::uninit();
 
     unsafe {
@@ -24,3 +78,40 @@ pub fn disable_raw_mode() {
         libc::tcsetattr(stdin_fd, libc::TCSANOW, &termios);
     }
 }
+
+fn window_size() -> Option<libc::winsize> {
+    let mut size = std::mem::MaybeUninit::<libc::winsize>::uninit();
+
+    let result = unsafe { libc::ioctl(libc::STDOUT_FILENO, libc::TIOCGWINSZ, size.as_mut_ptr()) };
+    if result == 0 {
+        Some(unsafe { size.assume_init() })
+    } else {
+        None
+    }
+}
+
+pub fn terminal_width() -> usize {
+    if let Some(size) = window_size()
+        && size.ws_col > 0
+    {
+        return size.ws_col as usize;
+    }
+
+    std::env::var("COLUMNS")
+        .ok()
+        .and_then(|columns| columns.parse().ok())
+        .unwrap_or(80)
+}
+
+pub fn terminal_height() -> usize {
+    if let Some(size) = window_size()
+        && size.ws_row > 0
+    {
+        return size.ws_row as usize;
+    }
+
+    std::env::var("LINES")
+        .ok()
+        .and_then(|lines| lines.parse().ok())
+        .unwrap_or(24)
+}
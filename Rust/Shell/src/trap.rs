@@ -0,0 +1,257 @@
+use crate::executor::execute_command_line;
+use crate::shell::Shell;
+use std::io::Write;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+pub const SIGNALS: [&str; 31] = [
+    "SIGHUP",
+    "SIGINT",
+    "SIGQUIT",
+    "SIGILL",
+    "SIGTRAP",
+    "SIGABRT",
+    "SIGBUS",
+    "SIGFPE",
+    "SIGKILL",
+    "SIGUSR1",
+    "SIGSEGV",
+    "SIGUSR2",
+    "SIGPIPE",
+    "SIGALRM",
+    "SIGTERM",
+    "SIGSTKFLT",
+    "SIGCHLD",
+    "SIGCONT",
+    "SIGSTOP",
+    "SIGTSTP",
+    "SIGTTIN",
+    "SIGTTOU",
+    "SIGURG",
+    "SIGXCPU",
+    "SIGXFSZ",
+    "SIGVTALRM",
+    "SIGPROF",
+    "SIGWINCH",
+    "SIGIO",
+    "SIGPWR",
+    "SIGSYS",
+];
+
+pub const EXIT_TRAP: i32 = 0;
+pub const DEBUG_TRAP: i32 = 65;
+pub const ERR_TRAP: i32 = 66;
+pub const RETURN_TRAP: i32 = 67;
+
+const PSEUDO_SIGNALS: [(&str, i32); 4] = [
+    ("EXIT", EXIT_TRAP),
+    ("DEBUG", DEBUG_TRAP),
+    ("ERR", ERR_TRAP),
+    ("RETURN", RETURN_TRAP),
+];
+
+static PENDING_SIGNALS: AtomicU64 = AtomicU64::new(0);
+
+extern "C" fn record_signal(signal: libc::c_int) {
+    PENDING_SIGNALS.fetch_or(1 << signal, Ordering::SeqCst);
+}
+
+pub fn signal_number(spec: &str) -> Option<i32> {
+    if let Ok(number) = spec.parse::<i32>() {
+        return (0..=SIGNALS.len() as i32)
+            .contains(&number)
+            .then_some(number);
+    }
+    let upper = spec.to_ascii_uppercase();
+    let name = upper.strip_prefix("SIG").unwrap_or(&upper);
+    SIGNALS
+        .iter()
+        .position(|signal| &signal[3..] == name)
+        .map(|index| index as i32 + 1)
+}
+
+pub fn signal_name(number: i32) -> Option<&'static str> {
+    usize::try_from(number)
+        .ok()
+        .and_then(|number| number.checked_sub(1))
+        .and_then(|index| SIGNALS.get(index))
+        .map(|name| &name[3..])
+}
+
+fn trap_number(spec: &str) -> Option<i32> {
+    let upper = spec.to_ascii_uppercase();
+    PSEUDO_SIGNALS
+        .iter()
+        .find(|(name, _)| *name == upper)
+        .map(|(_, number)| *number)
+        .or_else(|| signal_number(spec))
+}
+
+fn trap_name(number: i32) -> String {
+    PSEUDO_SIGNALS
+        .iter()
+        .find(|(_, pseudo)| *pseudo == number)
+        .map(|(name, _)| name.to_string())
+        .unwrap_or_else(|| format!("SIG{}", signal_name(number).unwrap_or_default()))
+}
+
+fn set_disposition(number: i32, command: Option<&str>) {
+    if !(1..=SIGNALS.len() as i32).contains(&number) {
+        return;
+    }
+    let handler = match command {
+        None => libc::SIG_DFL,
+        Some("") => libc::SIG_IGN,
+        Some(_) => record_signal as extern "C" fn(libc::c_int) as libc::sighandler_t,
+    };
+    unsafe {
+        libc::signal(number, handler);
+    }
+}
+
+fn run_handler(shell: &mut Shell, command: &str) {
+    let status = shell.variables.status();
+    let current_line_added = std::mem::take(&mut shell.current_line_added);
+    shell.running_trap = true;
+    execute_command_line(command, shell);
+    shell.running_trap = false;
+    shell.current_line_added = current_line_added;
+    if !shell.exiting {
+        shell.variables.set_status(status);
+    }
+}
+
+pub fn run_trap(shell: &mut Shell, number: i32) {
+    if shell.running_trap {
+        return;
+    }
+    if let Some(command) = shell
+        .traps
+        .get(&number)
+        .filter(|command| !command.is_empty())
+    {
+        run_handler(shell, &command.clone());
+    }
+}
+
+pub fn run_pending_traps(shell: &mut Shell) {
+    let pending = PENDING_SIGNALS.swap(0, Ordering::SeqCst);
+    if pending == 0 {
+        return;
+    }
+    for number in 1..=SIGNALS.len() as i32 {
+        if pending & (1 << number) != 0 {
+            run_trap(shell, number);
+        }
+    }
+}
+
+pub fn run_exit_trap(shell: &mut Shell) {
+    if let Some(command) = shell.traps.remove(&EXIT_TRAP) {
+        shell.exiting = false;
+        run_handler(shell, &command);
+        shell.exiting = true;
+    }
+}
+
+pub fn reset_subshell_traps(shell: &mut Shell) {
+    shell.traps.retain(|number, command| {
+        if command.is_empty() {
+            return true;
+        }
+        set_disposition(*number, None);
+        false
+    });
+}
+
+fn print_trap(out: &mut dyn Write, number: i32, command: &str) {
+    let quoted = command.replace('\'', "'\\''");
+    let _ = writeln!(out, "trap -- '{}' {}", quoted, trap_name(number));
+}
+
+pub fn list_signals(out: &mut dyn Write) {
+    for (index, name) in SIGNALS.iter().enumerate() {
+        let separator = if (index + 1) % 5 == 0 || index + 1 == SIGNALS.len() {
+            "\n"
+        } else {
+            "\t"
+        };
+        let _ = write!(out, "{:2}) {}{}", index + 1, name, separator);
+    }
+}
+
+pub fn trap_builtin(
+    args: &[String],
+    shell: &mut Shell,
+    out: &mut dyn Write,
+    err: &mut dyn Write,
+) -> i32 {
+    let mut print = false;
+    let mut index = 0;
+    while let Some(arg) = args.get(index) {
+        match arg.as_str() {
+            "--" => {
+                index += 1;
+                break;
+            }
+            "-l" => {
+                list_signals(out);
+                return 0;
+            }
+            "-p" => print = true,
+            _ if arg.starts_with('-') && arg.len() > 1 => {
+                let _ = writeln!(err, "trap: {}: invalid option", arg);
+                let _ = writeln!(err, "trap: usage: trap [-lp] [[arg] signal_spec ...]");
+                return 2;
+            }
+            _ => break,
+        }
+        index += 1;
+    }
+    let args = &args[index..];
+
+    if print || args.is_empty() {
+        let mut status = 0;
+        if args.is_empty() {
+            for (number, command) in &shell.traps {
+                print_trap(out, *number, command);
+            }
+        }
+        for spec in args {
+            match trap_number(spec) {
+                Some(number) => {
+                    if let Some(command) = shell.traps.get(&number) {
+                        print_trap(out, number, command);
+                    }
+                }
+                None => {
+                    let _ = writeln!(err, "trap: {}: invalid signal specification", spec);
+                    status = 1;
+                }
+            }
+        }
+        return status;
+    }
+
+    let (command, specs) = if args.len() == 1 || args[0].parse::<u32>().is_ok() {
+        (None, args)
+    } else if args[0] == "-" {
+        (None, &args[1..])
+    } else {
+        (Some(args[0].as_str()), &args[1..])
+    };
+
+    let mut status = 0;
+    for spec in specs {
+        let Some(number) = trap_number(spec) else {
+            let _ = writeln!(err, "trap: {}: invalid signal specification", spec);
+            status = 1;
+            continue;
+        };
+        set_disposition(number, command);
+        match command {
+            Some(command) => shell.traps.insert(number, command.to_string()),
+            None => shell.traps.remove(&number),
+        };
+    }
+    status
+}
@@ -0,0 +1,721 @@
+use crate::parser::{assignment_name, is_valid_name, parse_input};
+use crate::shell::Shell;
+use std::collections::BTreeMap;
+use std::env;
+use std::io::Write;
+
+#[derive(Clone)]
+pub enum Value {
+    Scalar(String),
+    Array(Vec<String>),
+}
+
+#[derive(Clone, Default)]
+pub struct Variable {
+    pub value: Option<Value>,
+    pub exported: bool,
+    pub readonly: bool,
+    pub integer: bool,
+    pub lowercase: bool,
+    pub uppercase: bool,
+}
+
+impl Variable {
+    fn attributes(&self) -> String {
+        let mut flags = String::new();
+        let array = matches!(self.value, Some(Value::Array(_)));
+        for (set, flag) in [
+            (array, 'a'),
+            (self.integer, 'i'),
+            (self.lowercase, 'l'),
+            (self.readonly, 'r'),
+            (self.uppercase, 'u'),
+            (self.exported, 'x'),
+        ] {
+            if set {
+                flags.push(flag);
+            }
+        }
+        if flags.is_empty() {
+            "--".to_string()
+        } else {
+            format!("-{}", flags)
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct Variables {
+    vars: BTreeMap<String, Variable>,
+    status: String,
+    background_pid: String,
+    positional: Vec<String>,
+}
+
+impl Variables {
+    pub fn from_env() -> Self {
+        let vars = env::vars()
+            .map(|(name, value)| {
+                let variable = Variable {
+                    value: Some(Value::Scalar(value)),
+                    exported: true,
+                    ..Variable::default()
+                };
+                (name, variable)
+            })
+            .collect();
+        Variables {
+            vars,
+            status: "0".to_string(),
+            background_pid: String::new(),
+            positional: env::args().take(1).collect(),
+        }
+    }
+
+    pub fn get(&self, name: &str) -> Option<&str> {
+        match name {
+            "?" => return Some(&self.status),
+            "!" => return Some(&self.background_pid),
+            _ => {}
+        }
+        match self.vars.get(name)?.value.as_ref()? {
+            Value::Scalar(value) => Some(value),
+            Value::Array(values) => values.first().map(|value| value.as_str()),
+        }
+    }
+
+    pub fn get_array(&self, name: &str) -> Option<&[String]> {
+        match self.vars.get(name)?.value.as_ref()? {
+            Value::Scalar(value) => Some(std::slice::from_ref(value)),
+            Value::Array(values) => Some(values),
+        }
+    }
+
+    pub fn positional(&self, index: usize) -> Option<&str> {
+        self.positional.get(index).map(|value| value.as_str())
+    }
+
+    pub fn arguments(&self) -> &[String] {
+        self.positional.get(1..).unwrap_or_default()
+    }
+
+    pub fn set_arguments(&mut self, arguments: Vec<String>) -> Vec<String> {
+        let name = self.positional.first().cloned().unwrap_or_default();
+        let previous = self.positional.split_off(1.min(self.positional.len()));
+        self.positional = std::iter::once(name).chain(arguments).collect();
+        previous
+    }
+
+    pub fn status(&self) -> i32 {
+        self.status.parse().unwrap_or(0)
+    }
+
+    pub fn set_status(&mut self, status: i32) {
+        self.status = status.to_string();
+    }
+
+    pub fn set_background_pid(&mut self, pid: i32) {
+        self.background_pid = pid.to_string();
+    }
+
+    pub fn names(&self) -> impl Iterator<Item = &String> {
+        self.vars
+            .iter()
+            .filter(|(_, var)| var.value.is_some())
+            .map(|(name, _)| name)
+    }
+
+    pub fn exported_names(&self) -> impl Iterator<Item = &String> {
+        self.vars
+            .iter()
+            .filter(|(_, var)| var.exported)
+            .map(|(name, _)| name)
+    }
+
+    pub fn exported(&self) -> impl Iterator<Item = (&String, &String)> {
+        self.vars
+            .iter()
+            .filter(|(_, var)| var.exported)
+            .filter_map(|(name, var)| match &var.value {
+                Some(Value::Scalar(value)) => Some((name, value)),
+                _ => None,
+            })
+    }
+
+    pub fn set(&mut self, name: &str, value: &str) -> Result<(), String> {
+        let var = self.vars.get(name).cloned().unwrap_or_default();
+        if var.readonly {
+            return Err(format!("{}: readonly variable", name));
+        }
+
+        let value = self.transform(&var, value);
+        let value = match var.value.clone() {
+            Some(Value::Array(mut values)) if !values.is_empty() => {
+                values[0] = value;
+                Value::Array(values)
+            }
+            Some(Value::Array(_)) => Value::Array(vec![value]),
+            _ => Value::Scalar(value),
+        };
+
+        self.vars.insert(
+            name.to_string(),
+            Variable {
+                value: Some(value),
+                ..var
+            },
+        );
+        Ok(())
+    }
+
+    pub fn set_array(&mut self, name: &str, values: Vec<String>) -> Result<(), String> {
+        let var = self.vars.get(name).cloned().unwrap_or_default();
+        if var.readonly {
+            return Err(format!("{}: readonly variable", name));
+        }
+
+        let values = values
+            .iter()
+            .map(|value| self.transform(&var, value))
+            .collect();
+        self.vars.insert(
+            name.to_string(),
+            Variable {
+                value: Some(Value::Array(values)),
+                ..var
+            },
+        );
+        Ok(())
+    }
+
+    pub fn set_value(&mut self, name: &str, value: Value) -> Result<(), String> {
+        match value {
+            Value::Scalar(value) => self.set(name, &value),
+            Value::Array(values) => self.set_array(name, values),
+        }
+    }
+
+    fn transform(&self, var: &Variable, value: &str) -> String {
+        if var.integer {
+            evaluate_arithmetic(value, self).to_string()
+        } else if var.lowercase {
+            value.to_lowercase()
+        } else if var.uppercase {
+            value.to_uppercase()
+        } else {
+            value.to_string()
+        }
+    }
+
+    pub fn unset(&mut self, name: &str) -> Result<(), String> {
+        match self.vars.get(name) {
+            Some(var) if var.readonly => Err(format!("{}: cannot unset: readonly variable", name)),
+            _ => {
+                self.vars.remove(name);
+                Ok(())
+            }
+        }
+    }
+
+    fn entry(&mut self, name: &str) -> &mut Variable {
+        self.vars.entry(name.to_string()).or_default()
+    }
+
+    pub fn snapshot(&self, name: &str) -> Option<Variable> {
+        self.vars.get(name).cloned()
+    }
+
+    pub fn restore(&mut self, name: &str, snapshot: Option<Variable>) {
+        match snapshot {
+            Some(var) => self.vars.insert(name.to_string(), var),
+            None => self.vars.remove(name),
+        };
+    }
+}
+
+fn assign(variables: &mut Variables, word: &str) -> Result<(), String> {
+    let (name, value) = word.split_once('=').unwrap_or((word, ""));
+    match value.strip_prefix('(').and_then(|v| v.strip_suffix(')')) {
+        Some(elements) => variables.set_array(name, parse_input(elements)),
+        None => variables.set(name, value),
+    }
+}
+
+pub fn split_assignments(words: &[String]) -> usize {
+    words
+        .iter()
+        .take_while(|word| assignment_name(word).is_some())
+        .count()
+}
+
+fn arithmetic_operand(token: &str, variables: &Variables) -> i64 {
+    if let Ok(number) = token.parse() {
+        return number;
+    }
+    variables
+        .get(token)
+        .and_then(|value| value.trim().parse().ok())
+        .unwrap_or(0)
+}
+
+fn arithmetic_tokens(expression: &str) -> Vec<String> {
+    let mut tokens = Vec::new();
+    let mut current = String::new();
+    for c in expression.chars() {
+        if c.is_ascii_alphanumeric() || c == '_' {
+            current.push(c);
+            continue;
+        }
+        if !current.is_empty() {
+            tokens.push(std::mem::take(&mut current));
+        }
+        if !c.is_whitespace() {
+            tokens.push(c.to_string());
+        }
+    }
+    if !current.is_empty() {
+        tokens.push(current);
+    }
+    tokens
+}
+
+fn arithmetic_expression(tokens: &[String], pos: &mut usize, vars: &Variables, level: u8) -> i64 {
+    if level == 2 {
+        return match tokens.get(*pos).map(|s| s.as_str()) {
+            Some("-") => {
+                *pos += 1;
+                arithmetic_expression(tokens, pos, vars, 2).wrapping_neg()
+            }
+            Some("+") => {
+                *pos += 1;
+                arithmetic_expression(tokens, pos, vars, 2)
+            }
+            Some("(") => {
+                *pos += 1;
+                let value = arithmetic_expression(tokens, pos, vars, 0);
+                *pos += 1;
+                value
+            }
+            Some(token) => {
+                *pos += 1;
+                arithmetic_operand(token, vars)
+            }
+            None => 0,
+        };
+    }
+
+    let mut value = arithmetic_expression(tokens, pos, vars, level + 1);
+    while let Some(op) = tokens.get(*pos).map(|s| s.as_str()) {
+        let applies = match level {
+            0 => op == "+" || op == "-",
+            _ => op == "*" || op == "/" || op == "%",
+        };
+        if !applies {
+            break;
+        }
+        *pos += 1;
+        let rhs = arithmetic_expression(tokens, pos, vars, level + 1);
+        value = match op {
+            "+" => value.wrapping_add(rhs),
+            "-" => value.wrapping_sub(rhs),
+            "*" => value.wrapping_mul(rhs),
+            "/" => value.checked_div(rhs).unwrap_or(0),
+            _ => value.checked_rem(rhs).unwrap_or(0),
+        };
+    }
+    value
+}
+
+pub fn evaluate_arithmetic(expression: &str, variables: &Variables) -> i64 {
+    let tokens = arithmetic_tokens(expression);
+    arithmetic_expression(&tokens, &mut 0, variables, 0)
+}
+
+fn quote_value(value: &str) -> String {
+    let mut quoted = String::from("\"");
+    for c in value.chars() {
+        if matches!(c, '"' | '\\' | '$' | '`') {
+            quoted.push('\\');
+        }
+        quoted.push(c);
+    }
+    quoted.push('"');
+    quoted
+}
+
+pub fn print_variables(variables: &Variables, out: &mut dyn Write) {
+    for (name, var) in &variables.vars {
+        let _ = match &var.value {
+            Some(Value::Array(values)) => {
+                let elements: Vec<String> = values
+                    .iter()
+                    .enumerate()
+                    .map(|(index, value)| format!("[{}]={}", index, quote_value(value)))
+                    .collect();
+                writeln!(out, "{}=({})", name, elements.join(" "))
+            }
+            Some(Value::Scalar(value)) => writeln!(out, "{}={}", name, quote_value(value)),
+            None => Ok(()),
+        };
+    }
+}
+
+fn print_declaration(out: &mut dyn Write, name: &str, var: &Variable) {
+    match &var.value {
+        Some(Value::Array(values)) => {
+            let elements: Vec<String> = values
+                .iter()
+                .enumerate()
+                .map(|(index, value)| format!("[{}]={}", index, quote_value(value)))
+                .collect();
+            let _ = writeln!(
+                out,
+                "declare {} {}=({})",
+                var.attributes(),
+                name,
+                elements.join(" ")
+            );
+        }
+        Some(Value::Scalar(value)) => {
+            let _ = writeln!(
+                out,
+                "declare {} {}={}",
+                var.attributes(),
+                name,
+                quote_value(value)
+            );
+        }
+        None => {
+            let _ = writeln!(out, "declare {} {}", var.attributes(), name);
+        }
+    }
+}
+
+#[derive(Default, Clone, Copy)]
+struct AttributeChanges {
+    array: Option<bool>,
+    exported: Option<bool>,
+    readonly: Option<bool>,
+    integer: Option<bool>,
+    lowercase: Option<bool>,
+    uppercase: Option<bool>,
+}
+
+impl AttributeChanges {
+    fn apply(&self, var: &mut Variable) {
+        if self.array == Some(true) {
+            var.value = match var.value.take() {
+                Some(Value::Scalar(value)) => Some(Value::Array(vec![value])),
+                Some(array) => Some(array),
+                None => Some(Value::Array(Vec::new())),
+            };
+        }
+        if let Some(exported) = self.exported {
+            var.exported = exported;
+        }
+        if let Some(integer) = self.integer {
+            var.integer = integer;
+        }
+        if let Some(lowercase) = self.lowercase {
+            var.lowercase = lowercase;
+            var.uppercase &= !lowercase;
+        }
+        if let Some(uppercase) = self.uppercase {
+            var.uppercase = uppercase;
+            var.lowercase &= !uppercase;
+        }
+    }
+
+    fn matches(&self, var: &Variable) -> bool {
+        let array = matches!(var.value, Some(Value::Array(_)));
+        self.array.is_none_or(|set| array == set)
+            && self.exported.is_none_or(|set| var.exported == set)
+            && self.readonly.is_none_or(|set| var.readonly == set)
+            && self.integer.is_none_or(|set| var.integer == set)
+            && self.lowercase.is_none_or(|set| var.lowercase == set)
+            && self.uppercase.is_none_or(|set| var.uppercase == set)
+    }
+}
+
+fn declare_names(
+    builtin: &str,
+    names: &[&String],
+    changes: AttributeChanges,
+    variables: &mut Variables,
+    err: &mut dyn Write,
+) -> i32 {
+    let mut status = 0;
+    for word in names {
+        let name = word.split_once('=').map_or(word.as_str(), |(name, _)| name);
+        if !is_valid_name(name) {
+            let _ = writeln!(err, "{}: `{}': not a valid identifier", builtin, word);
+            status = 1;
+            continue;
+        }
+
+        let var = variables.entry(name);
+        if var.readonly && (changes.readonly == Some(false) || word.contains('=')) {
+            let _ = writeln!(err, "{}: {}: readonly variable", builtin, name);
+            status = 1;
+            continue;
+        }
+        if changes.array == Some(false) && matches!(var.value, Some(Value::Array(_))) {
+            let _ = writeln!(
+                err,
+                "{}: {}: cannot destroy array variables in this way",
+                builtin, name
+            );
+            status = 1;
+            continue;
+        }
+        changes.apply(var);
+
+        if word.contains('=')
+            && let Err(e) = assign(variables, word)
+        {
+            let _ = writeln!(err, "{}: {}", builtin, e);
+            status = 1;
+            continue;
+        }
+        if changes.readonly == Some(true) {
+            variables.entry(name).readonly = true;
+        }
+    }
+    status
+}
+
+pub fn declare_builtin(
+    args: &[String],
+    variables: &mut Variables,
+    out: &mut dyn Write,
+    err: &mut dyn Write,
+) -> i32 {
+    let mut changes = AttributeChanges::default();
+    let mut print = false;
+    let mut names: Vec<&String> = Vec::new();
+
+    for arg in args {
+        let enable = arg.starts_with('-');
+        if !names.is_empty() || !(enable || arg.starts_with('+')) || arg.len() == 1 {
+            names.push(arg);
+            continue;
+        }
+        for flag in arg[1..].chars() {
+            match flag {
+                'x' => changes.exported = Some(enable),
+                'r' => changes.readonly = Some(enable),
+                'i' => changes.integer = Some(enable),
+                'l' => changes.lowercase = Some(enable),
+                'u' => changes.uppercase = Some(enable),
+                'p' => print = true,
+                'g' => {}
+                'f' | 'F' => return 0,
+                'a' => changes.array = Some(enable),
+                'A' => {
+                    let _ = writeln!(err, "declare: -A: associative arrays are not supported");
+                    return 1;
+                }
+                _ => {
+                    let _ = writeln!(err, "declare: {}{}: invalid option", &arg[..1], flag);
+                    let _ = writeln!(
+                        err,
+                        "declare: usage: declare [-ailrux] [-p] [name[=value] ...]"
+                    );
+                    return 2;
+                }
+            }
+        }
+    }
+
+    if print || names.is_empty() {
+        if names.is_empty() {
+            for (name, var) in &variables.vars {
+                if changes.matches(var) {
+                    print_declaration(out, name, var);
+                }
+            }
+            return 0;
+        }
+        let mut status = 0;
+        for name in names {
+            match variables.vars.get(name.as_str()) {
+                Some(var) => print_declaration(out, name, var),
+                None => {
+                    let _ = writeln!(err, "declare: {}: not found", name);
+                    status = 1;
+                }
+            }
+        }
+        return status;
+    }
+
+    declare_names("declare", &names, changes, variables, err)
+}
+
+pub fn export_builtin(
+    args: &[String],
+    variables: &mut Variables,
+    out: &mut dyn Write,
+    err: &mut dyn Write,
+) -> i32 {
+    let mut unexport = false;
+    let mut names: Vec<&String> = Vec::new();
+
+    for arg in args {
+        if !names.is_empty() || !arg.starts_with('-') || arg.len() == 1 {
+            names.push(arg);
+            continue;
+        }
+        for flag in arg[1..].chars() {
+            match flag {
+                'n' => unexport = true,
+                'p' => {}
+                'f' => {
+                    let _ = writeln!(err, "export: -f: functions are not supported");
+                    return 1;
+                }
+                _ => {
+                    let _ = writeln!(err, "export: -{}: invalid option", flag);
+                    let _ = writeln!(
+                        err,
+                        "export: usage: export [-n] [name[=value] ...] or export -p"
+                    );
+                    return 2;
+                }
+            }
+        }
+    }
+
+    if names.is_empty() {
+        for (name, var) in variables.vars.iter().filter(|(_, var)| var.exported) {
+            print_declaration(out, name, var);
+        }
+        return 0;
+    }
+
+    let changes = AttributeChanges {
+        exported: Some(!unexport),
+        ..AttributeChanges::default()
+    };
+    declare_names("export", &names, changes, variables, err)
+}
+
+pub fn readonly_builtin(
+    args: &[String],
+    variables: &mut Variables,
+    out: &mut dyn Write,
+    err: &mut dyn Write,
+) -> i32 {
+    let mut changes = AttributeChanges {
+        readonly: Some(true),
+        ..AttributeChanges::default()
+    };
+    let mut names: Vec<&String> = Vec::new();
+
+    for arg in args {
+        if !names.is_empty() || !arg.starts_with('-') || arg.len() == 1 {
+            names.push(arg);
+            continue;
+        }
+        for flag in arg[1..].chars() {
+            match flag {
+                'p' => {}
+                'a' => changes.array = Some(true),
+                'A' | 'f' => {
+                    let _ = writeln!(err, "readonly: -{}: not supported", flag);
+                    return 1;
+                }
+                _ => {
+                    let _ = writeln!(err, "readonly: -{}: invalid option", flag);
+                    let _ = writeln!(
+                        err,
+                        "readonly: usage: readonly [name[=value] ...] or readonly -p"
+                    );
+                    return 2;
+                }
+            }
+        }
+    }
+
+    if names.is_empty() {
+        for (name, var) in variables.vars.iter().filter(|(_, var)| var.readonly) {
+            print_declaration(out, name, var);
+        }
+        return 0;
+    }
+
+    declare_names("readonly", &names, changes, variables, err)
+}
+
+pub fn unset_builtin(args: &[String], shell: &mut Shell, err: &mut dyn Write) -> i32 {
+    let mut functions = false;
+    let mut names: Vec<&String> = Vec::new();
+
+    for arg in args {
+        if !names.is_empty() || !arg.starts_with('-') || arg.len() == 1 {
+            names.push(arg);
+            continue;
+        }
+        for flag in arg[1..].chars() {
+            match flag {
+                'v' => functions = false,
+                'f' => functions = true,
+                _ => {
+                    let _ = writeln!(err, "unset: -{}: invalid option", flag);
+                    let _ = writeln!(err, "unset: usage: unset [-f] [-v] [name ...]");
+                    return 2;
+                }
+            }
+        }
+    }
+
+    if functions {
+        for name in names {
+            shell.functions.remove(name.as_str());
+        }
+        return 0;
+    }
+
+    let mut status = 0;
+    for name in names {
+        if !is_valid_name(name) {
+            let _ = writeln!(err, "unset: `{}': not a valid identifier", name);
+            status = 1;
+        } else if let Err(e) = shell.variables.unset(name) {
+            let _ = writeln!(err, "unset: {}", e);
+            status = 1;
+        }
+    }
+    status
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn arithmetic_wraps_instead_of_overflowing() {
+        let variables = Variables::default();
+        assert_eq!(
+            evaluate_arithmetic("-(-9223372036854775807-1)", &variables),
+            i64::MIN
+        );
+        assert_eq!(
+            evaluate_arithmetic("9223372036854775807+1", &variables),
+            i64::MIN
+        );
+        assert_eq!(evaluate_arithmetic("7/0", &variables), 0);
+    }
+
+    #[test]
+    fn readonly_variables_reject_assignment() {
+        let mut variables = Variables::default();
+        assign(&mut variables, "r=1").unwrap();
+        variables.entry("r").readonly = true;
+        assert_eq!(
+            variables.set("r", "2"),
+            Err("r: readonly variable".to_string())
+        );
+        assert_eq!(variables.get("r"), Some("1"));
+    }
+}